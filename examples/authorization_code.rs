@@ -0,0 +1,30 @@
+use keycloak_oauth::client::{
+    AppConfigBuilder, ClientConfiguration, EnvironmentCredential, KeycloakClient,
+    WithAuthorizationCode,
+};
+use oauth2::TokenResponse;
+
+#[tokio::main]
+async fn main() -> anyhow::Result<()> {
+    let auth_code_credential = EnvironmentCredential::authorization_code_credential()?;
+    let config = ClientConfiguration::from_env();
+    let app_config = AppConfigBuilder::new("waves-ui")
+        .auth_url(config.auth_url.clone().expect("should have auth_url"))
+        .with_authorization_code_credentials(auth_code_credential)
+        .build()
+        .expect("app config");
+
+    let keycloak_client = KeycloakClient::<WithAuthorizationCode>::try_from(app_config)?;
+    let token = match keycloak_client.verify_and_refresh_access_token().await {
+        Ok(token) => token,
+        Err(_) => keycloak_client
+            .authenticate()
+            .await?
+            .access_token()
+            .secret()
+            .clone(),
+    };
+    println!("{:#?}", token);
+
+    Ok(())
+}
@@ -0,0 +1,21 @@
+use keycloak_oauth::client::{
+    AppConfigBuilder, ClientConfiguration, EnvironmentCredential, KeycloakClient,
+    WithClientCredentials,
+};
+
+#[tokio::main]
+async fn main() -> anyhow::Result<()> {
+    let client_credentials = EnvironmentCredential::client_credentials()?;
+    let config = ClientConfiguration::from_env();
+    let app_config = AppConfigBuilder::new("waves-worker")
+        .auth_url(config.auth_url.clone().expect("should have auth_url"))
+        .with_client_credentials(client_credentials)
+        .build()
+        .expect("app config");
+
+    let keycloak_client = KeycloakClient::<WithClientCredentials>::try_from(app_config)?;
+    let token = keycloak_client.access_token().await?;
+    println!("{:#?}", token);
+
+    Ok(())
+}
@@ -0,0 +1,30 @@
+use keycloak_oauth::client::{
+    AppConfigBuilder, ClientConfiguration, EnvironmentCredential, KeycloakClient,
+    WithDeviceCredentials,
+};
+use oauth2::TokenResponse;
+
+#[tokio::main]
+async fn main() -> anyhow::Result<()> {
+    let device_credential = EnvironmentCredential::device_credential()?;
+    let config = ClientConfiguration::discover("http://localhost:8080", "waves").await?;
+    let app_config = AppConfigBuilder::new("waves-ui")
+        .configuration(config)
+        .with_device_code_credentials(device_credential)
+        .build()
+        .expect("app config");
+
+    let keycloak_client = KeycloakClient::<WithDeviceCredentials>::try_from(app_config)?;
+    let token = match keycloak_client.verify_and_refresh_access_token().await {
+        Ok(token) => token,
+        Err(_) => keycloak_client
+            .authenticate()
+            .await?
+            .access_token()
+            .secret()
+            .clone(),
+    };
+    println!("{:#?}", token);
+
+    Ok(())
+}
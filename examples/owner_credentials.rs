@@ -1,6 +1,6 @@
 use keycloak_oauth::client::{
-    AppConfigBuilder, ClientConfiguration, ClientError, EnvironmentCredential, Flow,
-    KeycloakClient, WithOwnerCredentials,
+    AppConfigBuilder, ClientConfiguration, ClientError, EnvironmentCredential, KeycloakClient,
+    WithOwnerCredentials,
 };
 use oauth2::TokenResponse;
 
@@ -16,7 +16,7 @@ async fn main() -> Result<(), ClientError> {
         .build()
         .expect("app config to build");
 
-    let keycloak_client = KeycloakClient::<WithOwnerCredentials>::from(app_config);
+    let keycloak_client = KeycloakClient::<WithOwnerCredentials>::try_from(app_config)?;
 
     let _token = match keycloak_client.verify_and_refresh_access_token().await {
         Ok(token) => token,
@@ -1,6 +1,6 @@
 use keycloak_oauth::client::{
     AppConfigBuilder, ClientConfiguration, EnvironmentCredential, KeycloakClient,
-    WithDeviceCredentials, WithOwnerCredentials,
+    WithDeviceCredentials,
 };
 use oauth2::TokenResponse;
 
@@ -15,7 +15,7 @@ async fn main() -> anyhow::Result<()> {
         .build()
         .expect("app config");
 
-    let keycloak_client = KeycloakClient::<WithDeviceCredentials>::from(app_config);
+    let keycloak_client = KeycloakClient::<WithDeviceCredentials>::try_from(app_config)?;
     let token = match keycloak_client.verify_and_refresh_access_token().await {
         Ok(token) => token,
         Err(_) => keycloak_client
@@ -0,0 +1,38 @@
+use keycloak_oauth::admin::{KeycloakAdmin, RealmRepresentation, ReconcileOptions};
+use keycloak_oauth::client::{
+    AppConfigBuilder, ClientConfiguration, EnvironmentCredential, KeycloakClient,
+    WithClientCredentials,
+};
+
+/// cargo run --example reconcile_realm -- realm.json [--apply] [--prune]
+#[tokio::main]
+async fn main() -> anyhow::Result<()> {
+    let args: Vec<String> = std::env::args().skip(1).collect();
+    let path = args
+        .iter()
+        .find(|arg| !arg.starts_with("--"))
+        .expect("path of the realm JSON");
+    let options = ReconcileOptions::new()
+        .with_dry_run(!args.iter().any(|arg| arg == "--apply"))
+        .with_prune(args.iter().any(|arg| arg == "--prune"));
+
+    let client_credentials = EnvironmentCredential::client_credentials()?;
+    let config = ClientConfiguration::from_env();
+    let app_config = AppConfigBuilder::new("realm-admin")
+        .auth_url(config.auth_url.clone().expect("should have auth_url"))
+        .with_client_credentials(client_credentials)
+        .build()
+        .expect("app config");
+    let admin = KeycloakAdmin::from_client(KeycloakClient::<WithClientCredentials>::try_from(
+        app_config,
+    )?)?;
+
+    let desired = RealmRepresentation::from_json(&std::fs::read_to_string(path)?)?;
+    let plan = admin.reconcile_realm(&desired, &options).await?;
+    print!("{}", plan);
+    if options.dry_run && !plan.is_empty() {
+        println!("Dry run, pass --apply to make these changes");
+    }
+
+    Ok(())
+}
@@ -0,0 +1,114 @@
+use reqwest::Method;
+
+use crate::client::ClientError;
+
+use super::{ClientScopeRepresentation, KeycloakAdmin, ProtocolMapperRepresentation};
+
+/// What protocol mappers are attached to.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum MapperOwner<'a> {
+    /// A client, by UUID
+    Client(&'a str),
+    /// A client scope, by id
+    ClientScope(&'a str),
+}
+
+impl<'a> MapperOwner<'a> {
+    fn segments(&self) -> [&'a str; 4] {
+        match self {
+            MapperOwner::Client(id) => ["clients", id, "protocol-mappers", "models"],
+            MapperOwner::ClientScope(id) => ["client-scopes", id, "protocol-mappers", "models"],
+        }
+    }
+}
+
+impl KeycloakAdmin {
+    pub async fn client_scopes(&self) -> Result<Vec<ClientScopeRepresentation>, ClientError> {
+        self.get(&["client-scopes"], &()).await
+    }
+
+    pub async fn client_scope(
+        &self,
+        id: &str,
+    ) -> Result<Option<ClientScopeRepresentation>, ClientError> {
+        self.find(&["client-scopes", id]).await
+    }
+
+    /// Client scopes have no search endpoint, so this lists them all and filters by name.
+    pub async fn client_scope_by_name(
+        &self,
+        name: &str,
+    ) -> Result<Option<ClientScopeRepresentation>, ClientError> {
+        Ok(self
+            .client_scopes()
+            .await?
+            .into_iter()
+            .find(|scope| scope.name.as_deref() == Some(name)))
+    }
+
+    /// Creates the client scope and returns its id.
+    pub async fn create_client_scope(
+        &self,
+        scope: &ClientScopeRepresentation,
+    ) -> Result<String, ClientError> {
+        self.create(&["client-scopes"], scope).await
+    }
+
+    pub async fn update_client_scope(
+        &self,
+        id: &str,
+        scope: &ClientScopeRepresentation,
+    ) -> Result<(), ClientError> {
+        self.send_json(Method::PUT, &["client-scopes", id], scope)
+            .await?;
+        Ok(())
+    }
+
+    pub async fn delete_client_scope(&self, id: &str) -> Result<(), ClientError> {
+        self.delete(&["client-scopes", id]).await
+    }
+
+    pub async fn protocol_mappers(
+        &self,
+        owner: MapperOwner<'_>,
+    ) -> Result<Vec<ProtocolMapperRepresentation>, ClientError> {
+        self.get(&owner.segments(), &()).await
+    }
+
+    pub async fn protocol_mapper(
+        &self,
+        owner: MapperOwner<'_>,
+        id: &str,
+    ) -> Result<Option<ProtocolMapperRepresentation>, ClientError> {
+        self.find(&[&owner.segments()[..], &[id]].concat()).await
+    }
+
+    /// Adds the mapper and returns its id.
+    pub async fn create_protocol_mapper(
+        &self,
+        owner: MapperOwner<'_>,
+        mapper: &ProtocolMapperRepresentation,
+    ) -> Result<String, ClientError> {
+        self.create(&owner.segments(), mapper).await
+    }
+
+    /// Replaces the mapper. Keycloak expects `mapper.id` to be set to `id`.
+    pub async fn update_protocol_mapper(
+        &self,
+        owner: MapperOwner<'_>,
+        id: &str,
+        mapper: &ProtocolMapperRepresentation,
+    ) -> Result<(), ClientError> {
+        let segments = [&owner.segments()[..], &[id]].concat();
+        self.send_json(Method::PUT, &segments, mapper).await?;
+        Ok(())
+    }
+
+    pub async fn delete_protocol_mapper(
+        &self,
+        owner: MapperOwner<'_>,
+        id: &str,
+    ) -> Result<(), ClientError> {
+        self.delete(&[&owner.segments()[..], &[id]].concat()).await
+    }
+}
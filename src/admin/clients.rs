@@ -0,0 +1,165 @@
+use reqwest::Method;
+use serde::Serialize;
+
+use crate::client::ClientError;
+
+use super::{
+    ClientRepresentation, ClientScopeRepresentation, CredentialRepresentation, KeycloakAdmin,
+    UserRepresentation,
+};
+
+/// Search and paging parameters of `KeycloakAdmin::clients`.
+#[serde_with::skip_serializing_none]
+#[derive(Debug, Clone, Default, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ClientQuery {
+    pub client_id: Option<String>,
+    /// Match `client_id` as a substring instead of exactly
+    pub search: Option<bool>,
+    pub first: Option<u32>,
+    pub max: Option<u32>,
+}
+
+impl ClientQuery {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Only the client with exactly this client id.
+    pub fn client_id(mut self, client_id: impl Into<String>) -> Self {
+        self.client_id = Some(client_id.into());
+        self.search = None;
+        self
+    }
+
+    /// Clients whose client id contains `client_id`.
+    pub fn search(mut self, client_id: impl Into<String>) -> Self {
+        self.client_id = Some(client_id.into());
+        self.search = Some(true);
+        self
+    }
+
+    pub fn page(mut self, first: u32, max: u32) -> Self {
+        self.first = Some(first);
+        self.max = Some(max);
+        self
+    }
+}
+
+/// Whether a client scope is added to every token of a client or only when requested.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ScopeKind {
+    Default,
+    Optional,
+}
+
+impl ScopeKind {
+    fn segment(&self) -> &'static str {
+        match self {
+            ScopeKind::Default => "default-client-scopes",
+            ScopeKind::Optional => "optional-client-scopes",
+        }
+    }
+}
+
+impl KeycloakAdmin {
+    pub async fn clients(
+        &self,
+        query: &ClientQuery,
+    ) -> Result<Vec<ClientRepresentation>, ClientError> {
+        self.get(&["clients"], query).await
+    }
+
+    /// Returns the client with UUID `id`.
+    pub async fn client(&self, id: &str) -> Result<Option<ClientRepresentation>, ClientError> {
+        self.find(&["clients", id]).await
+    }
+
+    pub async fn client_by_client_id(
+        &self,
+        client_id: &str,
+    ) -> Result<Option<ClientRepresentation>, ClientError> {
+        let query = ClientQuery::new().client_id(client_id);
+        Ok(self.clients(&query).await?.into_iter().next())
+    }
+
+    /// The UUID of the client with `client_id`, which the client endpoints take.
+    pub async fn client_uuid(&self, client_id: &str) -> Result<Option<String>, ClientError> {
+        Ok(self
+            .client_by_client_id(client_id)
+            .await?
+            .and_then(|client| client.id))
+    }
+
+    /// Creates the client and returns its UUID.
+    pub async fn create_client(
+        &self,
+        client: &ClientRepresentation,
+    ) -> Result<String, ClientError> {
+        self.create(&["clients"], client).await
+    }
+
+    pub async fn update_client(
+        &self,
+        id: &str,
+        client: &ClientRepresentation,
+    ) -> Result<(), ClientError> {
+        self.send_json(Method::PUT, &["clients", id], client)
+            .await?;
+        Ok(())
+    }
+
+    pub async fn delete_client(&self, id: &str) -> Result<(), ClientError> {
+        self.delete(&["clients", id]).await
+    }
+
+    pub async fn client_secret(&self, id: &str) -> Result<CredentialRepresentation, ClientError> {
+        self.get(&["clients", id, "client-secret"], &()).await
+    }
+
+    /// Replaces the client's secret and returns the new one. The old secret stops working.
+    pub async fn regenerate_client_secret(
+        &self,
+        id: &str,
+    ) -> Result<CredentialRepresentation, ClientError> {
+        let response = self
+            .send_query(Method::POST, &["clients", id, "client-secret"], &())
+            .await?;
+        Ok(response.json().await?)
+    }
+
+    /// The user acting for a client with service accounts enabled.
+    pub async fn service_account_user(&self, id: &str) -> Result<UserRepresentation, ClientError> {
+        self.get(&["clients", id, "service-account-user"], &())
+            .await
+    }
+
+    pub async fn client_scopes_of_client(
+        &self,
+        id: &str,
+        kind: ScopeKind,
+    ) -> Result<Vec<ClientScopeRepresentation>, ClientError> {
+        self.get(&["clients", id, kind.segment()], &()).await
+    }
+
+    pub async fn add_client_scope_to_client(
+        &self,
+        id: &str,
+        scope_id: &str,
+        kind: ScopeKind,
+    ) -> Result<(), ClientError> {
+        self.send_query(Method::PUT, &["clients", id, kind.segment(), scope_id], &())
+            .await?;
+        Ok(())
+    }
+
+    pub async fn remove_client_scope_from_client(
+        &self,
+        id: &str,
+        scope_id: &str,
+        kind: ScopeKind,
+    ) -> Result<(), ClientError> {
+        self.delete(&["clients", id, kind.segment(), scope_id])
+            .await
+    }
+}
@@ -0,0 +1,117 @@
+use reqwest::Method;
+use serde::Serialize;
+
+use crate::client::ClientError;
+
+use super::{GroupRepresentation, KeycloakAdmin, Page, UserRepresentation};
+
+/// Search and paging parameters of `KeycloakAdmin::groups`.
+#[serde_with::skip_serializing_none]
+#[derive(Debug, Clone, Default, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct GroupQuery {
+    /// Matched against group names, anywhere in the hierarchy
+    pub search: Option<String>,
+    /// Attribute filters, as `key:value` pairs separated by spaces
+    pub q: Option<String>,
+    pub exact: Option<bool>,
+    pub brief_representation: Option<bool>,
+    pub first: Option<u32>,
+    pub max: Option<u32>,
+}
+
+impl GroupQuery {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn search(mut self, search: impl Into<String>) -> Self {
+        self.search = Some(search.into());
+        self
+    }
+
+    pub fn exact(mut self, exact: bool) -> Self {
+        self.exact = Some(exact);
+        self
+    }
+
+    pub fn brief(mut self, brief: bool) -> Self {
+        self.brief_representation = Some(brief);
+        self
+    }
+
+    pub fn page(mut self, first: u32, max: u32) -> Self {
+        self.first = Some(first);
+        self.max = Some(max);
+        self
+    }
+}
+
+impl KeycloakAdmin {
+    /// Lists the top level groups (or, with `search`, the matching groups and their parents).
+    pub async fn groups(
+        &self,
+        query: &GroupQuery,
+    ) -> Result<Vec<GroupRepresentation>, ClientError> {
+        self.get(&["groups"], query).await
+    }
+
+    pub async fn group(&self, id: &str) -> Result<Option<GroupRepresentation>, ClientError> {
+        self.find(&["groups", id]).await
+    }
+
+    /// Looks a group up by its path, such as `/engineering/backend`.
+    pub async fn group_by_path(
+        &self,
+        path: &str,
+    ) -> Result<Option<GroupRepresentation>, ClientError> {
+        let path = path.trim_start_matches('/');
+        let mut segments = vec!["group-by-path"];
+        segments.extend(path.split('/'));
+        self.find(&segments).await
+    }
+
+    /// Creates a top level group and returns its id.
+    pub async fn create_group(&self, group: &GroupRepresentation) -> Result<String, ClientError> {
+        self.create(&["groups"], group).await
+    }
+
+    /// Creates a subgroup of `parent_id` and returns its id.
+    pub async fn create_subgroup(
+        &self,
+        parent_id: &str,
+        group: &GroupRepresentation,
+    ) -> Result<String, ClientError> {
+        self.create(&["groups", parent_id, "children"], group).await
+    }
+
+    pub async fn subgroups(
+        &self,
+        parent_id: &str,
+        page: Page,
+    ) -> Result<Vec<GroupRepresentation>, ClientError> {
+        self.get(&["groups", parent_id, "children"], &page).await
+    }
+
+    pub async fn update_group(
+        &self,
+        id: &str,
+        group: &GroupRepresentation,
+    ) -> Result<(), ClientError> {
+        self.send_json(Method::PUT, &["groups", id], group).await?;
+        Ok(())
+    }
+
+    /// Deletes the group along with its subgroups.
+    pub async fn delete_group(&self, id: &str) -> Result<(), ClientError> {
+        self.delete(&["groups", id]).await
+    }
+
+    pub async fn group_members(
+        &self,
+        id: &str,
+        page: Page,
+    ) -> Result<Vec<UserRepresentation>, ClientError> {
+        self.get(&["groups", id, "members"], &page).await
+    }
+}
@@ -0,0 +1,50 @@
+use reqwest::Method;
+
+use crate::client::ClientError;
+
+use super::{IdentityProviderRepresentation, KeycloakAdmin};
+
+impl KeycloakAdmin {
+    pub async fn identity_providers(
+        &self,
+    ) -> Result<Vec<IdentityProviderRepresentation>, ClientError> {
+        self.get(&["identity-provider", "instances"], &()).await
+    }
+
+    pub async fn identity_provider(
+        &self,
+        alias: &str,
+    ) -> Result<Option<IdentityProviderRepresentation>, ClientError> {
+        self.find(&["identity-provider", "instances", alias]).await
+    }
+
+    pub async fn create_identity_provider(
+        &self,
+        provider: &IdentityProviderRepresentation,
+    ) -> Result<(), ClientError> {
+        self.send_json(Method::POST, &["identity-provider", "instances"], provider)
+            .await?;
+        Ok(())
+    }
+
+    /// Replaces the identity provider. Unlike most updates, unset fields are reset to their
+    /// defaults, so start from `identity_provider`.
+    pub async fn update_identity_provider(
+        &self,
+        alias: &str,
+        provider: &IdentityProviderRepresentation,
+    ) -> Result<(), ClientError> {
+        self.send_json(
+            Method::PUT,
+            &["identity-provider", "instances", alias],
+            provider,
+        )
+        .await?;
+        Ok(())
+    }
+
+    pub async fn delete_identity_provider(&self, alias: &str) -> Result<(), ClientError> {
+        self.delete(&["identity-provider", "instances", alias])
+            .await
+    }
+}
@@ -0,0 +1,213 @@
+use std::sync::Arc;
+
+use reqwest::{header, Method, RequestBuilder, Response, Url};
+use serde::{de::DeserializeOwned, Serialize};
+
+use crate::client::{AccessTokenProvider, ClientError, KeycloakClient};
+
+/// Client for the Keycloak Admin REST API of one realm.
+///
+/// Requests are authenticated with the access token of a `KeycloakClient` (or any other
+/// `AccessTokenProvider`), so the account behind it needs the matching `realm-management` roles.
+///
+/// ```no_run
+/// # async fn run(client: keycloak_oauth::client::KeycloakClient<keycloak_oauth::client::WithClientCredentials>) -> Result<(), keycloak_oauth::client::ClientError> {
+/// use keycloak_oauth::admin::{KeycloakAdmin, UserQuery};
+///
+/// let admin = KeycloakAdmin::from_client(client)?;
+/// let users = admin.users(&UserQuery::new().search("alice").page(0, 20)).await?;
+/// # Ok(())
+/// # }
+/// ```
+#[derive(Clone)]
+pub struct KeycloakAdmin {
+    tokens: Arc<dyn AccessTokenProvider>,
+    http: reqwest::Client,
+    server_url: Url,
+    realm: String,
+}
+
+impl KeycloakAdmin {
+    /// Manages `realm` on the Keycloak server at `server_url` (without `/admin` or `/realms`).
+    pub fn new(
+        tokens: impl AccessTokenProvider + 'static,
+        server_url: &str,
+        realm: impl Into<String>,
+    ) -> Result<Self, ClientError> {
+        let server_url = Url::parse(server_url)
+            .map_err(|e| ClientError::InvalidUrlError(format!("{}: {}", server_url, e)))?;
+        if server_url.cannot_be_a_base() {
+            return Err(ClientError::InvalidUrlError(server_url.to_string()));
+        }
+
+        Ok(Self {
+            tokens: Arc::new(tokens),
+            http: reqwest::Client::new(),
+            server_url,
+            realm: realm.into(),
+        })
+    }
+
+    /// Manages the realm the client authenticates against, taking the server and realm from the
+    /// client's token URL (`<server>/realms/<realm>/protocol/openid-connect/token`).
+    pub fn from_client<C>(client: KeycloakClient<C>) -> Result<Self, ClientError>
+    where
+        KeycloakClient<C>: AccessTokenProvider + 'static,
+    {
+        let token_url = client
+            .inner
+            .token_url()
+            .map(|url| url.as_str().to_string())
+            .ok_or_else(|| ClientError::InvalidUrlError("no token URL configured".into()))?;
+        let (server_url, rest) = token_url
+            .split_once("/realms/")
+            .ok_or_else(|| ClientError::InvalidUrlError(token_url.clone()))?;
+        let realm = rest.split('/').next().unwrap_or_default().to_string();
+
+        Self::new(client, server_url, realm)
+    }
+
+    /// The same server and credentials, managing another realm.
+    pub fn realm(&self, realm: impl Into<String>) -> Self {
+        Self {
+            realm: realm.into(),
+            ..self.clone()
+        }
+    }
+
+    pub fn realm_name(&self) -> &str {
+        &self.realm
+    }
+
+    /// `<server>/admin/realms/<realm>/<segments...>`, each segment percent-encoded.
+    pub(crate) fn url(&self, segments: &[&str]) -> Result<Url, ClientError> {
+        let mut url = self.server_url.clone();
+        url.path_segments_mut()
+            .map_err(|()| ClientError::InvalidUrlError(self.server_url.to_string()))?
+            .pop_if_empty()
+            .extend(["admin", "realms", self.realm.as_str()])
+            .extend(segments);
+        Ok(url)
+    }
+
+    async fn request(
+        &self,
+        method: Method,
+        segments: &[&str],
+    ) -> Result<RequestBuilder, ClientError> {
+        let token = self.tokens.access_token().await?;
+        Ok(self
+            .http
+            .request(method, self.url(segments)?)
+            .bearer_auth(token))
+    }
+
+    pub(crate) async fn get<T: DeserializeOwned>(
+        &self,
+        segments: &[&str],
+        query: &(impl Serialize + ?Sized),
+    ) -> Result<T, ClientError> {
+        let request = self.request(Method::GET, segments).await?.query(query);
+        Ok(send(request).await?.json().await?)
+    }
+
+    /// Like `get`, but a `404` is `None`.
+    pub(crate) async fn find<T: DeserializeOwned>(
+        &self,
+        segments: &[&str],
+    ) -> Result<Option<T>, ClientError> {
+        match self.get(segments, &()).await {
+            Ok(value) => Ok(Some(value)),
+            Err(ClientError::AdminApiError { status: 404, .. }) => Ok(None),
+            Err(e) => Err(e),
+        }
+    }
+
+    /// Posts `body` to a collection and returns the id of the created resource.
+    pub(crate) async fn create(
+        &self,
+        segments: &[&str],
+        body: &(impl Serialize + ?Sized),
+    ) -> Result<String, ClientError> {
+        let request = self.request(Method::POST, segments).await?.json(body);
+        let response = send(request).await?;
+        // the id is only returned as the last segment of the Location header
+        response
+            .headers()
+            .get(header::LOCATION)
+            .and_then(|location| location.to_str().ok())
+            .and_then(|location| location.rsplit('/').next())
+            .map(str::to_string)
+            .ok_or_else(|| ClientError::AdminApiError {
+                status: response.status().as_u16(),
+                message: "created resource has no Location header".into(),
+            })
+    }
+
+    pub(crate) async fn send_json(
+        &self,
+        method: Method,
+        segments: &[&str],
+        body: &(impl Serialize + ?Sized),
+    ) -> Result<Response, ClientError> {
+        let request = self.request(method, segments).await?.json(body);
+        send(request).await
+    }
+
+    pub(crate) async fn send_query(
+        &self,
+        method: Method,
+        segments: &[&str],
+        query: &(impl Serialize + ?Sized),
+    ) -> Result<Response, ClientError> {
+        let request = self.request(method, segments).await?.query(query);
+        send(request).await
+    }
+
+    pub(crate) async fn delete(&self, segments: &[&str]) -> Result<(), ClientError> {
+        self.send_query(Method::DELETE, segments, &()).await?;
+        Ok(())
+    }
+}
+
+async fn send(request: RequestBuilder) -> Result<Response, ClientError> {
+    let response = request.send().await?;
+    let status = response.status();
+    if status.is_success() {
+        return Ok(response);
+    }
+
+    // Keycloak explains most rejections in `errorMessage` (or `error`), but not all of them
+    let body = response.text().await.unwrap_or_default();
+    let message = serde_json::from_str::<serde_json::Value>(&body)
+        .ok()
+        .and_then(|value| {
+            value
+                .get("errorMessage")
+                .or_else(|| value.get("error"))
+                .and_then(|message| message.as_str())
+                .map(str::to_string)
+        })
+        .unwrap_or(body);
+    Err(ClientError::AdminApiError {
+        status: status.as_u16(),
+        message,
+    })
+}
+
+/// `first`/`max` paging of the list endpoints.
+#[serde_with::skip_serializing_none]
+#[derive(Debug, Clone, Copy, Default, Serialize)]
+pub struct Page {
+    pub first: Option<u32>,
+    pub max: Option<u32>,
+}
+
+impl Page {
+    pub fn new(first: u32, max: u32) -> Self {
+        Self {
+            first: Some(first),
+            max: Some(max),
+        }
+    }
+}
@@ -0,0 +1,20 @@
+mod client_scopes;
+mod clients;
+mod groups;
+mod identity_providers;
+mod keycloak_admin;
+mod realm;
+mod reconcile;
+mod representations;
+mod roles;
+mod users;
+
+pub use client_scopes::*;
+pub use clients::*;
+pub use groups::*;
+pub use keycloak_admin::*;
+pub use realm::*;
+pub use reconcile::*;
+pub use representations::*;
+pub use roles::*;
+pub use users::*;
@@ -0,0 +1,97 @@
+use reqwest::Method;
+use serde::{Deserialize, Serialize};
+
+use crate::client::ClientError;
+
+use super::{KeycloakAdmin, RealmRepresentation};
+
+/// What a partial import does with resources that already exist.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "SCREAMING_SNAKE_CASE")]
+pub enum IfResourceExists {
+    /// Abort the import
+    #[default]
+    Fail,
+    Skip,
+    Overwrite,
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct PartialImportResults {
+    #[serde(default)]
+    pub added: u32,
+    #[serde(default)]
+    pub skipped: u32,
+    #[serde(default)]
+    pub overwritten: u32,
+    #[serde(default)]
+    pub results: Vec<PartialImportResult>,
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct PartialImportResult {
+    /// `ADDED`, `SKIPPED` or `OVERWRITTEN`
+    pub action: Option<String>,
+    /// `CLIENT`, `REALM_ROLE`, `GROUP`, `IDP`, ...
+    pub resource_type: Option<String>,
+    pub resource_name: Option<String>,
+    pub id: Option<String>,
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+struct PartialImport<'a> {
+    #[serde(flatten)]
+    realm: &'a RealmRepresentation,
+    if_resource_exists: IfResourceExists,
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+struct PartialExportQuery {
+    export_clients: bool,
+    export_groups_and_roles: bool,
+}
+
+impl KeycloakAdmin {
+    /// The realm's settings, without clients, roles or groups.
+    pub async fn realm_settings(&self) -> Result<RealmRepresentation, ClientError> {
+        self.get(&[], &()).await
+    }
+
+    /// Exports the realm, optionally with its clients and its groups and roles. Secrets are
+    /// masked as `**********`.
+    pub async fn export_realm(
+        &self,
+        clients: bool,
+        groups_and_roles: bool,
+    ) -> Result<RealmRepresentation, ClientError> {
+        let query = PartialExportQuery {
+            export_clients: clients,
+            export_groups_and_roles: groups_and_roles,
+        };
+        let response = self
+            .send_query(Method::POST, &["partial-export"], &query)
+            .await?;
+        Ok(response.json().await?)
+    }
+
+    /// Imports the clients, roles, groups, identity providers and users of `realm` in one
+    /// request. For updating existing resources field by field see `plan_realm`.
+    pub async fn import_realm(
+        &self,
+        realm: &RealmRepresentation,
+        if_resource_exists: IfResourceExists,
+    ) -> Result<PartialImportResults, ClientError> {
+        let body = PartialImport {
+            realm,
+            if_resource_exists,
+        };
+        let response = self
+            .send_json(Method::POST, &["partialImport"], &body)
+            .await?;
+        Ok(response.json().await?)
+    }
+}
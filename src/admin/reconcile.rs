@@ -0,0 +1,1172 @@
+use std::{
+    collections::{HashMap, HashSet},
+    fmt,
+    future::Future,
+};
+
+use serde::{de::DeserializeOwned, Serialize};
+use serde_json::Value;
+
+use crate::client::ClientError;
+
+use super::{
+    ClientQuery, ClientRepresentation, ClientScopeRepresentation, GroupQuery, GroupRepresentation,
+    IdentityProviderRepresentation, KeycloakAdmin, Page, RealmRepresentation, RoleHolder,
+    RoleQuery, RoleRepresentation,
+};
+
+/// Secrets are exported and read back as this placeholder. It must not overwrite the real
+/// value, and a live placeholder matches any desired secret.
+const MASKED: &str = "**********";
+
+const PAGE_SIZE: u32 = 100;
+
+// Fields assigned by Keycloak, or managed through their own endpoints
+const CLIENT_IGNORED: &[&str] = &[
+    "id",
+    "protocolMappers",
+    "defaultClientScopes",
+    "optionalClientScopes",
+    "authorizationSettings",
+    "access",
+];
+const ROLE_IGNORED: &[&str] = &["id", "containerId", "composite", "composites", "clientRole"];
+// Compared, but updating the resource does not change them
+const CLIENT_UNSUPPORTED: &[&str] = &[
+    "protocolMappers",
+    "defaultClientScopes",
+    "optionalClientScopes",
+];
+const GROUP_IGNORED: &[&str] = &[
+    "id",
+    "path",
+    "parentId",
+    "subGroups",
+    "subGroupCount",
+    "realmRoles",
+    "clientRoles",
+    "access",
+];
+const SCOPE_IGNORED: &[&str] = &["id", "protocolMappers"];
+const SCOPE_UNSUPPORTED: &[&str] = &["protocolMappers"];
+const PROVIDER_IGNORED: &[&str] = &["internalId"];
+
+// Created with every realm, so never pruned
+const BUILTIN_CLIENTS: &[&str] = &[
+    "account",
+    "account-console",
+    "admin-cli",
+    "broker",
+    "realm-management",
+    "security-admin-console",
+];
+const BUILTIN_ROLES: &[&str] = &["offline_access", "uma_authorization"];
+const BUILTIN_CLIENT_SCOPES: &[&str] = &[
+    "acr",
+    "address",
+    "basic",
+    "email",
+    "microprofile-jwt",
+    "offline_access",
+    "organization",
+    "phone",
+    "profile",
+    "role_list",
+    "roles",
+    "saml_organization",
+    "service_account",
+    "web-origins",
+];
+
+/// Options of `KeycloakAdmin::plan_realm` and `KeycloakAdmin::reconcile_realm`.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct ReconcileOptions {
+    /// Delete resources missing from the desired state. Only kinds the desired state lists are
+    /// pruned, and the clients (with their roles), roles and client scopes every realm starts
+    /// with are kept.
+    pub prune: bool,
+    /// Only plan, do not apply
+    pub dry_run: bool,
+}
+
+impl ReconcileOptions {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn with_prune(mut self, prune: bool) -> Self {
+        self.prune = prune;
+        self
+    }
+
+    pub fn with_dry_run(mut self, dry_run: bool) -> Self {
+        self.dry_run = dry_run;
+        self
+    }
+}
+
+/// A realm role, or a role of the client with client id `client_id`.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub enum RoleRef {
+    Realm(String),
+    Client { client_id: String, role: String },
+}
+
+impl fmt::Display for RoleRef {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            RoleRef::Realm(role) => write!(f, "{}", role),
+            RoleRef::Client { client_id, role } => write!(f, "{}/{}", client_id, role),
+        }
+    }
+}
+
+/// One step of a `RealmPlan`. Clients are identified by client id, roles and client scopes by
+/// name, groups by path and identity providers by alias. Updates carry the live resource with
+/// the desired fields applied, and `fields` names the top level fields that differ.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Change {
+    CreateClientScope(ClientScopeRepresentation),
+    UpdateClientScope {
+        name: String,
+        fields: Vec<String>,
+        scope: ClientScopeRepresentation,
+    },
+    DeleteClientScope {
+        name: String,
+    },
+    CreateClient(ClientRepresentation),
+    UpdateClient {
+        client_id: String,
+        fields: Vec<String>,
+        client: ClientRepresentation,
+    },
+    DeleteClient {
+        client_id: String,
+    },
+    CreateRealmRole(RoleRepresentation),
+    UpdateRealmRole {
+        name: String,
+        fields: Vec<String>,
+        role: RoleRepresentation,
+    },
+    DeleteRealmRole {
+        name: String,
+    },
+    CreateClientRole {
+        client_id: String,
+        role: RoleRepresentation,
+    },
+    UpdateClientRole {
+        client_id: String,
+        name: String,
+        fields: Vec<String>,
+        role: RoleRepresentation,
+    },
+    DeleteClientRole {
+        client_id: String,
+        name: String,
+    },
+    /// Creates a group below `parent`, or at the top level
+    CreateGroup {
+        parent: Option<String>,
+        group: GroupRepresentation,
+    },
+    UpdateGroup {
+        path: String,
+        fields: Vec<String>,
+        group: GroupRepresentation,
+    },
+    DeleteGroup {
+        path: String,
+    },
+    AddGroupRoles {
+        path: String,
+        roles: Vec<RoleRef>,
+    },
+    RemoveGroupRoles {
+        path: String,
+        roles: Vec<RoleRef>,
+    },
+    CreateIdentityProvider(IdentityProviderRepresentation),
+    UpdateIdentityProvider {
+        alias: String,
+        fields: Vec<String>,
+        provider: IdentityProviderRepresentation,
+    },
+    DeleteIdentityProvider {
+        alias: String,
+    },
+    /// Fields of `resource` (e.g. `client app`) that differ but cannot be reconciled, such as
+    /// the protocol mappers of a client or client scope, or a role's composites. Applying skips
+    /// it.
+    Unsupported {
+        resource: String,
+        fields: Vec<String>,
+    },
+}
+
+impl fmt::Display for Change {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let name = |name: &Option<String>| name.clone().unwrap_or_default();
+        let group_path = |parent: &Option<String>, group: &GroupRepresentation| {
+            format!(
+                "{}/{}",
+                parent.as_deref().unwrap_or_default(),
+                name(&group.name)
+            )
+        };
+        match self {
+            Change::CreateClientScope(scope) => write!(f, "+ client scope {}", name(&scope.name)),
+            Change::UpdateClientScope { name, fields, .. } => {
+                write!(f, "~ client scope {} ({})", name, fields.join(", "))
+            }
+            Change::DeleteClientScope { name } => write!(f, "- client scope {}", name),
+            Change::CreateClient(client) => write!(f, "+ client {}", name(&client.client_id)),
+            Change::UpdateClient {
+                client_id, fields, ..
+            } => write!(f, "~ client {} ({})", client_id, fields.join(", ")),
+            Change::DeleteClient { client_id } => write!(f, "- client {}", client_id),
+            Change::CreateRealmRole(role) => write!(f, "+ realm role {}", name(&role.name)),
+            Change::UpdateRealmRole { name, fields, .. } => {
+                write!(f, "~ realm role {} ({})", name, fields.join(", "))
+            }
+            Change::DeleteRealmRole { name } => write!(f, "- realm role {}", name),
+            Change::CreateClientRole { client_id, role } => {
+                write!(f, "+ client role {}/{}", client_id, name(&role.name))
+            }
+            Change::UpdateClientRole {
+                client_id,
+                name,
+                fields,
+                ..
+            } => write!(
+                f,
+                "~ client role {}/{} ({})",
+                client_id,
+                name,
+                fields.join(", ")
+            ),
+            Change::DeleteClientRole { client_id, name } => {
+                write!(f, "- client role {}/{}", client_id, name)
+            }
+            Change::CreateGroup { parent, group } => {
+                write!(f, "+ group {}", group_path(parent, group))
+            }
+            Change::UpdateGroup { path, fields, .. } => {
+                write!(f, "~ group {} ({})", path, fields.join(", "))
+            }
+            Change::DeleteGroup { path } => write!(f, "- group {}", path),
+            Change::AddGroupRoles { path, roles } => {
+                write!(f, "+ roles of group {}: {}", path, join(roles))
+            }
+            Change::RemoveGroupRoles { path, roles } => {
+                write!(f, "- roles of group {}: {}", path, join(roles))
+            }
+            Change::CreateIdentityProvider(provider) => {
+                write!(f, "+ identity provider {}", name(&provider.alias))
+            }
+            Change::UpdateIdentityProvider { alias, fields, .. } => {
+                write!(f, "~ identity provider {} ({})", alias, fields.join(", "))
+            }
+            Change::DeleteIdentityProvider { alias } => write!(f, "- identity provider {}", alias),
+            Change::Unsupported { resource, fields } => {
+                write!(f, "! {} ({}): not reconciled", resource, fields.join(", "))
+            }
+        }
+    }
+}
+
+fn join(roles: &[RoleRef]) -> String {
+    roles
+        .iter()
+        .map(ToString::to_string)
+        .collect::<Vec<_>>()
+        .join(", ")
+}
+
+/// The changes that bring a realm to its desired state, in the order they are applied. Its
+/// `Display` lists one change per line, prefixed with `+`, `~` or `-`, and differences that
+/// have to be fixed by hand with `!`.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct RealmPlan {
+    pub changes: Vec<Change>,
+}
+
+impl RealmPlan {
+    pub fn is_empty(&self) -> bool {
+        self.changes.is_empty()
+    }
+}
+
+impl fmt::Display for RealmPlan {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if self.changes.is_empty() {
+            return writeln!(f, "No changes");
+        }
+        for change in &self.changes {
+            writeln!(f, "{}", change)?;
+        }
+        Ok(())
+    }
+}
+
+enum Diff<T> {
+    Missing,
+    Changed(Vec<String>, T),
+    Unchanged,
+}
+
+/// Compares the fields `desired` sets with `live`, returning `live` with those fields applied
+/// when they differ.
+fn diff<T: Serialize + DeserializeOwned>(
+    desired: &T,
+    live: Option<&T>,
+    ignored: &[&str],
+) -> Result<Diff<T>, ClientError> {
+    let Some(live) = live else {
+        return Ok(Diff::Missing);
+    };
+    let desired = serde_json::to_value(desired)?;
+    let mut merged = serde_json::to_value(live)?;
+
+    let Value::Object(fields) = &desired else {
+        return Ok(Diff::Unchanged);
+    };
+    let changed: Vec<String> = fields
+        .iter()
+        .filter(|(field, value)| {
+            !ignored.contains(&field.as_str()) && !matches(value, merged.get(field.as_str()))
+        })
+        .map(|(field, _)| field.clone())
+        .collect();
+    if changed.is_empty() {
+        return Ok(Diff::Unchanged);
+    }
+
+    if let Value::Object(merged) = &mut merged {
+        for field in &changed {
+            apply(
+                merged.entry(field.as_str()).or_insert(Value::Null),
+                &fields[field],
+            );
+        }
+    }
+    Ok(Diff::Changed(changed, serde_json::from_value(merged)?))
+}
+
+/// The `fields` set by `desired` that `live` does not match.
+fn differing<T: Serialize>(
+    desired: &T,
+    live: &T,
+    fields: &[&str],
+) -> Result<Vec<String>, ClientError> {
+    let desired = serde_json::to_value(desired)?;
+    let live = serde_json::to_value(live)?;
+    Ok(fields
+        .iter()
+        .filter(|field| {
+            desired
+                .get(**field)
+                .is_some_and(|value| !matches(value, live.get(**field)))
+        })
+        .map(|field| field.to_string())
+        .collect())
+}
+
+/// Whether `live` has everything `desired` sets. Objects may have more entries than desired,
+/// arrays are compared regardless of order.
+fn matches(desired: &Value, live: Option<&Value>) -> bool {
+    match (desired, live) {
+        (Value::String(value), _) if value == MASKED => true,
+        (Value::String(_), Some(Value::String(live))) if live == MASKED => true,
+        (Value::Object(desired), Some(Value::Object(live))) => desired
+            .iter()
+            .all(|(key, value)| matches(value, live.get(key))),
+        (Value::Object(desired), None) => desired.values().all(|value| matches(value, None)),
+        (Value::Array(desired), Some(Value::Array(live))) => {
+            desired.len() == live.len()
+                && desired
+                    .iter()
+                    .all(|value| live.iter().any(|live| matches(value, Some(live))))
+        }
+        (Value::Array(desired), None) => desired.is_empty(),
+        (Value::Null, None) => true,
+        (desired, Some(live)) => desired == live,
+        (_, None) => false,
+    }
+}
+
+fn apply(live: &mut Value, desired: &Value) {
+    match (live, desired) {
+        (Value::String(_), Value::String(value)) if value == MASKED => {}
+        (Value::Object(live), Value::Object(desired)) => {
+            for (key, value) in desired {
+                apply(live.entry(key.as_str()).or_insert(Value::Null), value);
+            }
+        }
+        (live, desired) => *live = desired.clone(),
+    }
+}
+
+fn key<'a>(key: &'a Option<String>, kind: &str) -> Result<&'a str, ClientError> {
+    key.as_deref().ok_or_else(|| {
+        ClientError::InvalidRealmRepresentationError(format!("a {} has no name", kind))
+    })
+}
+
+fn not_found(what: String) -> ClientError {
+    ClientError::AdminApiError {
+        status: 404,
+        message: format!("{} not found", what),
+    }
+}
+
+/// Treats a `409 Conflict` on create as done, so a plan can be applied again.
+fn created<T>(result: Result<T, ClientError>) -> Result<(), ClientError> {
+    match result {
+        Ok(_) | Err(ClientError::AdminApiError { status: 409, .. }) => Ok(()),
+        Err(e) => Err(e),
+    }
+}
+
+async fn all_pages<T, F, Fut>(mut fetch: F) -> Result<Vec<T>, ClientError>
+where
+    F: FnMut(Page) -> Fut,
+    Fut: Future<Output = Result<Vec<T>, ClientError>>,
+{
+    let mut all = Vec::new();
+    loop {
+        let page = fetch(Page::new(all.len() as u32, PAGE_SIZE)).await?;
+        let last = page.len() < PAGE_SIZE as usize;
+        all.extend(page);
+        if last {
+            return Ok(all);
+        }
+    }
+}
+
+/// `groups` and their subgroups as `(path, group)`, parents before children.
+fn flatten_groups(
+    groups: &[GroupRepresentation],
+) -> Vec<(Option<String>, String, &GroupRepresentation)> {
+    let mut flat = Vec::new();
+    let mut pending: Vec<(Option<String>, &GroupRepresentation)> =
+        groups.iter().rev().map(|group| (None, group)).collect();
+    while let Some((parent, group)) = pending.pop() {
+        let path = format!(
+            "{}/{}",
+            parent.as_deref().unwrap_or_default(),
+            group.name.as_deref().unwrap_or_default()
+        );
+        for child in group.sub_groups.iter().flatten().rev() {
+            pending.push((Some(path.clone()), child));
+        }
+        flat.push((parent, path, group));
+    }
+    flat
+}
+
+fn desired_roles(group: &GroupRepresentation) -> Option<HashSet<RoleRef>> {
+    if group.realm_roles.is_none() && group.client_roles.is_none() {
+        return None;
+    }
+    let realm = group
+        .realm_roles
+        .iter()
+        .flatten()
+        .cloned()
+        .map(RoleRef::Realm);
+    let client = group
+        .client_roles
+        .iter()
+        .flatten()
+        .flat_map(|(client_id, roles)| {
+            roles.iter().map(|role| RoleRef::Client {
+                client_id: client_id.clone(),
+                role: role.clone(),
+            })
+        });
+    Some(realm.chain(client).collect())
+}
+
+fn sorted(roles: impl IntoIterator<Item = RoleRef>) -> Vec<RoleRef> {
+    let mut roles: Vec<RoleRef> = roles.into_iter().collect();
+    roles.sort_by_key(ToString::to_string);
+    roles
+}
+
+impl KeycloakAdmin {
+    /// Compares `desired` with the live realm and returns the changes that would make them
+    /// match: client scopes, clients, realm and client roles, groups (with their role mappings)
+    /// and identity providers, in that order. Kinds `desired` leaves out are not touched, and
+    /// only the fields it sets are compared, so a hand written subset works as well as a full
+    /// export.
+    ///
+    /// ```no_run
+    /// # async fn run(admin: keycloak_oauth::admin::KeycloakAdmin) -> Result<(), Box<dyn std::error::Error>> {
+    /// use keycloak_oauth::admin::{RealmRepresentation, ReconcileOptions};
+    ///
+    /// let desired = RealmRepresentation::from_json(&std::fs::read_to_string("realm.json")?)?;
+    /// let plan = admin.plan_realm(&desired, &ReconcileOptions::new()).await?;
+    /// print!("{}", plan);
+    /// admin.apply_plan(&plan).await?;
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub async fn plan_realm(
+        &self,
+        desired: &RealmRepresentation,
+        options: &ReconcileOptions,
+    ) -> Result<RealmPlan, ClientError> {
+        let mut changes = Vec::new();
+        if let Some(scopes) = &desired.client_scopes {
+            self.plan_client_scopes(scopes, options, &mut changes)
+                .await?;
+        }
+        if let Some(clients) = &desired.clients {
+            self.plan_clients(clients, options, &mut changes).await?;
+        }
+        let roles = desired.roles.as_ref();
+        if let Some(roles) = roles.and_then(|roles| roles.realm.as_ref()) {
+            self.plan_realm_roles(roles, options, &mut changes).await?;
+        }
+        if let Some(roles) = roles.and_then(|roles| roles.client.as_ref()) {
+            let mut client_ids: Vec<&String> = roles.keys().collect();
+            client_ids.sort();
+            for client_id in client_ids {
+                self.plan_client_roles(client_id, &roles[client_id], options, &mut changes)
+                    .await?;
+            }
+        }
+        if let Some(groups) = &desired.groups {
+            self.plan_groups(groups, options, &mut changes).await?;
+        }
+        if let Some(providers) = &desired.identity_providers {
+            self.plan_identity_providers(providers, options, &mut changes)
+                .await?;
+        }
+        Ok(RealmPlan { changes })
+    }
+
+    /// Applies the changes of `plan` in order. Creating something that already exists and
+    /// deleting something already gone are not errors, so an interrupted plan can be applied
+    /// again; planning afterwards returns only the `Change::Unsupported` differences.
+    pub async fn apply_plan(&self, plan: &RealmPlan) -> Result<(), ClientError> {
+        for change in &plan.changes {
+            self.apply_change(change).await?;
+        }
+        Ok(())
+    }
+
+    /// Plans the changes to reach `desired` and, unless `options.dry_run` is set, applies them.
+    /// Returns the plan either way.
+    pub async fn reconcile_realm(
+        &self,
+        desired: &RealmRepresentation,
+        options: &ReconcileOptions,
+    ) -> Result<RealmPlan, ClientError> {
+        let plan = self.plan_realm(desired, options).await?;
+        if !options.dry_run {
+            self.apply_plan(&plan).await?;
+        }
+        Ok(plan)
+    }
+
+    async fn plan_client_scopes(
+        &self,
+        desired: &[ClientScopeRepresentation],
+        options: &ReconcileOptions,
+        changes: &mut Vec<Change>,
+    ) -> Result<(), ClientError> {
+        let live = self.client_scopes().await?;
+        let mut names = HashSet::new();
+        for scope in desired {
+            let name = key(&scope.name, "client scope")?;
+            names.insert(name);
+            let current = live.iter().find(|live| live.name.as_deref() == Some(name));
+            if let Some(current) = current {
+                unsupported(
+                    format!("client scope {}", name),
+                    differing(scope, current, SCOPE_UNSUPPORTED)?,
+                    changes,
+                );
+            }
+            match diff(scope, current, SCOPE_IGNORED)? {
+                Diff::Missing => {
+                    changes.push(Change::CreateClientScope(ClientScopeRepresentation {
+                        id: None,
+                        ..scope.clone()
+                    }))
+                }
+                Diff::Changed(fields, scope) => changes.push(Change::UpdateClientScope {
+                    name: name.to_string(),
+                    fields,
+                    scope,
+                }),
+                Diff::Unchanged => {}
+            }
+        }
+
+        if options.prune {
+            for scope in &live {
+                let name = scope.name.as_deref().unwrap_or_default();
+                if !names.contains(name) && !BUILTIN_CLIENT_SCOPES.contains(&name) {
+                    changes.push(Change::DeleteClientScope { name: name.into() });
+                }
+            }
+        }
+        Ok(())
+    }
+
+    async fn plan_clients(
+        &self,
+        desired: &[ClientRepresentation],
+        options: &ReconcileOptions,
+        changes: &mut Vec<Change>,
+    ) -> Result<(), ClientError> {
+        let live = all_pages(|page| async move {
+            let query = ClientQuery::new().page(page.first.unwrap_or_default(), PAGE_SIZE);
+            self.clients(&query).await
+        })
+        .await?;
+        let mut client_ids = HashSet::new();
+        for client in desired {
+            let client_id = key(&client.client_id, "client")?;
+            client_ids.insert(client_id);
+            let current = live
+                .iter()
+                .find(|live| live.client_id.as_deref() == Some(client_id));
+            if let Some(current) = current {
+                unsupported(
+                    format!("client {}", client_id),
+                    differing(client, current, CLIENT_UNSUPPORTED)?,
+                    changes,
+                );
+            }
+            match diff(client, current, CLIENT_IGNORED)? {
+                Diff::Missing => changes.push(Change::CreateClient(ClientRepresentation {
+                    id: None,
+                    ..client.clone()
+                })),
+                Diff::Changed(fields, client) => changes.push(Change::UpdateClient {
+                    client_id: client_id.to_string(),
+                    fields,
+                    client,
+                }),
+                Diff::Unchanged => {}
+            }
+        }
+
+        if options.prune {
+            for client in &live {
+                let client_id = client.client_id.as_deref().unwrap_or_default();
+                if !client_ids.contains(client_id) && !BUILTIN_CLIENTS.contains(&client_id) {
+                    changes.push(Change::DeleteClient {
+                        client_id: client_id.into(),
+                    });
+                }
+            }
+        }
+        Ok(())
+    }
+
+    async fn plan_realm_roles(
+        &self,
+        desired: &[RoleRepresentation],
+        options: &ReconcileOptions,
+        changes: &mut Vec<Change>,
+    ) -> Result<(), ClientError> {
+        let live = all_pages(|page| async move {
+            let query = RoleQuery::new()
+                .brief(false)
+                .page(page.first.unwrap_or_default(), PAGE_SIZE);
+            self.realm_roles(&query).await
+        })
+        .await?;
+        let mut names = HashSet::new();
+        for role in desired {
+            let name = key(&role.name, "realm role")?;
+            names.insert(name);
+            let current = live.iter().find(|live| live.name.as_deref() == Some(name));
+            if let Some(current) = current {
+                let fields = self.differing_composites(role, current).await?;
+                unsupported(format!("realm role {}", name), fields, changes);
+            }
+            match diff(role, current, ROLE_IGNORED)? {
+                Diff::Missing => changes.push(Change::CreateRealmRole(for_create(role))),
+                Diff::Changed(fields, role) => changes.push(Change::UpdateRealmRole {
+                    name: name.to_string(),
+                    fields,
+                    role,
+                }),
+                Diff::Unchanged => {}
+            }
+        }
+
+        if options.prune {
+            for role in &live {
+                let name = role.name.as_deref().unwrap_or_default();
+                let builtin = BUILTIN_ROLES.contains(&name) || name.starts_with("default-roles-");
+                if !names.contains(name) && !builtin {
+                    changes.push(Change::DeleteRealmRole { name: name.into() });
+                }
+            }
+        }
+        Ok(())
+    }
+
+    async fn plan_client_roles(
+        &self,
+        client_id: &str,
+        desired: &[RoleRepresentation],
+        options: &ReconcileOptions,
+        changes: &mut Vec<Change>,
+    ) -> Result<(), ClientError> {
+        // a client created by this plan has no roles yet
+        let live = match self.client_uuid(client_id).await? {
+            Some(uuid) => {
+                let uuid = uuid.as_str();
+                all_pages(|page| async move {
+                    let query = RoleQuery::new()
+                        .brief(false)
+                        .page(page.first.unwrap_or_default(), PAGE_SIZE);
+                    self.client_roles(uuid, &query).await
+                })
+                .await?
+            }
+            None => Vec::new(),
+        };
+        let mut names = HashSet::new();
+        for role in desired {
+            let name = key(&role.name, "client role")?;
+            names.insert(name);
+            let current = live.iter().find(|live| live.name.as_deref() == Some(name));
+            if let Some(current) = current {
+                let fields = self.differing_composites(role, current).await?;
+                unsupported(
+                    format!("client role {}/{}", client_id, name),
+                    fields,
+                    changes,
+                );
+            }
+            match diff(role, current, ROLE_IGNORED)? {
+                Diff::Missing => changes.push(Change::CreateClientRole {
+                    client_id: client_id.to_string(),
+                    role: for_create(role),
+                }),
+                Diff::Changed(fields, role) => changes.push(Change::UpdateClientRole {
+                    client_id: client_id.to_string(),
+                    name: name.to_string(),
+                    fields,
+                    role,
+                }),
+                Diff::Unchanged => {}
+            }
+        }
+
+        // the roles of the built-in clients are Keycloak's, like the clients themselves
+        if options.prune && !BUILTIN_CLIENTS.contains(&client_id) {
+            for role in &live {
+                let name = role.name.as_deref().unwrap_or_default();
+                if !names.contains(name) {
+                    changes.push(Change::DeleteClientRole {
+                        client_id: client_id.to_string(),
+                        name: name.into(),
+                    });
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// `["composites"]` when the roles `desired` is made of differ from those of `live`.
+    async fn differing_composites(
+        &self,
+        desired: &RoleRepresentation,
+        live: &RoleRepresentation,
+    ) -> Result<Vec<String>, ClientError> {
+        let Some(composites) = desired.extra.get("composites") else {
+            return Ok(Vec::new());
+        };
+        let mut realm = Vec::new();
+        let mut client: HashMap<String, Vec<String>> = HashMap::new();
+        if live.composite == Some(true) {
+            let id = live.id.as_deref().ok_or_else(|| {
+                not_found(format!("role {}", live.name.as_deref().unwrap_or_default()))
+            })?;
+            for role in self.role_composites(id).await? {
+                let name = role.name.unwrap_or_default();
+                if role.client_role != Some(true) {
+                    realm.push(name);
+                    continue;
+                }
+                let container = role.container_id.unwrap_or_default();
+                let client_id = self
+                    .client(&container)
+                    .await?
+                    .and_then(|client| client.client_id)
+                    .unwrap_or(container);
+                client.entry(client_id).or_default().push(name);
+            }
+        }
+        let live = serde_json::json!({ "realm": realm, "client": client });
+        Ok(if matches(composites, Some(&live)) {
+            Vec::new()
+        } else {
+            vec!["composites".into()]
+        })
+    }
+
+    async fn plan_groups(
+        &self,
+        desired: &[GroupRepresentation],
+        options: &ReconcileOptions,
+        changes: &mut Vec<Change>,
+    ) -> Result<(), ClientError> {
+        let live = self.group_tree().await?;
+        let mut paths = HashSet::new();
+        for (parent, path, group) in flatten_groups(desired) {
+            key(&group.name, "group")?;
+            let current = live
+                .iter()
+                .find(|(live_path, _)| *live_path == path)
+                .map(|(_, group)| group);
+            let mapped = match current.and_then(|group| group.id.as_deref()) {
+                Some(id) => self.group_roles(id).await?,
+                None => HashSet::new(),
+            };
+
+            match diff(group, current, GROUP_IGNORED)? {
+                Diff::Missing => changes.push(Change::CreateGroup {
+                    parent,
+                    group: GroupRepresentation {
+                        id: None,
+                        sub_groups: None,
+                        realm_roles: None,
+                        client_roles: None,
+                        ..group.clone()
+                    },
+                }),
+                Diff::Changed(fields, group) => changes.push(Change::UpdateGroup {
+                    path: path.clone(),
+                    fields,
+                    group: GroupRepresentation {
+                        sub_groups: None,
+                        ..group
+                    },
+                }),
+                Diff::Unchanged => {}
+            }
+
+            if let Some(roles) = desired_roles(group) {
+                let missing = sorted(roles.difference(&mapped).cloned());
+                if !missing.is_empty() {
+                    changes.push(Change::AddGroupRoles {
+                        path: path.clone(),
+                        roles: missing,
+                    });
+                }
+                let extra = sorted(mapped.difference(&roles).cloned());
+                if options.prune && !extra.is_empty() {
+                    changes.push(Change::RemoveGroupRoles {
+                        path: path.clone(),
+                        roles: extra,
+                    });
+                }
+            }
+            paths.insert(path);
+        }
+
+        if options.prune {
+            for (path, _) in &live {
+                // deleting a group deletes its subgroups with it
+                let parent_deleted = changes.iter().any(|change| {
+                    matches!(change, Change::DeleteGroup { path: deleted }
+                        if path.starts_with(&format!("{}/", deleted)))
+                });
+                if !paths.contains(path) && !parent_deleted {
+                    changes.push(Change::DeleteGroup { path: path.clone() });
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// Every group of the realm with its path, parents before children.
+    async fn group_tree(&self) -> Result<Vec<(String, GroupRepresentation)>, ClientError> {
+        let top = all_pages(|page| async move {
+            let query = GroupQuery::new()
+                .brief(false)
+                .page(page.first.unwrap_or_default(), PAGE_SIZE);
+            self.groups(&query).await
+        })
+        .await?;
+
+        let mut tree = Vec::new();
+        let mut pending: Vec<(String, GroupRepresentation)> = top
+            .into_iter()
+            .rev()
+            .map(|group| (String::new(), group))
+            .collect();
+        while let Some((parent, mut group)) = pending.pop() {
+            let path = format!("{}/{}", parent, group.name.as_deref().unwrap_or_default());
+            // older Keycloak versions nest subgroups, newer ones only count them
+            let mut children = group.sub_groups.take().unwrap_or_default();
+            if children.is_empty() && group.sub_group_count.unwrap_or_default() > 0 {
+                if let Some(id) = group.id.as_deref() {
+                    children = all_pages(|page| self.subgroups(id, page)).await?;
+                }
+            }
+            pending.extend(
+                children
+                    .into_iter()
+                    .rev()
+                    .map(|child| (path.clone(), child)),
+            );
+            tree.push((path, group));
+        }
+        Ok(tree)
+    }
+
+    async fn group_roles(&self, id: &str) -> Result<HashSet<RoleRef>, ClientError> {
+        let mappings = self.role_mappings(RoleHolder::Group(id)).await?;
+        let realm = mappings
+            .realm_mappings
+            .into_iter()
+            .filter_map(|role| role.name)
+            .map(RoleRef::Realm);
+        let client = mappings
+            .client_mappings
+            .into_iter()
+            .flat_map(|(client_id, mappings)| {
+                mappings
+                    .mappings
+                    .into_iter()
+                    .filter_map(|role| role.name)
+                    .map(move |role| RoleRef::Client {
+                        client_id: client_id.clone(),
+                        role,
+                    })
+            });
+        Ok(realm.chain(client).collect())
+    }
+
+    async fn plan_identity_providers(
+        &self,
+        desired: &[IdentityProviderRepresentation],
+        options: &ReconcileOptions,
+        changes: &mut Vec<Change>,
+    ) -> Result<(), ClientError> {
+        let live = self.identity_providers().await?;
+        let mut aliases = HashSet::new();
+        for provider in desired {
+            let alias = key(&provider.alias, "identity provider")?;
+            aliases.insert(alias);
+            let current = live
+                .iter()
+                .find(|live| live.alias.as_deref() == Some(alias));
+            match diff(provider, current, PROVIDER_IGNORED)? {
+                Diff::Missing => changes.push(Change::CreateIdentityProvider(
+                    IdentityProviderRepresentation {
+                        internal_id: None,
+                        ..provider.clone()
+                    },
+                )),
+                Diff::Changed(fields, provider) => changes.push(Change::UpdateIdentityProvider {
+                    alias: alias.to_string(),
+                    fields,
+                    provider,
+                }),
+                Diff::Unchanged => {}
+            }
+        }
+
+        if options.prune {
+            for provider in &live {
+                let alias = provider.alias.as_deref().unwrap_or_default();
+                if !aliases.contains(alias) {
+                    changes.push(Change::DeleteIdentityProvider {
+                        alias: alias.into(),
+                    });
+                }
+            }
+        }
+        Ok(())
+    }
+
+    async fn apply_change(&self, change: &Change) -> Result<(), ClientError> {
+        match change {
+            Change::CreateClientScope(scope) => created(self.create_client_scope(scope).await),
+            Change::UpdateClientScope { name, scope, .. } => {
+                let id = scope
+                    .id
+                    .as_deref()
+                    .ok_or_else(|| not_found(format!("client scope {}", name)))?;
+                self.update_client_scope(id, scope).await
+            }
+            Change::DeleteClientScope { name } => match self.client_scope_by_name(name).await? {
+                Some(ClientScopeRepresentation { id: Some(id), .. }) => {
+                    gone(self.delete_client_scope(&id).await)
+                }
+                _ => Ok(()),
+            },
+            Change::CreateClient(client) => created(self.create_client(client).await),
+            Change::UpdateClient {
+                client_id, client, ..
+            } => {
+                let id = client
+                    .id
+                    .as_deref()
+                    .ok_or_else(|| not_found(format!("client {}", client_id)))?;
+                self.update_client(id, client).await
+            }
+            Change::DeleteClient { client_id } => match self.client_uuid(client_id).await? {
+                Some(id) => gone(self.delete_client(&id).await),
+                None => Ok(()),
+            },
+            Change::CreateRealmRole(role) => created(self.create_realm_role(role).await),
+            Change::UpdateRealmRole { name, role, .. } => self.update_realm_role(name, role).await,
+            Change::DeleteRealmRole { name } => gone(self.delete_realm_role(name).await),
+            Change::CreateClientRole { client_id, role } => {
+                let uuid = self.required_client_uuid(client_id).await?;
+                created(self.create_client_role(&uuid, role).await)
+            }
+            Change::UpdateClientRole {
+                client_id,
+                name,
+                role,
+                ..
+            } => {
+                let uuid = self.required_client_uuid(client_id).await?;
+                self.update_client_role(&uuid, name, role).await
+            }
+            Change::DeleteClientRole { client_id, name } => {
+                match self.client_uuid(client_id).await? {
+                    Some(uuid) => gone(self.delete_client_role(&uuid, name).await),
+                    None => Ok(()),
+                }
+            }
+            Change::CreateGroup { parent, group } => match parent {
+                Some(parent) => {
+                    let parent_id = self.required_group_id(parent).await?;
+                    created(self.create_subgroup(&parent_id, group).await)
+                }
+                None => created(self.create_group(group).await),
+            },
+            Change::UpdateGroup { path, group, .. } => {
+                let id = group
+                    .id
+                    .as_deref()
+                    .ok_or_else(|| not_found(format!("group {}", path)))?;
+                self.update_group(id, group).await
+            }
+            Change::DeleteGroup { path } => match self.group_by_path(path).await? {
+                Some(GroupRepresentation { id: Some(id), .. }) => {
+                    gone(self.delete_group(&id).await)
+                }
+                _ => Ok(()),
+            },
+            Change::AddGroupRoles { path, roles } => self.map_group_roles(path, roles, true).await,
+            Change::RemoveGroupRoles { path, roles } => {
+                self.map_group_roles(path, roles, false).await
+            }
+            Change::CreateIdentityProvider(provider) => {
+                created(self.create_identity_provider(provider).await)
+            }
+            Change::UpdateIdentityProvider {
+                alias, provider, ..
+            } => self.update_identity_provider(alias, provider).await,
+            Change::DeleteIdentityProvider { alias } => {
+                gone(self.delete_identity_provider(alias).await)
+            }
+            Change::Unsupported { .. } => Ok(()),
+        }
+    }
+
+    async fn required_client_uuid(&self, client_id: &str) -> Result<String, ClientError> {
+        self.client_uuid(client_id)
+            .await?
+            .ok_or_else(|| not_found(format!("client {}", client_id)))
+    }
+
+    async fn required_group_id(&self, path: &str) -> Result<String, ClientError> {
+        self.group_by_path(path)
+            .await?
+            .and_then(|group| group.id)
+            .ok_or_else(|| not_found(format!("group {}", path)))
+    }
+
+    async fn map_group_roles(
+        &self,
+        path: &str,
+        roles: &[RoleRef],
+        add: bool,
+    ) -> Result<(), ClientError> {
+        let id = self.required_group_id(path).await?;
+        let holder = RoleHolder::Group(&id);
+
+        let mut realm_roles = Vec::new();
+        let mut client_roles: Vec<(String, Vec<RoleRepresentation>)> = Vec::new();
+        for role in roles {
+            match role {
+                RoleRef::Realm(name) => realm_roles.push(
+                    self.realm_role(name)
+                        .await?
+                        .ok_or_else(|| not_found(format!("realm role {}", name)))?,
+                ),
+                RoleRef::Client { client_id, role } => {
+                    let uuid = self.required_client_uuid(client_id).await?;
+                    let role = self
+                        .client_role(&uuid, role)
+                        .await?
+                        .ok_or_else(|| not_found(format!("client role {}/{}", client_id, role)))?;
+                    match client_roles.iter_mut().find(|(client, _)| *client == uuid) {
+                        Some((_, roles)) => roles.push(role),
+                        None => client_roles.push((uuid, vec![role])),
+                    }
+                }
+            }
+        }
+
+        if !realm_roles.is_empty() {
+            if add {
+                self.add_realm_role_mappings(holder, &realm_roles).await?;
+            } else {
+                self.remove_realm_role_mappings(holder, &realm_roles)
+                    .await?;
+            }
+        }
+        for (uuid, roles) in &client_roles {
+            if add {
+                self.add_client_role_mappings(holder, uuid, roles).await?;
+            } else {
+                self.remove_client_role_mappings(holder, uuid, roles)
+                    .await?;
+            }
+        }
+        Ok(())
+    }
+}
+
+fn unsupported(resource: String, fields: Vec<String>, changes: &mut Vec<Change>) {
+    if !fields.is_empty() {
+        changes.push(Change::Unsupported { resource, fields });
+    }
+}
+
+fn for_create(role: &RoleRepresentation) -> RoleRepresentation {
+    RoleRepresentation {
+        id: None,
+        container_id: None,
+        ..role.clone()
+    }
+}
+
+/// Treats a `404 Not Found` on delete as done.
+fn gone(result: Result<(), ClientError>) -> Result<(), ClientError> {
+    match result {
+        Err(ClientError::AdminApiError { status: 404, .. }) => Ok(()),
+        result => result,
+    }
+}
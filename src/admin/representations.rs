@@ -0,0 +1,397 @@
+use std::collections::HashMap;
+
+use serde::{Deserialize, Serialize};
+use serde_with::skip_serializing_none;
+
+// Unset fields are left out of requests, so updates only touch what was set.
+
+#[skip_serializing_none]
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct UserRepresentation {
+    pub id: Option<String>,
+    pub username: Option<String>,
+    pub enabled: Option<bool>,
+    pub email: Option<String>,
+    pub email_verified: Option<bool>,
+    pub first_name: Option<String>,
+    pub last_name: Option<String>,
+    pub attributes: Option<HashMap<String, Vec<String>>>,
+    pub required_actions: Option<Vec<String>>,
+    /// Group paths to join, only read when the user is created
+    pub groups: Option<Vec<String>>,
+    /// Credentials to set, only read when the user is created
+    pub credentials: Option<Vec<CredentialRepresentation>>,
+    pub federation_link: Option<String>,
+    pub service_account_client_id: Option<String>,
+    pub created_timestamp: Option<i64>,
+    /// Fields this crate does not model, kept so representations round-trip
+    #[serde(flatten)]
+    pub extra: HashMap<String, serde_json::Value>,
+}
+
+impl UserRepresentation {
+    pub fn new(username: impl Into<String>) -> Self {
+        Self {
+            username: Some(username.into()),
+            enabled: Some(true),
+            ..Default::default()
+        }
+    }
+}
+
+#[skip_serializing_none]
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct CredentialRepresentation {
+    pub id: Option<String>,
+    #[serde(rename = "type")]
+    pub credential_type: Option<String>,
+    pub value: Option<String>,
+    pub temporary: Option<bool>,
+    pub user_label: Option<String>,
+    pub created_date: Option<i64>,
+}
+
+impl CredentialRepresentation {
+    /// A password credential. A `temporary` password must be changed at the next sign in.
+    pub fn password(value: impl Into<String>, temporary: bool) -> Self {
+        Self {
+            credential_type: Some("password".into()),
+            value: Some(value.into()),
+            temporary: Some(temporary),
+            ..Default::default()
+        }
+    }
+}
+
+/// Keycloak's built-in required actions. Custom actions are plain strings.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum RequiredAction {
+    VerifyEmail,
+    UpdateProfile,
+    UpdatePassword,
+    ConfigureTotp,
+    TermsAndConditions,
+    UpdateEmail,
+    WebauthnRegister,
+}
+
+impl RequiredAction {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            RequiredAction::VerifyEmail => "VERIFY_EMAIL",
+            RequiredAction::UpdateProfile => "UPDATE_PROFILE",
+            RequiredAction::UpdatePassword => "UPDATE_PASSWORD",
+            RequiredAction::ConfigureTotp => "CONFIGURE_TOTP",
+            RequiredAction::TermsAndConditions => "TERMS_AND_CONDITIONS",
+            RequiredAction::UpdateEmail => "UPDATE_EMAIL",
+            RequiredAction::WebauthnRegister => "webauthn-register",
+        }
+    }
+}
+
+impl From<RequiredAction> for String {
+    fn from(action: RequiredAction) -> Self {
+        action.as_str().to_string()
+    }
+}
+
+#[skip_serializing_none]
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct GroupRepresentation {
+    pub id: Option<String>,
+    pub name: Option<String>,
+    pub path: Option<String>,
+    pub parent_id: Option<String>,
+    pub sub_group_count: Option<i64>,
+    pub sub_groups: Option<Vec<GroupRepresentation>>,
+    pub attributes: Option<HashMap<String, Vec<String>>>,
+    pub realm_roles: Option<Vec<String>>,
+    pub client_roles: Option<HashMap<String, Vec<String>>>,
+    /// Fields this crate does not model, kept so representations round-trip
+    #[serde(flatten)]
+    pub extra: HashMap<String, serde_json::Value>,
+}
+
+impl GroupRepresentation {
+    pub fn new(name: impl Into<String>) -> Self {
+        Self {
+            name: Some(name.into()),
+            ..Default::default()
+        }
+    }
+}
+
+#[skip_serializing_none]
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct RoleRepresentation {
+    pub id: Option<String>,
+    pub name: Option<String>,
+    pub description: Option<String>,
+    pub composite: Option<bool>,
+    pub client_role: Option<bool>,
+    /// Id of the realm or client the role belongs to
+    pub container_id: Option<String>,
+    pub attributes: Option<HashMap<String, Vec<String>>>,
+    /// Fields this crate does not model, kept so representations round-trip
+    #[serde(flatten)]
+    pub extra: HashMap<String, serde_json::Value>,
+}
+
+impl RoleRepresentation {
+    pub fn new(name: impl Into<String>) -> Self {
+        Self {
+            name: Some(name.into()),
+            ..Default::default()
+        }
+    }
+}
+
+/// The realm and client roles mapped to a user or group.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct MappingsRepresentation {
+    #[serde(default)]
+    pub realm_mappings: Vec<RoleRepresentation>,
+    /// Keyed by client id (not the client's UUID)
+    #[serde(default)]
+    pub client_mappings: HashMap<String, ClientMappingsRepresentation>,
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ClientMappingsRepresentation {
+    /// The client's UUID
+    pub id: Option<String>,
+    /// The client id
+    pub client: Option<String>,
+    #[serde(default)]
+    pub mappings: Vec<RoleRepresentation>,
+}
+
+#[skip_serializing_none]
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ClientRepresentation {
+    /// The client's UUID, assigned by Keycloak
+    pub id: Option<String>,
+    pub client_id: Option<String>,
+    pub name: Option<String>,
+    pub description: Option<String>,
+    pub enabled: Option<bool>,
+    pub protocol: Option<String>,
+    pub public_client: Option<bool>,
+    pub bearer_only: Option<bool>,
+    pub secret: Option<String>,
+    pub client_authenticator_type: Option<String>,
+    pub root_url: Option<String>,
+    pub base_url: Option<String>,
+    pub admin_url: Option<String>,
+    pub redirect_uris: Option<Vec<String>>,
+    pub web_origins: Option<Vec<String>>,
+    pub standard_flow_enabled: Option<bool>,
+    pub implicit_flow_enabled: Option<bool>,
+    pub direct_access_grants_enabled: Option<bool>,
+    pub service_accounts_enabled: Option<bool>,
+    pub authorization_services_enabled: Option<bool>,
+    pub consent_required: Option<bool>,
+    pub full_scope_allowed: Option<bool>,
+    pub frontchannel_logout: Option<bool>,
+    /// Free-form settings such as `post.logout.redirect.uris` or
+    /// `oauth2.device.authorization.grant.enabled`
+    pub attributes: Option<HashMap<String, String>>,
+    pub default_client_scopes: Option<Vec<String>>,
+    pub optional_client_scopes: Option<Vec<String>>,
+    pub protocol_mappers: Option<Vec<ProtocolMapperRepresentation>>,
+    /// Fields this crate does not model, kept so representations round-trip
+    #[serde(flatten)]
+    pub extra: HashMap<String, serde_json::Value>,
+}
+
+impl ClientRepresentation {
+    /// An enabled OpenID Connect client.
+    pub fn new(client_id: impl Into<String>) -> Self {
+        Self {
+            client_id: Some(client_id.into()),
+            enabled: Some(true),
+            protocol: Some(OPENID_CONNECT.into()),
+            ..Default::default()
+        }
+    }
+}
+
+pub const OPENID_CONNECT: &str = "openid-connect";
+
+#[skip_serializing_none]
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ProtocolMapperRepresentation {
+    pub id: Option<String>,
+    pub name: Option<String>,
+    pub protocol: Option<String>,
+    /// The mapper type, such as `oidc-usermodel-attribute-mapper`
+    pub protocol_mapper: Option<String>,
+    pub config: Option<HashMap<String, String>>,
+}
+
+impl ProtocolMapperRepresentation {
+    /// An OpenID Connect mapper of type `protocol_mapper`.
+    pub fn new(name: impl Into<String>, protocol_mapper: impl Into<String>) -> Self {
+        Self {
+            name: Some(name.into()),
+            protocol: Some(OPENID_CONNECT.into()),
+            protocol_mapper: Some(protocol_mapper.into()),
+            config: Some(HashMap::new()),
+            ..Default::default()
+        }
+    }
+
+    pub fn config(mut self, key: impl Into<String>, value: impl Into<String>) -> Self {
+        self.config
+            .get_or_insert_with(HashMap::new)
+            .insert(key.into(), value.into());
+        self
+    }
+
+    /// Adds `audience` to the `aud` claim of access tokens.
+    pub fn audience(name: impl Into<String>, audience: impl Into<String>) -> Self {
+        Self::new(name, "oidc-audience-mapper")
+            .config("included.client.audience", audience)
+            .config("access.token.claim", "true")
+            .config("id.token.claim", "false")
+    }
+
+    /// Copies the user attribute `attribute` into the token claim `claim`.
+    pub fn user_attribute(
+        name: impl Into<String>,
+        attribute: impl Into<String>,
+        claim: impl Into<String>,
+    ) -> Self {
+        Self::new(name, "oidc-usermodel-attribute-mapper")
+            .config("user.attribute", attribute)
+            .config("claim.name", claim)
+            .config("jsonType.label", "String")
+            .config("access.token.claim", "true")
+            .config("id.token.claim", "true")
+            .config("userinfo.token.claim", "true")
+    }
+
+    /// Lists the user's group paths in the `groups` claim.
+    pub fn group_membership(name: impl Into<String>) -> Self {
+        Self::new(name, "oidc-group-membership-mapper")
+            .config("claim.name", "groups")
+            .config("full.path", "true")
+            .config("access.token.claim", "true")
+            .config("id.token.claim", "true")
+            .config("userinfo.token.claim", "true")
+    }
+}
+
+#[skip_serializing_none]
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ClientScopeRepresentation {
+    pub id: Option<String>,
+    pub name: Option<String>,
+    pub description: Option<String>,
+    pub protocol: Option<String>,
+    /// Settings such as `include.in.token.scope` or `display.on.consent.screen`
+    pub attributes: Option<HashMap<String, String>>,
+    pub protocol_mappers: Option<Vec<ProtocolMapperRepresentation>>,
+    /// Fields this crate does not model, kept so representations round-trip
+    #[serde(flatten)]
+    pub extra: HashMap<String, serde_json::Value>,
+}
+
+impl ClientScopeRepresentation {
+    /// An OpenID Connect client scope.
+    pub fn new(name: impl Into<String>) -> Self {
+        Self {
+            name: Some(name.into()),
+            protocol: Some(OPENID_CONNECT.into()),
+            ..Default::default()
+        }
+    }
+}
+
+#[skip_serializing_none]
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct IdentityProviderRepresentation {
+    pub alias: Option<String>,
+    pub display_name: Option<String>,
+    /// The provider type, such as `oidc`, `saml` or `github`
+    pub provider_id: Option<String>,
+    pub enabled: Option<bool>,
+    pub trust_email: Option<bool>,
+    pub store_token: Option<bool>,
+    pub add_read_token_role_on_create: Option<bool>,
+    pub authenticate_by_default: Option<bool>,
+    pub link_only: Option<bool>,
+    pub hide_on_login: Option<bool>,
+    pub first_broker_login_flow_alias: Option<String>,
+    pub post_broker_login_flow_alias: Option<String>,
+    /// Provider settings such as `clientId`, `clientSecret` or `authorizationUrl`
+    pub config: Option<HashMap<String, String>>,
+    pub internal_id: Option<String>,
+    /// Fields this crate does not model, kept so representations round-trip
+    #[serde(flatten)]
+    pub extra: HashMap<String, serde_json::Value>,
+}
+
+impl IdentityProviderRepresentation {
+    pub fn new(alias: impl Into<String>, provider_id: impl Into<String>) -> Self {
+        Self {
+            alias: Some(alias.into()),
+            provider_id: Some(provider_id.into()),
+            enabled: Some(true),
+            ..Default::default()
+        }
+    }
+
+    pub fn config(mut self, key: impl Into<String>, value: impl Into<String>) -> Self {
+        self.config
+            .get_or_insert_with(HashMap::new)
+            .insert(key.into(), value.into());
+        self
+    }
+}
+
+/// A realm as exported by Keycloak, or the desired state of one.
+#[skip_serializing_none]
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct RealmRepresentation {
+    pub id: Option<String>,
+    pub realm: Option<String>,
+    pub display_name: Option<String>,
+    pub enabled: Option<bool>,
+    pub clients: Option<Vec<ClientRepresentation>>,
+    pub roles: Option<RolesRepresentation>,
+    /// Top level groups, with their subgroups nested in `sub_groups`
+    pub groups: Option<Vec<GroupRepresentation>>,
+    pub client_scopes: Option<Vec<ClientScopeRepresentation>>,
+    pub identity_providers: Option<Vec<IdentityProviderRepresentation>>,
+    pub users: Option<Vec<UserRepresentation>>,
+    /// Fields this crate does not model, kept so representations round-trip
+    #[serde(flatten)]
+    pub extra: HashMap<String, serde_json::Value>,
+}
+
+impl RealmRepresentation {
+    /// Reads a realm export (or a hand written desired state) from JSON.
+    pub fn from_json(json: &str) -> Result<Self, serde_json::Error> {
+        serde_json::from_str(json)
+    }
+}
+
+#[skip_serializing_none]
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct RolesRepresentation {
+    pub realm: Option<Vec<RoleRepresentation>>,
+    /// Keyed by client id (not the client's UUID)
+    pub client: Option<HashMap<String, Vec<RoleRepresentation>>>,
+}
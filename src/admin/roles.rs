@@ -0,0 +1,230 @@
+use reqwest::Method;
+use serde::Serialize;
+
+use crate::client::ClientError;
+
+use super::{KeycloakAdmin, MappingsRepresentation, Page, RoleRepresentation, UserRepresentation};
+
+/// Search and paging parameters of the role listings.
+#[serde_with::skip_serializing_none]
+#[derive(Debug, Clone, Default, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct RoleQuery {
+    pub search: Option<String>,
+    pub brief_representation: Option<bool>,
+    pub first: Option<u32>,
+    pub max: Option<u32>,
+}
+
+impl RoleQuery {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn search(mut self, search: impl Into<String>) -> Self {
+        self.search = Some(search.into());
+        self
+    }
+
+    pub fn brief(mut self, brief: bool) -> Self {
+        self.brief_representation = Some(brief);
+        self
+    }
+
+    pub fn page(mut self, first: u32, max: u32) -> Self {
+        self.first = Some(first);
+        self.max = Some(max);
+        self
+    }
+}
+
+/// Who roles are mapped to.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RoleHolder<'a> {
+    /// A user, by id
+    User(&'a str),
+    /// A group, by id
+    Group(&'a str),
+}
+
+impl<'a> RoleHolder<'a> {
+    fn segments(&self) -> [&'a str; 3] {
+        match self {
+            RoleHolder::User(id) => ["users", id, "role-mappings"],
+            RoleHolder::Group(id) => ["groups", id, "role-mappings"],
+        }
+    }
+}
+
+impl KeycloakAdmin {
+    pub async fn realm_roles(
+        &self,
+        query: &RoleQuery,
+    ) -> Result<Vec<RoleRepresentation>, ClientError> {
+        self.get(&["roles"], query).await
+    }
+
+    pub async fn realm_role(&self, name: &str) -> Result<Option<RoleRepresentation>, ClientError> {
+        self.find(&["roles", name]).await
+    }
+
+    pub async fn create_realm_role(&self, role: &RoleRepresentation) -> Result<(), ClientError> {
+        self.send_json(Method::POST, &["roles"], role).await?;
+        Ok(())
+    }
+
+    pub async fn update_realm_role(
+        &self,
+        name: &str,
+        role: &RoleRepresentation,
+    ) -> Result<(), ClientError> {
+        self.send_json(Method::PUT, &["roles", name], role).await?;
+        Ok(())
+    }
+
+    pub async fn delete_realm_role(&self, name: &str) -> Result<(), ClientError> {
+        self.delete(&["roles", name]).await
+    }
+
+    /// The users the realm role is directly mapped to.
+    pub async fn realm_role_users(
+        &self,
+        name: &str,
+        page: Page,
+    ) -> Result<Vec<UserRepresentation>, ClientError> {
+        self.get(&["roles", name, "users"], &page).await
+    }
+
+    /// The roles the composite role with id `id` is made of.
+    pub async fn role_composites(&self, id: &str) -> Result<Vec<RoleRepresentation>, ClientError> {
+        self.get(&["roles-by-id", id, "composites"], &()).await
+    }
+
+    pub async fn client_roles(
+        &self,
+        client_uuid: &str,
+        query: &RoleQuery,
+    ) -> Result<Vec<RoleRepresentation>, ClientError> {
+        self.get(&["clients", client_uuid, "roles"], query).await
+    }
+
+    pub async fn client_role(
+        &self,
+        client_uuid: &str,
+        name: &str,
+    ) -> Result<Option<RoleRepresentation>, ClientError> {
+        self.find(&["clients", client_uuid, "roles", name]).await
+    }
+
+    pub async fn create_client_role(
+        &self,
+        client_uuid: &str,
+        role: &RoleRepresentation,
+    ) -> Result<(), ClientError> {
+        self.send_json(Method::POST, &["clients", client_uuid, "roles"], role)
+            .await?;
+        Ok(())
+    }
+
+    pub async fn update_client_role(
+        &self,
+        client_uuid: &str,
+        name: &str,
+        role: &RoleRepresentation,
+    ) -> Result<(), ClientError> {
+        self.send_json(Method::PUT, &["clients", client_uuid, "roles", name], role)
+            .await?;
+        Ok(())
+    }
+
+    pub async fn delete_client_role(
+        &self,
+        client_uuid: &str,
+        name: &str,
+    ) -> Result<(), ClientError> {
+        self.delete(&["clients", client_uuid, "roles", name]).await
+    }
+
+    /// All realm and client roles directly mapped to `holder`.
+    pub async fn role_mappings(
+        &self,
+        holder: RoleHolder<'_>,
+    ) -> Result<MappingsRepresentation, ClientError> {
+        self.get(&holder.segments(), &()).await
+    }
+
+    pub async fn realm_role_mappings(
+        &self,
+        holder: RoleHolder<'_>,
+    ) -> Result<Vec<RoleRepresentation>, ClientError> {
+        self.get(&[&holder.segments()[..], &["realm"]].concat(), &())
+            .await
+    }
+
+    /// The realm roles of `holder` including those granted through composite roles and groups.
+    pub async fn effective_realm_roles(
+        &self,
+        holder: RoleHolder<'_>,
+    ) -> Result<Vec<RoleRepresentation>, ClientError> {
+        self.get(
+            &[&holder.segments()[..], &["realm", "composite"]].concat(),
+            &(),
+        )
+        .await
+    }
+
+    /// Maps realm roles to `holder`. The roles need at least their `id` and `name`, as
+    /// returned by `realm_role`.
+    pub async fn add_realm_role_mappings(
+        &self,
+        holder: RoleHolder<'_>,
+        roles: &[RoleRepresentation],
+    ) -> Result<(), ClientError> {
+        let segments = [&holder.segments()[..], &["realm"]].concat();
+        self.send_json(Method::POST, &segments, roles).await?;
+        Ok(())
+    }
+
+    pub async fn remove_realm_role_mappings(
+        &self,
+        holder: RoleHolder<'_>,
+        roles: &[RoleRepresentation],
+    ) -> Result<(), ClientError> {
+        let segments = [&holder.segments()[..], &["realm"]].concat();
+        self.send_json(Method::DELETE, &segments, roles).await?;
+        Ok(())
+    }
+
+    pub async fn client_role_mappings(
+        &self,
+        holder: RoleHolder<'_>,
+        client_uuid: &str,
+    ) -> Result<Vec<RoleRepresentation>, ClientError> {
+        let segments = [&holder.segments()[..], &["clients", client_uuid]].concat();
+        self.get(&segments, &()).await
+    }
+
+    /// Maps roles of the client `client_uuid` to `holder`. The roles need at least their `id`
+    /// and `name`, as returned by `client_role`.
+    pub async fn add_client_role_mappings(
+        &self,
+        holder: RoleHolder<'_>,
+        client_uuid: &str,
+        roles: &[RoleRepresentation],
+    ) -> Result<(), ClientError> {
+        let segments = [&holder.segments()[..], &["clients", client_uuid]].concat();
+        self.send_json(Method::POST, &segments, roles).await?;
+        Ok(())
+    }
+
+    pub async fn remove_client_role_mappings(
+        &self,
+        holder: RoleHolder<'_>,
+        client_uuid: &str,
+        roles: &[RoleRepresentation],
+    ) -> Result<(), ClientError> {
+        let segments = [&holder.segments()[..], &["clients", client_uuid]].concat();
+        self.send_json(Method::DELETE, &segments, roles).await?;
+        Ok(())
+    }
+}
@@ -0,0 +1,206 @@
+use reqwest::Method;
+use serde::Serialize;
+
+use crate::client::ClientError;
+
+use super::{
+    CredentialRepresentation, GroupRepresentation, KeycloakAdmin, Page, UserRepresentation,
+};
+
+/// Search and paging parameters of `KeycloakAdmin::users`.
+#[serde_with::skip_serializing_none]
+#[derive(Debug, Clone, Default, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct UserQuery {
+    /// Matched against username, first and last name and email
+    pub search: Option<String>,
+    pub username: Option<String>,
+    pub email: Option<String>,
+    pub first_name: Option<String>,
+    pub last_name: Option<String>,
+    pub enabled: Option<bool>,
+    pub email_verified: Option<bool>,
+    /// Match the field filters exactly instead of as substrings
+    pub exact: Option<bool>,
+    /// Attribute filters, as `key:value` pairs separated by spaces
+    pub q: Option<String>,
+    pub brief_representation: Option<bool>,
+    pub first: Option<u32>,
+    pub max: Option<u32>,
+}
+
+impl UserQuery {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn search(mut self, search: impl Into<String>) -> Self {
+        self.search = Some(search.into());
+        self
+    }
+
+    pub fn username(mut self, username: impl Into<String>) -> Self {
+        self.username = Some(username.into());
+        self
+    }
+
+    pub fn email(mut self, email: impl Into<String>) -> Self {
+        self.email = Some(email.into());
+        self
+    }
+
+    pub fn enabled(mut self, enabled: bool) -> Self {
+        self.enabled = Some(enabled);
+        self
+    }
+
+    pub fn exact(mut self, exact: bool) -> Self {
+        self.exact = Some(exact);
+        self
+    }
+
+    /// Only users whose attribute `key` is `value`.
+    pub fn attribute(mut self, key: &str, value: &str) -> Self {
+        let filter = format!("{}:{}", key, value);
+        self.q = Some(match self.q {
+            Some(q) => format!("{} {}", q, filter),
+            None => filter,
+        });
+        self
+    }
+
+    pub fn brief(mut self, brief: bool) -> Self {
+        self.brief_representation = Some(brief);
+        self
+    }
+
+    pub fn page(mut self, first: u32, max: u32) -> Self {
+        self.first = Some(first);
+        self.max = Some(max);
+        self
+    }
+}
+
+impl KeycloakAdmin {
+    pub async fn users(&self, query: &UserQuery) -> Result<Vec<UserRepresentation>, ClientError> {
+        self.get(&["users"], query).await
+    }
+
+    /// Counts the users matching `query`; paging parameters are ignored.
+    pub async fn count_users(&self, query: &UserQuery) -> Result<u64, ClientError> {
+        self.get(&["users", "count"], query).await
+    }
+
+    /// Returns the user, or `None` when there is no user with that id.
+    pub async fn user(&self, id: &str) -> Result<Option<UserRepresentation>, ClientError> {
+        self.find(&["users", id]).await
+    }
+
+    /// Looks a user up by exact username.
+    pub async fn user_by_username(
+        &self,
+        username: &str,
+    ) -> Result<Option<UserRepresentation>, ClientError> {
+        let query = UserQuery::new().username(username).exact(true);
+        Ok(self.users(&query).await?.into_iter().next())
+    }
+
+    /// Creates the user and returns its id.
+    pub async fn create_user(&self, user: &UserRepresentation) -> Result<String, ClientError> {
+        self.create(&["users"], user).await
+    }
+
+    /// Updates the fields set in `user`.
+    pub async fn update_user(
+        &self,
+        id: &str,
+        user: &UserRepresentation,
+    ) -> Result<(), ClientError> {
+        self.send_json(Method::PUT, &["users", id], user).await?;
+        Ok(())
+    }
+
+    pub async fn delete_user(&self, id: &str) -> Result<(), ClientError> {
+        self.delete(&["users", id]).await
+    }
+
+    /// Sets the user's password. A `temporary` password must be changed at the next sign in.
+    pub async fn reset_password(
+        &self,
+        id: &str,
+        password: &str,
+        temporary: bool,
+    ) -> Result<(), ClientError> {
+        let credential = CredentialRepresentation::password(password, temporary);
+        self.send_json(Method::PUT, &["users", id, "reset-password"], &credential)
+            .await?;
+        Ok(())
+    }
+
+    pub async fn user_credentials(
+        &self,
+        id: &str,
+    ) -> Result<Vec<CredentialRepresentation>, ClientError> {
+        self.get(&["users", id, "credentials"], &()).await
+    }
+
+    pub async fn delete_user_credential(
+        &self,
+        id: &str,
+        credential_id: &str,
+    ) -> Result<(), ClientError> {
+        self.delete(&["users", id, "credentials", credential_id])
+            .await
+    }
+
+    /// Replaces the actions the user has to complete at the next sign in.
+    pub async fn set_required_actions<A: Into<String>>(
+        &self,
+        id: &str,
+        actions: impl IntoIterator<Item = A>,
+    ) -> Result<(), ClientError> {
+        let user = UserRepresentation {
+            required_actions: Some(actions.into_iter().map(Into::into).collect()),
+            ..Default::default()
+        };
+        self.update_user(id, &user).await
+    }
+
+    /// Emails the user a link to complete `actions`.
+    pub async fn execute_actions_email<A: Into<String>>(
+        &self,
+        id: &str,
+        actions: impl IntoIterator<Item = A>,
+    ) -> Result<(), ClientError> {
+        let actions = actions.into_iter().map(Into::into).collect::<Vec<String>>();
+        self.send_json(
+            Method::PUT,
+            &["users", id, "execute-actions-email"],
+            &actions,
+        )
+        .await?;
+        Ok(())
+    }
+
+    pub async fn user_groups(
+        &self,
+        id: &str,
+        page: Page,
+    ) -> Result<Vec<GroupRepresentation>, ClientError> {
+        self.get(&["users", id, "groups"], &page).await
+    }
+
+    pub async fn add_user_to_group(&self, id: &str, group_id: &str) -> Result<(), ClientError> {
+        self.send_query(Method::PUT, &["users", id, "groups", group_id], &())
+            .await?;
+        Ok(())
+    }
+
+    pub async fn remove_user_from_group(
+        &self,
+        id: &str,
+        group_id: &str,
+    ) -> Result<(), ClientError> {
+        self.delete(&["users", id, "groups", group_id]).await
+    }
+}
@@ -0,0 +1,174 @@
+//! The git and docker credential helper protocols, answering with the access token of the
+//! profile mapped to the requested host.
+//!
+//! Neither helper signs in: a helper runs without a terminal, so when there is no session it
+//! answers like a helper without credentials and points to `kc-oauth login` on stderr.
+
+use std::{
+    collections::{BTreeMap, HashMap},
+    io::{BufRead, Read},
+};
+
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    profile::{host_of, Config, HostMapping},
+    session,
+};
+
+#[derive(Debug, Clone, Copy, clap::ValueEnum)]
+pub enum GitAction {
+    Get,
+    Store,
+    Erase,
+}
+
+#[derive(Debug, Clone, Copy, clap::ValueEnum)]
+pub enum DockerAction {
+    Get,
+    Store,
+    Erase,
+    List,
+}
+
+/// The message docker expects when a helper has no credentials for a server.
+const DOCKER_NOT_FOUND: &str = "credentials not found in native keychain";
+
+/// An access token for `server` and when it expires, or `None` when the host is not mapped or
+/// the profile is logged out.
+async fn credential<'a>(
+    config: &'a Config,
+    server: &str,
+) -> anyhow::Result<Option<(&'a HostMapping, String, DateTime<Utc>)>> {
+    let Some(mapping) = config.host(server) else {
+        return Ok(None);
+    };
+    let (name, profile) = config.profile(Some(&mapping.profile))?;
+    let client = profile.client(&name)?;
+    match session::fresh_token(&client).await {
+        Ok(_) => {
+            let cached = client.load_cached_token().await?;
+            Ok(Some((mapping, cached.access_token, cached.expires_at)))
+        }
+        Err(e) if e.requires_reauthentication() => {
+            eprintln!(
+                "kc-oauth: not logged in to profile {:?} for {}, run `kc-oauth login --profile {}`",
+                name,
+                host_of(server),
+                name
+            );
+            Ok(None)
+        }
+        Err(e) => Err(e.into()),
+    }
+}
+
+/// Expires the cached token of `server`'s profile if it is `token`, after a server rejected it.
+async fn reject(config: &Config, server: &str, token: &str) -> anyhow::Result<()> {
+    let Some(mapping) = config.host(server) else {
+        return Ok(());
+    };
+    let (name, profile) = config.profile(Some(&mapping.profile))?;
+    session::expire_access_token(&profile.client(&name)?, token).await?;
+    Ok(())
+}
+
+/// Implements `git credential-<helper> get|store|erase`: `key=value` lines on stdin, the
+/// username and password on stdout.
+pub async fn git(action: GitAction, config: &Config) -> anyhow::Result<()> {
+    let request = read_git_request(std::io::stdin().lock())?;
+    let host = request.get("host").map(String::as_str).unwrap_or_default();
+    // never send a token in clear text
+    if request.get("protocol").map(String::as_str) != Some("https") || host.is_empty() {
+        return Ok(());
+    }
+
+    match action {
+        GitAction::Get => {
+            if let Some((mapping, token, expires_at)) = credential(config, host).await? {
+                println!("username={}", mapping.username());
+                println!("password={}", token);
+                println!("password_expiry_utc={}", expires_at.timestamp());
+            }
+        }
+        // tokens come from Keycloak, there is nothing to store
+        GitAction::Store => {}
+        GitAction::Erase => {
+            if let Some(password) = request.get("password") {
+                reject(config, host, password).await?;
+            }
+        }
+    }
+    Ok(())
+}
+
+fn read_git_request(input: impl BufRead) -> std::io::Result<HashMap<String, String>> {
+    let mut request = HashMap::new();
+    for line in input.lines() {
+        let line = line?;
+        if line.is_empty() {
+            break;
+        }
+        if let Some((key, value)) = line.split_once('=') {
+            request.insert(key.to_string(), value.to_string());
+        }
+    }
+    Ok(request)
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(rename_all = "PascalCase")]
+struct DockerCredential {
+    #[serde(rename = "ServerURL")]
+    server_url: String,
+    username: String,
+    secret: String,
+}
+
+/// Implements `docker-credential-<helper> get|store|erase|list`: the server URL (or, for
+/// `store`, a credential) on stdin, JSON on stdout.
+pub async fn docker(action: DockerAction, config: &Config) -> anyhow::Result<()> {
+    let mut input = String::new();
+    std::io::stdin().read_to_string(&mut input)?;
+    let server = input.trim();
+
+    match action {
+        DockerAction::Get => match credential(config, server).await? {
+            Some((mapping, token, _)) => {
+                let credential = DockerCredential {
+                    server_url: server.to_string(),
+                    username: mapping.username().to_string(),
+                    secret: token,
+                };
+                println!("{}", serde_json::to_string(&credential)?);
+            }
+            None => {
+                // docker reads the error from stdout
+                println!("{}", DOCKER_NOT_FOUND);
+                std::process::exit(1);
+            }
+        },
+        DockerAction::Store => {}
+        DockerAction::Erase => {
+            let Some(mapping) = config.host(server) else {
+                return Ok(());
+            };
+            let (name, profile) = config.profile(Some(&mapping.profile))?;
+            let client = profile.client(&name)?;
+            if let Ok(cached) = client.load_cached_token().await {
+                session::expire_access_token(&client, &cached.access_token).await?;
+            }
+        }
+        DockerAction::List => {
+            let hosts: BTreeMap<&str, &str> = config
+                .hosts
+                .iter()
+                .filter(|(host, _)| !host.starts_with("*."))
+                .map(|(host, mapping)| (host.as_str(), mapping.username()))
+                .collect();
+            println!("{}", serde_json::to_string(&hosts)?);
+        }
+    }
+    Ok(())
+}
@@ -0,0 +1,90 @@
+use anyhow::bail;
+use chrono::{DateTime, SecondsFormat, Utc};
+use serde::{Deserialize, Serialize};
+
+use crate::{profile::Profile, session};
+
+const API_VERSION: &str = "client.authentication.k8s.io/v1";
+
+/// The response of a kubectl exec credential plugin.
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ExecCredential {
+    pub api_version: String,
+    pub kind: &'static str,
+    pub status: ExecCredentialStatus,
+}
+
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ExecCredentialStatus {
+    pub token: String,
+    /// kubectl reuses the token until then
+    pub expiration_timestamp: String,
+}
+
+impl ExecCredential {
+    fn new(api_version: String, token: String, expires_at: DateTime<Utc>) -> Self {
+        Self {
+            api_version,
+            kind: "ExecCredential",
+            status: ExecCredentialStatus {
+                token,
+                expiration_timestamp: expires_at.to_rfc3339_opts(SecondsFormat::Secs, true),
+            },
+        }
+    }
+}
+
+/// What kubectl passes in `KUBERNETES_EXEC_INFO`.
+#[derive(Debug, Default, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct ExecInfo {
+    api_version: Option<String>,
+    #[serde(default)]
+    spec: ExecInfoSpec,
+}
+
+#[derive(Debug, Deserialize)]
+struct ExecInfoSpec {
+    interactive: bool,
+}
+
+impl Default for ExecInfoSpec {
+    /// Run by hand, without kubectl, there is a terminal to prompt on.
+    fn default() -> Self {
+        Self { interactive: true }
+    }
+}
+
+/// Returns the profile's access token as an `ExecCredential`. When the user has to sign in
+/// again the device flow is run on stderr, unless kubectl says there is nobody to answer it.
+pub async fn exec_credential(profile: &Profile, name: &str) -> anyhow::Result<ExecCredential> {
+    let client = profile.client(name)?;
+    let exec_info: ExecInfo = match std::env::var("KUBERNETES_EXEC_INFO") {
+        Ok(json) => serde_json::from_str(&json)?,
+        Err(_) => ExecInfo::default(),
+    };
+
+    match session::fresh_token(&client).await {
+        Ok(_) => {}
+        Err(e) if e.requires_reauthentication() => {
+            if !exec_info.spec.interactive {
+                bail!(
+                    "not logged in to profile {:?} and kubectl is not interactive, run `kc-oauth login --profile {}`",
+                    name,
+                    name
+                );
+            }
+            session::device_login(profile, name).await?;
+        }
+        Err(e) => return Err(e.into()),
+    }
+
+    let cached = client.load_cached_token().await?;
+    Ok(ExecCredential::new(
+        exec_info.api_version.unwrap_or_else(|| API_VERSION.into()),
+        cached.access_token,
+        cached.expires_at,
+    ))
+}
@@ -0,0 +1,293 @@
+//! `kc-oauth`: sign in to Keycloak from a shell and hand tokens to other tools.
+//!
+//! Profiles are kept in `~/.config/kc-oauth/config.toml` (or `$KC_OAUTH_CONFIG`):
+//!
+//! ```toml
+//! default_profile = "work"
+//!
+//! [profiles.work]
+//! server_url = "https://sso.example.com"
+//! realm = "engineering"
+//! client_id = "kc-oauth"
+//! flow = "device"
+//!
+//! [hosts."git.example.com"]
+//! profile = "work"
+//! ```
+
+mod credential_helpers;
+mod kubectl;
+mod profile;
+mod session;
+
+use std::path::{Path, PathBuf};
+
+use clap::{Parser, Subcommand};
+
+use credential_helpers::{DockerAction, GitAction};
+use profile::{host_of, Config, HostMapping, LoginFlow, Profile};
+
+#[derive(Debug, Parser)]
+#[command(name = "kc-oauth", version, about = "Keycloak tokens for the shell")]
+struct Cli {
+    /// Profile to use instead of the configured default
+    #[arg(short, long, global = true, env = "KC_OAUTH_PROFILE")]
+    profile: Option<String>,
+
+    /// Config file holding the profiles
+    #[arg(long, global = true)]
+    config: Option<PathBuf>,
+
+    #[command(subcommand)]
+    command: Command,
+}
+
+#[derive(Debug, Subcommand)]
+enum Command {
+    #[command(flatten)]
+    Session(SessionCommand),
+    /// Act as a git credential helper for the hosts mapped with `kc-oauth host add`
+    GitCredential {
+        #[arg(value_enum)]
+        action: GitAction,
+    },
+    /// Act as a docker credential helper for the hosts mapped with `kc-oauth host add`
+    DockerCredential {
+        #[arg(value_enum)]
+        action: DockerAction,
+    },
+    /// Manage profiles
+    #[command(subcommand)]
+    Profile(ProfileCommand),
+    /// Map hosts to the profile whose tokens the credential helpers hand them
+    #[command(subcommand)]
+    Host(HostCommand),
+}
+
+/// The commands acting on the tokens of one profile.
+#[derive(Debug, Subcommand)]
+enum SessionCommand {
+    /// Sign in and cache the tokens
+    Login {
+        /// Overrides the profile's flow
+        #[arg(long, value_enum)]
+        flow: Option<LoginFlow>,
+    },
+    /// Print an access token, refreshing it when it is about to expire
+    Token,
+    /// Print the verified claims of the access token
+    Whoami,
+    /// Revoke the tokens, end the Keycloak session and clear the cache
+    Logout,
+    /// Act as a kubectl exec credential plugin, printing an ExecCredential
+    Kubectl,
+}
+
+#[derive(Debug, Subcommand)]
+enum ProfileCommand {
+    /// Add or replace a profile
+    Add {
+        name: String,
+        #[arg(long)]
+        server_url: String,
+        #[arg(long)]
+        realm: String,
+        #[arg(long)]
+        client_id: String,
+        #[arg(long)]
+        client_secret: Option<String>,
+        #[arg(long, value_enum, default_value_t = LoginFlow::Device)]
+        flow: LoginFlow,
+        /// Scope to request, repeatable
+        #[arg(long = "scope")]
+        scopes: Vec<String>,
+        /// Port of the loopback redirect of the browser flow
+        #[arg(long)]
+        redirect_port: Option<u16>,
+        /// Make it the default profile
+        #[arg(long)]
+        default: bool,
+    },
+    /// List the profiles
+    List,
+    /// Remove a profile and its cached tokens
+    Remove { name: String },
+}
+
+#[derive(Debug, Subcommand)]
+enum HostCommand {
+    /// Map a host, or `*.domain`, to `--profile` or the default profile
+    Add {
+        host: String,
+        /// Username sent along with the token, `oauth2` by default
+        #[arg(long)]
+        username: Option<String>,
+    },
+    /// List the mapped hosts
+    List,
+    /// Remove a host mapping
+    Remove { host: String },
+}
+
+/// The arguments, with the helper subcommand inserted when the binary is installed as
+/// `git-credential-<name>` or `docker-credential-<name>`, the names git and docker look for.
+fn args() -> Vec<std::ffi::OsString> {
+    let mut args: Vec<_> = std::env::args_os().collect();
+    let program = args
+        .first()
+        .and_then(|arg0| Path::new(arg0).file_name())
+        .map(|name| name.to_string_lossy().into_owned())
+        .unwrap_or_default();
+    if program.starts_with("git-credential-") {
+        args.insert(1, "git-credential".into());
+    } else if program.starts_with("docker-credential-") {
+        args.insert(1, "docker-credential".into());
+    }
+    args
+}
+
+#[tokio::main]
+async fn main() -> anyhow::Result<()> {
+    let cli = Cli::parse_from(args());
+    let config_path = match cli.config {
+        Some(path) => path,
+        None => Config::default_path()?,
+    };
+    let mut config = Config::load(&config_path)?;
+
+    match cli.command {
+        Command::Session(command) => {
+            let (name, profile) = config.profile(cli.profile.as_deref())?;
+            session_command(command, &name, profile).await
+        }
+        Command::Profile(command) => profile_command(command, &mut config, &config_path),
+        Command::Host(command) => host_command(command, cli.profile, &mut config, &config_path),
+        Command::GitCredential { action } => credential_helpers::git(action, &config).await,
+        Command::DockerCredential { action } => credential_helpers::docker(action, &config).await,
+    }
+}
+
+async fn session_command(
+    command: SessionCommand,
+    name: &str,
+    profile: &Profile,
+) -> anyhow::Result<()> {
+    match command {
+        SessionCommand::Login { flow } => {
+            session::login(profile, name, flow.unwrap_or(profile.flow)).await?;
+            eprintln!("Logged in to {} ({})", profile.realm, name);
+        }
+        SessionCommand::Token => {
+            let client = profile.client(name)?;
+            println!("{}", session::access_token(&client, name).await?);
+        }
+        SessionCommand::Whoami => {
+            let claims = session::whoami(&profile.client(name)?, name).await?;
+            println!("{}", serde_json::to_string_pretty(&claims)?);
+        }
+        SessionCommand::Logout => {
+            session::logout(&profile.client(name)?).await?;
+            eprintln!("Logged out of {} ({})", profile.realm, name);
+        }
+        SessionCommand::Kubectl => {
+            let credential = kubectl::exec_credential(profile, name).await?;
+            println!("{}", serde_json::to_string(&credential)?);
+        }
+    }
+    Ok(())
+}
+
+fn profile_command(
+    command: ProfileCommand,
+    config: &mut Config,
+    config_path: &Path,
+) -> anyhow::Result<()> {
+    match command {
+        ProfileCommand::Add {
+            name,
+            server_url,
+            realm,
+            client_id,
+            client_secret,
+            flow,
+            scopes,
+            redirect_port,
+            default,
+        } => {
+            let profile = Profile {
+                server_url,
+                realm,
+                client_id,
+                client_secret,
+                flow,
+                scopes,
+                redirect_port,
+                token_cache_path: None,
+            };
+            if default || config.default_profile.is_none() {
+                config.default_profile = Some(name.clone());
+            }
+            config.profiles.insert(name, profile);
+            config.save(config_path)?;
+        }
+        ProfileCommand::List => {
+            for (name, profile) in &config.profiles {
+                let marker = if config.default_profile.as_ref() == Some(name) {
+                    "*"
+                } else {
+                    " "
+                };
+                println!(
+                    "{} {}\t{}/realms/{}\t{}",
+                    marker, name, profile.server_url, profile.realm, profile.client_id
+                );
+            }
+        }
+        ProfileCommand::Remove { name } => {
+            let (_, profile) = config.profile(Some(&name))?;
+            if let Some(path) = profile.configuration(&name)?.token_cache_path {
+                for path in [path.clone(), format!("{}.lock", path)] {
+                    match std::fs::remove_file(path) {
+                        Err(e) if e.kind() != std::io::ErrorKind::NotFound => return Err(e.into()),
+                        _ => {}
+                    }
+                }
+            }
+            config.profiles.remove(&name);
+            if config.default_profile.as_ref() == Some(&name) {
+                config.default_profile = None;
+            }
+            config.save(config_path)?;
+        }
+    }
+    Ok(())
+}
+
+fn host_command(
+    command: HostCommand,
+    profile: Option<String>,
+    config: &mut Config,
+    config_path: &Path,
+) -> anyhow::Result<()> {
+    match command {
+        HostCommand::Add { host, username } => {
+            let (profile, _) = config.profile(profile.as_deref())?;
+            config
+                .hosts
+                .insert(host_of(&host), HostMapping { profile, username });
+            config.save(config_path)?;
+        }
+        HostCommand::List => {
+            for (host, mapping) in &config.hosts {
+                println!("{}\t{}\t{}", host, mapping.profile, mapping.username());
+            }
+        }
+        HostCommand::Remove { host } => {
+            if config.hosts.remove(&host_of(&host)).is_none() {
+                anyhow::bail!("no mapping for host {:?}", host);
+            }
+            config.save(config_path)?;
+        }
+    }
+    Ok(())
+}
@@ -0,0 +1,271 @@
+use std::{
+    collections::BTreeMap,
+    io::Write,
+    path::{Path, PathBuf},
+};
+
+use anyhow::{anyhow, Context};
+use keycloak_oauth::client::{
+    AppConfigBuilder, AuthorizationCodeCredential, ClientConfiguration, DeviceCodeCredential,
+    KeycloakClient, NoCredentials, ResourceOwnerPasswordCredential, ValidationPolicy,
+    WithAuthorizationCode, WithDeviceCredentials,
+};
+use serde::{Deserialize, Serialize};
+
+/// How `kc-oauth login` signs in.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize, clap::ValueEnum)]
+#[serde(rename_all = "kebab-case")]
+pub enum LoginFlow {
+    /// Show a code to enter on another device
+    #[default]
+    Device,
+    /// Open the browser and receive the code on a loopback redirect
+    Browser,
+}
+
+/// A Keycloak client in one realm, as stored in the config file.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Profile {
+    /// Keycloak base URL, without `/realms`
+    pub server_url: String,
+    pub realm: String,
+    pub client_id: String,
+    pub client_secret: Option<String>,
+    #[serde(default)]
+    pub flow: LoginFlow,
+    #[serde(default)]
+    pub scopes: Vec<String>,
+    pub redirect_port: Option<u16>,
+    /// Defaults to `<cache dir>/kc-oauth/<profile>.json`
+    pub token_cache_path: Option<PathBuf>,
+}
+
+impl Profile {
+    /// The realm's endpoints, derived from Keycloak's fixed layout so no discovery request is
+    /// needed before using a cached token.
+    pub fn configuration(&self, name: &str) -> anyhow::Result<ClientConfiguration> {
+        let issuer = format!(
+            "{}/realms/{}",
+            self.server_url.trim_end_matches('/'),
+            self.realm
+        );
+        let endpoint = |path: &str| format!("{}/protocol/openid-connect/{}", issuer, path);
+        let token_cache_path = match &self.token_cache_path {
+            Some(path) => path.clone(),
+            None => cache_dir()?.join(format!("{}.json", name)),
+        };
+
+        Ok(ClientConfiguration {
+            server_url: Some(self.server_url.clone()),
+            client_id: Some(self.client_id.clone()),
+            client_secret: self.client_secret.clone(),
+            auth_url: Some(endpoint("auth")),
+            token_url: Some(endpoint("token")),
+            device_authorization_url: Some(endpoint("auth/device")),
+            token_cache_path: Some(token_cache_path.to_string_lossy().into_owned()),
+            jwks_url: Some(endpoint("certs")),
+            realm: Some(self.realm.clone()),
+            scopes: self.scopes.clone(),
+            username: None,
+            password: None,
+            redirect_port: self.redirect_port,
+            authorization_timeout: None,
+            issuer: Some(issuer.clone()),
+            introspection_url: Some(endpoint("token/introspect")),
+            revocation_url: Some(endpoint("revoke")),
+            end_session_url: Some(endpoint("logout")),
+            userinfo_url: Some(endpoint("userinfo")),
+            grant_types_supported: Vec::new(),
+            signing_algorithms: Vec::new(),
+            validation_policy: ValidationPolicy::Local,
+            allowed_algorithms: Vec::new(),
+        })
+    }
+
+    /// A client for the profile. Every flow shares its token cache, so the authorization code
+    /// client serves every command but the device login.
+    pub fn client(&self, name: &str) -> anyhow::Result<KeycloakClient<WithAuthorizationCode>> {
+        let app_config = self
+            .app_config_builder(name)?
+            .with_authorization_code_credentials(AuthorizationCodeCredential::new(
+                self.client_id.clone(),
+            ))
+            .build()
+            .map_err(|e| anyhow!("profile {}: {}", name, e))?;
+        Ok(KeycloakClient::<WithAuthorizationCode>::try_from(
+            app_config,
+        )?)
+    }
+
+    /// A client running the device flow into the profile's token cache.
+    pub fn device_client(
+        &self,
+        name: &str,
+    ) -> anyhow::Result<KeycloakClient<WithDeviceCredentials>> {
+        let app_config = self
+            .app_config_builder(name)?
+            .with_device_code_credentials(DeviceCodeCredential {
+                client_id: self.client_id.clone(),
+            })
+            .build()
+            .map_err(|e| anyhow!("profile {}: {}", name, e))?;
+        Ok(KeycloakClient::<WithDeviceCredentials>::try_from(
+            app_config,
+        )?)
+    }
+
+    fn app_config_builder(
+        &self,
+        name: &str,
+    ) -> anyhow::Result<AppConfigBuilder<NoCredentials, ResourceOwnerPasswordCredential>> {
+        let configuration = self.configuration(name)?;
+        if let Some(path) = &configuration.token_cache_path {
+            prepare_cache_dir(Path::new(path))?;
+        }
+        Ok(AppConfigBuilder::new(self.client_id.clone()).configuration(configuration))
+    }
+}
+
+/// Which profile answers git and docker credential requests for a host.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct HostMapping {
+    pub profile: String,
+    /// Sent along with the token. Most hosts accept any name for token logins
+    pub username: Option<String>,
+}
+
+impl HostMapping {
+    pub fn username(&self) -> &str {
+        self.username.as_deref().unwrap_or("oauth2")
+    }
+}
+
+/// The profiles and host mappings of `config.toml`.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct Config {
+    /// Used when `--profile` is not given
+    pub default_profile: Option<String>,
+    #[serde(default)]
+    pub profiles: BTreeMap<String, Profile>,
+    /// Keyed by host name, with an optional port, or `*.domain` for every subdomain
+    #[serde(default)]
+    pub hosts: BTreeMap<String, HostMapping>,
+}
+
+impl Config {
+    /// `$KC_OAUTH_CONFIG`, or `kc-oauth/config.toml` in the user's config directory.
+    pub fn default_path() -> anyhow::Result<PathBuf> {
+        if let Some(path) = std::env::var_os("KC_OAUTH_CONFIG") {
+            return Ok(PathBuf::from(path));
+        }
+        Ok(base_dir("XDG_CONFIG_HOME", ".config")?
+            .join("kc-oauth")
+            .join("config.toml"))
+    }
+
+    /// Reads `path`. A missing file is an empty config.
+    pub fn load(path: &Path) -> anyhow::Result<Self> {
+        match std::fs::read_to_string(path) {
+            Ok(data) => {
+                toml::from_str(&data).with_context(|| format!("reading {}", path.display()))
+            }
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(Self::default()),
+            Err(e) => Err(e).with_context(|| format!("reading {}", path.display())),
+        }
+    }
+
+    /// Writes `path`, only readable by the user since profiles may hold client secrets.
+    pub fn save(&self, path: &Path) -> anyhow::Result<()> {
+        if let Some(parent) = path.parent() {
+            std::fs::create_dir_all(parent)?;
+        }
+        let mut options = std::fs::OpenOptions::new();
+        options.write(true).create(true).truncate(true);
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::OpenOptionsExt;
+            options.mode(0o600);
+        }
+        let mut file = options
+            .open(path)
+            .with_context(|| format!("writing {}", path.display()))?;
+        file.write_all(toml::to_string_pretty(self)?.as_bytes())?;
+        Ok(())
+    }
+
+    /// The profile called `name`, or the default one.
+    pub fn profile(&self, name: Option<&str>) -> anyhow::Result<(String, &Profile)> {
+        let name = name
+            .map(str::to_string)
+            .or_else(|| self.default_profile.clone())
+            .unwrap_or_else(|| "default".into());
+        let profile = self.profiles.get(&name).ok_or_else(|| {
+            anyhow!(
+                "no profile named {:?}, add one with `kc-oauth profile add {}`",
+                name,
+                name
+            )
+        })?;
+        Ok((name, profile))
+    }
+
+    /// The mapping for `server` (a host or URL): an exact match, then the host without its
+    /// port, then the closest `*.domain` pattern.
+    pub fn host(&self, server: &str) -> Option<&HostMapping> {
+        let host = host_of(server);
+        let hostname = host.split(':').next().unwrap_or_default();
+        self.hosts
+            .get(&host)
+            .or_else(|| self.hosts.get(hostname))
+            .or_else(|| {
+                self.hosts
+                    .iter()
+                    .filter_map(|(pattern, mapping)| {
+                        let domain = pattern.strip_prefix("*.")?;
+                        hostname
+                            .strip_suffix(domain)
+                            .filter(|sub| sub.ends_with('.'))
+                            .map(|_| (domain.len(), mapping))
+                    })
+                    .max_by_key(|(len, _)| *len)
+                    .map(|(_, mapping)| mapping)
+            })
+    }
+}
+
+/// `git.example.com:8443` for `https://git.example.com:8443/v2/`, `git.example.com:8443` or
+/// `GIT.example.com:8443`.
+pub fn host_of(server: &str) -> String {
+    let server = server.split_once("://").map_or(server, |(_, rest)| rest);
+    let host = server.split('/').next().unwrap_or_default();
+    host.to_ascii_lowercase()
+}
+
+fn cache_dir() -> anyhow::Result<PathBuf> {
+    Ok(base_dir("XDG_CACHE_HOME", ".cache")?.join("kc-oauth"))
+}
+
+fn base_dir(xdg_var: &str, home_relative: &str) -> anyhow::Result<PathBuf> {
+    if let Some(dir) = std::env::var_os(xdg_var).filter(|dir| !dir.is_empty()) {
+        return Ok(PathBuf::from(dir));
+    }
+    let home = std::env::var_os("HOME").ok_or_else(|| anyhow!("HOME is not set"))?;
+    Ok(PathBuf::from(home).join(home_relative))
+}
+
+/// Tokens are cached in plain JSON, so their directory is only readable by the user.
+fn prepare_cache_dir(token_cache_path: &Path) -> anyhow::Result<()> {
+    let Some(dir) = token_cache_path.parent() else {
+        return Ok(());
+    };
+    if dir.as_os_str().is_empty() || dir.exists() {
+        return Ok(());
+    }
+    std::fs::create_dir_all(dir)?;
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::PermissionsExt;
+        std::fs::set_permissions(dir, std::fs::Permissions::from_mode(0o700))?;
+    }
+    Ok(())
+}
@@ -0,0 +1,122 @@
+use anyhow::{anyhow, Context};
+use keycloak_oauth::client::{
+    ClientError, KeycloakClaims, KeycloakClient, OpenIdConfiguration, TokenVerifier,
+};
+
+use crate::profile::{LoginFlow, Profile};
+
+/// Tokens expiring sooner than this are refreshed before being handed out, so the caller does
+/// not receive a token that expires in flight.
+const MIN_VALIDITY_SECS: i64 = 30;
+
+pub async fn login(profile: &Profile, name: &str, flow: LoginFlow) -> anyhow::Result<()> {
+    match flow {
+        LoginFlow::Device => device_login(profile, name).await,
+        LoginFlow::Browser => {
+            profile.client(name)?.authenticate().await?;
+            Ok(())
+        }
+    }
+}
+
+/// Runs the device flow with every prompt on stderr, leaving stdout to the caller.
+pub async fn device_login(profile: &Profile, name: &str) -> anyhow::Result<()> {
+    profile
+        .device_client(name)?
+        .authenticate_with_prompt(|details| {
+            eprintln!(
+                "To sign in, open {} and enter the code {}",
+                details.verification_uri().as_str(),
+                details.user_code().secret()
+            );
+            if let Some(uri) = details.verification_uri_complete() {
+                eprintln!("or open {}", uri.secret());
+            }
+        })
+        .await
+        .context("device login failed")?;
+    Ok(())
+}
+
+/// The cached access token, refreshed when it expires within `MIN_VALIDITY_SECS`.
+pub async fn fresh_token<C>(client: &KeycloakClient<C>) -> Result<String, ClientError> {
+    client
+        .refresh_if_needed(chrono::Duration::seconds(MIN_VALIDITY_SECS), None)
+        .await
+}
+
+/// Like `fresh_token`, telling the user how to sign in when there is no usable token.
+pub async fn access_token<C>(client: &KeycloakClient<C>, profile: &str) -> anyhow::Result<String> {
+    fresh_token(client).await.map_err(|e| match e {
+        e if e.requires_reauthentication() => anyhow!(
+            "not logged in to profile {:?}, run `kc-oauth login --profile {}`",
+            profile,
+            profile
+        ),
+        e => e.into(),
+    })
+}
+
+pub async fn whoami<C>(
+    client: &KeycloakClient<C>,
+    profile: &str,
+) -> anyhow::Result<KeycloakClaims> {
+    let token = access_token(client, profile).await?;
+    // profiles skip discovery, but the realm may sign with other algorithms than RS256
+    let mut configuration = client.config.clone();
+    if configuration.allowed_algorithms.is_empty() {
+        let server_url = configuration
+            .server_url
+            .clone()
+            .context("no server URL configured")?;
+        let realm = configuration.realm.clone().context("no realm configured")?;
+        let discovered = OpenIdConfiguration::fetch(&server_url, &realm)
+            .await
+            .context("cannot discover the realm's signing algorithms")?;
+        configuration.apply_discovery(discovered);
+    }
+    let jwks_url = configuration.jwks_url.context("no JWKS URL configured")?;
+    let issuer = configuration.issuer.context("no issuer configured")?;
+    // public CLI clients are rarely in the audience of their own tokens, so only the signature,
+    // expiry and issuer are checked
+    let mut verifier = TokenVerifier::new(jwks_url, client.cache.clone())
+        .any_audience()
+        .issuer([issuer]);
+    if !configuration.allowed_algorithms.is_empty() {
+        verifier = verifier.allowed_algorithms(&configuration.allowed_algorithms);
+    }
+    Ok(verifier.verify::<KeycloakClaims>(&token).await?.claims)
+}
+
+/// Revokes the access token and ends the session. The cache is cleared even when Keycloak
+/// cannot be reached, so a stale login never lingers.
+pub async fn logout<C>(client: &KeycloakClient<C>) -> anyhow::Result<()> {
+    if let Ok(cached) = client.load_cached_token().await {
+        if let Err(e) = client
+            .revoke_token(&cached.access_token, Some("access_token"))
+            .await
+        {
+            eprintln!("warning: could not revoke the access token: {}", e);
+        }
+    }
+    if let Err(e) = client.logout().await {
+        eprintln!("warning: could not end the Keycloak session: {}", e);
+        client.clear_cached_token().await?;
+    }
+    Ok(())
+}
+
+/// Marks `access_token` as expired if it is the cached one, so the next request refreshes it.
+/// For tokens a server rejected; the refresh token is kept.
+pub async fn expire_access_token<C>(
+    client: &KeycloakClient<C>,
+    access_token: &str,
+) -> Result<(), ClientError> {
+    if let Some(mut cached) = client.store.load().await? {
+        if cached.access_token == access_token {
+            cached.expires_at = chrono::Utc::now();
+            client.store.save(&cached).await?;
+        }
+    }
+    Ok(())
+}
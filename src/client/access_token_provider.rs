@@ -0,0 +1,109 @@
+use std::sync::Arc;
+
+use async_trait::async_trait;
+
+use super::{
+    ClientError, KeycloakClient, TokenManager, WithAuthorizationCode, WithClientCredentials,
+    WithDeviceCredentials, WithOwnerCredentials,
+};
+
+/// Something that hands out a valid access token, used by the API clients and middleware built
+/// on top of a `KeycloakClient`.
+#[async_trait]
+pub trait AccessTokenProvider: Send + Sync {
+    async fn access_token(&self) -> Result<String, ClientError>;
+
+    /// A token valid for at least `min_validity` that is not `rejected`, for callers refreshing
+    /// ahead of expiry or after a server turned a token down. Providers that cannot replace a
+    /// token on demand hand out `access_token`.
+    async fn fresh_access_token(
+        &self,
+        _min_validity: chrono::Duration,
+        _rejected: Option<&str>,
+    ) -> Result<String, ClientError> {
+        self.access_token().await
+    }
+}
+
+#[async_trait]
+impl AccessTokenProvider for KeycloakClient<WithDeviceCredentials> {
+    async fn access_token(&self) -> Result<String, ClientError> {
+        self.verify_and_refresh_access_token().await
+    }
+
+    async fn fresh_access_token(
+        &self,
+        min_validity: chrono::Duration,
+        rejected: Option<&str>,
+    ) -> Result<String, ClientError> {
+        self.refresh_if_needed(min_validity, rejected).await
+    }
+}
+
+#[async_trait]
+impl AccessTokenProvider for KeycloakClient<WithOwnerCredentials> {
+    async fn access_token(&self) -> Result<String, ClientError> {
+        self.verify_and_refresh_access_token().await
+    }
+
+    async fn fresh_access_token(
+        &self,
+        min_validity: chrono::Duration,
+        rejected: Option<&str>,
+    ) -> Result<String, ClientError> {
+        self.refresh_if_needed(min_validity, rejected).await
+    }
+}
+
+#[async_trait]
+impl AccessTokenProvider for KeycloakClient<WithAuthorizationCode> {
+    async fn access_token(&self) -> Result<String, ClientError> {
+        self.verify_and_refresh_access_token().await
+    }
+
+    async fn fresh_access_token(
+        &self,
+        min_validity: chrono::Duration,
+        rejected: Option<&str>,
+    ) -> Result<String, ClientError> {
+        self.refresh_if_needed(min_validity, rejected).await
+    }
+}
+
+/// Requests a new token once the cached one expires, as there is no refresh token.
+#[async_trait]
+impl AccessTokenProvider for KeycloakClient<WithClientCredentials> {
+    async fn access_token(&self) -> Result<String, ClientError> {
+        KeycloakClient::<WithClientCredentials>::access_token(self).await
+    }
+
+    async fn fresh_access_token(
+        &self,
+        min_validity: chrono::Duration,
+        rejected: Option<&str>,
+    ) -> Result<String, ClientError> {
+        self.renew_if_needed(min_validity, rejected).await
+    }
+}
+
+#[async_trait]
+impl<C: Send + Sync> AccessTokenProvider for TokenManager<C> {
+    async fn access_token(&self) -> Result<String, ClientError> {
+        TokenManager::access_token(self)
+    }
+}
+
+#[async_trait]
+impl<T: AccessTokenProvider + ?Sized> AccessTokenProvider for Arc<T> {
+    async fn access_token(&self) -> Result<String, ClientError> {
+        (**self).access_token().await
+    }
+
+    async fn fresh_access_token(
+        &self,
+        min_validity: chrono::Duration,
+        rejected: Option<&str>,
+    ) -> Result<String, ClientError> {
+        (**self).fresh_access_token(min_validity, rejected).await
+    }
+}
@@ -0,0 +1,214 @@
+use std::{
+    future::{ready, Future, Ready},
+    marker::PhantomData,
+    pin::Pin,
+    rc::Rc,
+};
+
+use actix_web::{
+    body::EitherBody,
+    dev::{forward_ready, Payload, Service, ServiceRequest, ServiceResponse, Transform},
+    http::{header, StatusCode},
+    web, FromRequest, HttpMessage, HttpRequest, HttpResponse, ResponseError,
+};
+
+use super::{
+    verify_bearer, BearerError, KeycloakClaims, Requirement, RequirementSource, TokenVerifier,
+};
+
+/// Actix-web middleware validating `Authorization: Bearer` tokens with a `TokenVerifier`.
+///
+/// Valid requests continue with their `KeycloakClaims` in the request extensions, where the
+/// `AuthenticatedUser` and `AuthorizedUser` extractors find them. Other requests are answered
+/// with an RFC 6750 `WWW-Authenticate` challenge.
+///
+/// ```no_run
+/// # fn app(verifier: keycloak_oauth::client::TokenVerifier) {
+/// use actix_web::{web, App};
+/// use keycloak_oauth::client::{AuthenticatedUser, KeycloakAuth};
+///
+/// async fn me(user: AuthenticatedUser) -> String {
+///     user.0.preferred_username.unwrap_or_default()
+/// }
+///
+/// let app = App::new()
+///     .wrap(KeycloakAuth::new(verifier))
+///     .route("/me", web::get().to(me));
+/// # }
+/// ```
+#[derive(Clone)]
+pub struct KeycloakAuth {
+    verifier: TokenVerifier,
+    requirement: Option<Requirement>,
+    realm: Option<String>,
+}
+
+impl KeycloakAuth {
+    pub fn new(verifier: TokenVerifier) -> Self {
+        Self {
+            verifier,
+            requirement: None,
+            realm: None,
+        }
+    }
+
+    /// Rejects valid tokens that do not meet `requirement` with `403 insufficient_scope`.
+    pub fn require(mut self, requirement: Requirement) -> Self {
+        self.requirement = Some(requirement);
+        self
+    }
+
+    /// Realm advertised in the `WWW-Authenticate` challenge.
+    pub fn realm(mut self, realm: impl Into<String>) -> Self {
+        self.realm = Some(realm.into());
+        self
+    }
+}
+
+impl<S, B> Transform<S, ServiceRequest> for KeycloakAuth
+where
+    S: Service<ServiceRequest, Response = ServiceResponse<B>, Error = actix_web::Error> + 'static,
+    B: 'static,
+{
+    type Response = ServiceResponse<EitherBody<B>>;
+    type Error = actix_web::Error;
+    type Transform = KeycloakAuthMiddleware<S>;
+    type InitError = ();
+    type Future = Ready<Result<Self::Transform, Self::InitError>>;
+
+    fn new_transform(&self, service: S) -> Self::Future {
+        ready(Ok(KeycloakAuthMiddleware {
+            service: Rc::new(service),
+            auth: self.clone(),
+        }))
+    }
+}
+
+pub struct KeycloakAuthMiddleware<S> {
+    service: Rc<S>,
+    auth: KeycloakAuth,
+}
+
+impl<S, B> Service<ServiceRequest> for KeycloakAuthMiddleware<S>
+where
+    S: Service<ServiceRequest, Response = ServiceResponse<B>, Error = actix_web::Error> + 'static,
+    B: 'static,
+{
+    type Response = ServiceResponse<EitherBody<B>>;
+    type Error = actix_web::Error;
+    type Future = Pin<Box<dyn Future<Output = Result<Self::Response, Self::Error>>>>;
+
+    forward_ready!(service);
+
+    fn call(&self, request: ServiceRequest) -> Self::Future {
+        let service = Rc::clone(&self.service);
+        let auth = self.auth.clone();
+
+        Box::pin(async move {
+            let authorization = authorization_header(request.request());
+            match verify_bearer::<KeycloakClaims>(
+                &auth.verifier,
+                authorization.as_deref(),
+                auth.requirement.as_ref(),
+            )
+            .await
+            {
+                Ok((claims, _)) => {
+                    request.extensions_mut().insert(claims);
+                    service
+                        .call(request)
+                        .await
+                        .map(ServiceResponse::map_into_left_body)
+                }
+                Err(error) => {
+                    let response = bearer_response(&error, auth.realm.as_deref());
+                    Ok(request.into_response(response).map_into_right_body())
+                }
+            }
+        })
+    }
+}
+
+fn authorization_header(request: &HttpRequest) -> Option<String> {
+    request
+        .headers()
+        .get(header::AUTHORIZATION)
+        .map(|value| value.to_str().unwrap_or_default().to_string())
+}
+
+fn bearer_response(error: &BearerError, realm: Option<&str>) -> HttpResponse {
+    let status =
+        StatusCode::from_u16(BearerError::status_code(error)).unwrap_or(StatusCode::UNAUTHORIZED);
+    let mut response = HttpResponse::build(status);
+    if error.is_challenge() {
+        response.insert_header((header::WWW_AUTHENTICATE, error.www_authenticate(realm)));
+    }
+    response.body(error.to_string())
+}
+
+impl ResponseError for BearerError {
+    fn status_code(&self) -> StatusCode {
+        StatusCode::from_u16(BearerError::status_code(self)).unwrap_or(StatusCode::UNAUTHORIZED)
+    }
+
+    fn error_response(&self) -> HttpResponse {
+        bearer_response(self, None)
+    }
+}
+
+/// The claims of the request's bearer token.
+///
+/// Behind `KeycloakAuth` the claims are taken from the request extensions. Without the
+/// middleware the token is verified here, with a `TokenVerifier` registered through
+/// `App::app_data` (either directly or as `web::Data<TokenVerifier>`).
+#[derive(Debug, Clone)]
+pub struct AuthenticatedUser(pub KeycloakClaims);
+
+impl FromRequest for AuthenticatedUser {
+    type Error = BearerError;
+    type Future = Pin<Box<dyn Future<Output = Result<Self, Self::Error>>>>;
+
+    fn from_request(request: &HttpRequest, _payload: &mut Payload) -> Self::Future {
+        if let Some(claims) = request.extensions().get::<KeycloakClaims>().cloned() {
+            return Box::pin(ready(Ok(AuthenticatedUser(claims))));
+        }
+
+        let verifier = request.app_data::<TokenVerifier>().cloned().or_else(|| {
+            request
+                .app_data::<web::Data<TokenVerifier>>()
+                .map(|data| data.get_ref().clone())
+        });
+        let authorization = authorization_header(request);
+
+        Box::pin(async move {
+            let verifier = verifier.ok_or_else(|| {
+                BearerError::Misconfigured("no token verifier is configured".into())
+            })?;
+            let (claims, _) =
+                verify_bearer::<KeycloakClaims>(&verifier, authorization.as_deref(), None).await?;
+            Ok(AuthenticatedUser(claims))
+        })
+    }
+}
+
+/// An `AuthenticatedUser` whose claims meet `R`, acting as a per-route role guard. Requests
+/// that fall short are rejected with `403 insufficient_scope`.
+#[derive(Debug, Clone)]
+pub struct AuthorizedUser<R>(pub KeycloakClaims, pub PhantomData<R>);
+
+impl<R: RequirementSource + 'static> FromRequest for AuthorizedUser<R> {
+    type Error = BearerError;
+    type Future = Pin<Box<dyn Future<Output = Result<Self, Self::Error>>>>;
+
+    fn from_request(request: &HttpRequest, payload: &mut Payload) -> Self::Future {
+        let authenticated = AuthenticatedUser::from_request(request, payload);
+
+        Box::pin(async move {
+            let AuthenticatedUser(claims) = authenticated.await?;
+            R::requirement()
+                .evaluate_claims(&claims)
+                .map_err(BearerError::InsufficientScope)?;
+            Ok(AuthorizedUser(claims, PhantomData))
+        })
+    }
+}
@@ -1,6 +1,9 @@
 use std::marker::PhantomData;
 
-use super::{Credential, DeviceCodeCredential, ResourceOwnerPasswordCredential};
+use super::{
+    AuthorizationCodeCredential, ClientConfiguration, ClientCredentials, Credential,
+    DeviceCodeCredential, ResourceOwnerPasswordCredential,
+};
 
 // base
 pub struct NoCredentials;
@@ -11,12 +14,20 @@ pub struct WithOwnerCredentials;
 // Builder after device creds are set
 pub struct WithDeviceCredentials;
 
+// Builder after authorization code creds are set
+pub struct WithAuthorizationCode;
+
+// Builder after client credentials are set
+pub struct WithClientCredentials;
+
 #[derive(Debug)]
 pub struct AppConfig<C: Credential> {
     pub client_id: String,
     pub auth_url: String,
     pub token_url: Option<String>, // this is only needed for Device flow
     pub credential: C,
+    /// Used instead of `ClientConfiguration::from_env` when set, e.g. after discovery
+    pub configuration: Option<ClientConfiguration>,
 }
 
 impl<C: Credential> AppConfig<C> {
@@ -26,6 +37,7 @@ impl<C: Credential> AppConfig<C> {
             auth_url: auth_url.into(),
             token_url: None,
             credential: credentials,
+            configuration: None,
         }
     }
 }
@@ -35,9 +47,33 @@ pub struct AppConfigBuilder<State, C: Credential> {
     auth_url: Option<String>,
     token_url: Option<String>,
     credential: Option<C>,
+    configuration: Option<ClientConfiguration>,
     _marker: PhantomData<State>,
 }
 
+impl<State, C: Credential> AppConfigBuilder<State, C> {
+    /// Uses `configuration` (e.g. from `ClientConfiguration::discover`) instead of reading the
+    /// environment. Its `auth_url` and `token_url` are used when they are not set explicitly.
+    pub fn configuration(mut self, configuration: ClientConfiguration) -> Self {
+        self.configuration = Some(configuration);
+        self
+    }
+
+    fn configured_auth_url(&self) -> Option<String> {
+        self.auth_url
+            .clone()
+            .or_else(|| self.configuration.as_ref().and_then(|c| c.auth_url.clone()))
+    }
+
+    fn configured_token_url(&self) -> Option<String> {
+        self.token_url.clone().or_else(|| {
+            self.configuration
+                .as_ref()
+                .and_then(|c| c.token_url.clone())
+        })
+    }
+}
+
 impl AppConfigBuilder<NoCredentials, ResourceOwnerPasswordCredential> {
     pub fn new(client_id: impl Into<String>) -> Self {
         AppConfigBuilder {
@@ -45,12 +81,13 @@ impl AppConfigBuilder<NoCredentials, ResourceOwnerPasswordCredential> {
             auth_url: None,
             token_url: None,
             credential: None,
+            configuration: None,
             _marker: PhantomData,
         }
     }
 
     pub fn auth_url(mut self, auth_url: impl Into<String>) -> Self {
-        self.auth_url.insert(auth_url.into());
+        self.auth_url = Some(auth_url.into());
         self
     }
 
@@ -63,6 +100,7 @@ impl AppConfigBuilder<NoCredentials, ResourceOwnerPasswordCredential> {
             auth_url: self.auth_url,
             token_url: self.token_url,
             credential: Some(credentials),
+            configuration: self.configuration,
             _marker: PhantomData::<WithOwnerCredentials>,
         }
     }
@@ -76,35 +114,65 @@ impl AppConfigBuilder<NoCredentials, ResourceOwnerPasswordCredential> {
             auth_url: self.auth_url,
             token_url: self.token_url,
             credential: Some(credentials),
+            configuration: self.configuration,
             _marker: PhantomData::<WithDeviceCredentials>,
         }
     }
+
+    pub fn with_authorization_code_credentials(
+        self,
+        credentials: AuthorizationCodeCredential,
+    ) -> AppConfigBuilder<WithAuthorizationCode, AuthorizationCodeCredential> {
+        AppConfigBuilder {
+            client_id: self.client_id,
+            auth_url: self.auth_url,
+            token_url: self.token_url,
+            credential: Some(credentials),
+            configuration: self.configuration,
+            _marker: PhantomData::<WithAuthorizationCode>,
+        }
+    }
+
+    pub fn with_client_credentials(
+        self,
+        credentials: ClientCredentials,
+    ) -> AppConfigBuilder<WithClientCredentials, ClientCredentials> {
+        AppConfigBuilder {
+            client_id: self.client_id,
+            auth_url: self.auth_url,
+            token_url: self.token_url,
+            credential: Some(credentials),
+            configuration: self.configuration,
+            _marker: PhantomData::<WithClientCredentials>,
+        }
+    }
 }
 
 impl AppConfigBuilder<WithOwnerCredentials, ResourceOwnerPasswordCredential> {
     pub fn build(self) -> Result<AppConfig<ResourceOwnerPasswordCredential>, &'static str> {
+        let auth_url = self.configured_auth_url().ok_or("auth_url is not set")?;
         let client_id = self.client_id.ok_or("client_id is not set")?;
-        let auth_url = self.auth_url.ok_or("auth_url is not set")?;
         let credential = self.credential.ok_or("Owner credentials not set")?;
         Ok(AppConfig {
             client_id,
             auth_url,
             token_url: None,
             credential,
+            configuration: self.configuration,
         })
     }
 }
 
 impl AppConfigBuilder<WithDeviceCredentials, DeviceCodeCredential> {
     pub fn token_url(mut self, token_url: impl Into<String>) -> Self {
-        self.token_url.insert(token_url.into());
+        self.token_url = Some(token_url.into());
         self
     }
 
     pub fn build(self) -> Result<AppConfig<DeviceCodeCredential>, &'static str> {
+        let auth_url = self.configured_auth_url().ok_or("auth_url is not set")?;
+        let token_url = self.configured_token_url().ok_or("token_url is not set")?;
         let client_id = self.client_id.ok_or("client_id is not set")?;
-        let auth_url = self.auth_url.ok_or("auth_url is not set")?;
-        let token_url = self.token_url.ok_or("token_url is not set")?;
         let credentials = self.credential.ok_or("DeviceCredential not set")?;
 
         Ok(AppConfig {
@@ -112,6 +180,51 @@ impl AppConfigBuilder<WithDeviceCredentials, DeviceCodeCredential> {
             auth_url,
             token_url: Some(token_url),
             credential: credentials,
+            configuration: self.configuration,
+        })
+    }
+}
+
+impl AppConfigBuilder<WithAuthorizationCode, AuthorizationCodeCredential> {
+    pub fn token_url(mut self, token_url: impl Into<String>) -> Self {
+        self.token_url = Some(token_url.into());
+        self
+    }
+
+    pub fn build(self) -> Result<AppConfig<AuthorizationCodeCredential>, &'static str> {
+        let auth_url = self.configured_auth_url().ok_or("auth_url is not set")?;
+        let client_id = self.client_id.ok_or("client_id is not set")?;
+        let credential = self
+            .credential
+            .ok_or("AuthorizationCodeCredential not set")?;
+
+        Ok(AppConfig {
+            client_id,
+            auth_url,
+            token_url: self.token_url,
+            credential,
+            configuration: self.configuration,
+        })
+    }
+}
+
+impl AppConfigBuilder<WithClientCredentials, ClientCredentials> {
+    pub fn token_url(mut self, token_url: impl Into<String>) -> Self {
+        self.token_url = Some(token_url.into());
+        self
+    }
+
+    pub fn build(self) -> Result<AppConfig<ClientCredentials>, &'static str> {
+        let auth_url = self.configured_auth_url().ok_or("auth_url is not set")?;
+        let client_id = self.client_id.ok_or("client_id is not set")?;
+        let credential = self.credential.ok_or("ClientCredentials not set")?;
+
+        Ok(AppConfig {
+            client_id,
+            auth_url,
+            token_url: self.token_url,
+            credential,
+            configuration: self.configuration,
         })
     }
 }
@@ -0,0 +1,262 @@
+use std::{
+    fmt::{Debug, Display, Formatter},
+    sync::Arc,
+};
+
+use jsonwebtoken::TokenData;
+
+use super::KeycloakClaims;
+
+type ClaimPredicate = Arc<dyn Fn(Option<&serde_json::Value>) -> bool + Send + Sync>;
+
+/// An authorization rule evaluated against verified token claims.
+///
+/// Requirements compose with `all`/`any` (or `and`/`or`):
+///
+/// ```
+/// use keycloak_oauth::client::Requirement;
+///
+/// let can_publish = Requirement::realm_role("admin").or(
+///     Requirement::client_role("waves-api", "publisher").and(Requirement::scope("waves:write")),
+/// );
+/// ```
+#[derive(Clone)]
+pub enum Requirement {
+    RealmRole(String),
+    ClientRole {
+        client_id: String,
+        role: String,
+    },
+    Scope(String),
+    /// Membership of a group, as sent by Keycloak's group membership mapper in `groups`
+    Group(String),
+    /// A claim accepted by a predicate, either one `KeycloakClaims` types (`azp`, `email`, ...)
+    /// or a custom one from `KeycloakClaims::extra`
+    Claim {
+        name: String,
+        predicate: ClaimPredicate,
+    },
+    All(Vec<Requirement>),
+    Any(Vec<Requirement>),
+}
+
+impl Requirement {
+    pub fn realm_role(role: impl Into<String>) -> Self {
+        Requirement::RealmRole(role.into())
+    }
+
+    pub fn client_role(client_id: impl Into<String>, role: impl Into<String>) -> Self {
+        Requirement::ClientRole {
+            client_id: client_id.into(),
+            role: role.into(),
+        }
+    }
+
+    pub fn scope(scope: impl Into<String>) -> Self {
+        Requirement::Scope(scope.into())
+    }
+
+    pub fn group(group: impl Into<String>) -> Self {
+        Requirement::Group(group.into())
+    }
+
+    /// The claim `name` must satisfy `predicate`, which receives `None` when it is absent.
+    pub fn claim<F>(name: impl Into<String>, predicate: F) -> Self
+    where
+        F: Fn(Option<&serde_json::Value>) -> bool + Send + Sync + 'static,
+    {
+        Requirement::Claim {
+            name: name.into(),
+            predicate: Arc::new(predicate),
+        }
+    }
+
+    pub fn all(requirements: impl IntoIterator<Item = Requirement>) -> Self {
+        Requirement::All(requirements.into_iter().collect())
+    }
+
+    pub fn any(requirements: impl IntoIterator<Item = Requirement>) -> Self {
+        Requirement::Any(requirements.into_iter().collect())
+    }
+
+    pub fn and(self, other: Requirement) -> Self {
+        match self {
+            Requirement::All(mut requirements) => {
+                requirements.push(other);
+                Requirement::All(requirements)
+            }
+            requirement => Requirement::All(vec![requirement, other]),
+        }
+    }
+
+    pub fn or(self, other: Requirement) -> Self {
+        match self {
+            Requirement::Any(mut requirements) => {
+                requirements.push(other);
+                Requirement::Any(requirements)
+            }
+            requirement => Requirement::Any(vec![requirement, other]),
+        }
+    }
+
+    /// Checks the requirement against a verified token.
+    pub fn evaluate(&self, token: &TokenData<KeycloakClaims>) -> Result<(), Denial> {
+        self.evaluate_claims(&token.claims)
+    }
+
+    pub fn evaluate_claims(&self, claims: &KeycloakClaims) -> Result<(), Denial> {
+        match self {
+            Requirement::RealmRole(role) => claims
+                .realm_roles()
+                .contains(role)
+                .then_some(())
+                .ok_or_else(|| Denial::MissingRealmRole(role.clone())),
+            Requirement::ClientRole { client_id, role } => claims
+                .client_roles(client_id)
+                .contains(role)
+                .then_some(())
+                .ok_or_else(|| Denial::MissingClientRole {
+                    client_id: client_id.clone(),
+                    role: role.clone(),
+                }),
+            Requirement::Scope(scope) => claims
+                .scopes()
+                .contains(&scope.as_str())
+                .then_some(())
+                .ok_or_else(|| Denial::MissingScope(scope.clone())),
+            Requirement::Group(group) => groups(claims)
+                .any(|g| normalize_group(g) == normalize_group(group))
+                .then_some(())
+                .ok_or_else(|| Denial::MissingGroup(group.clone())),
+            Requirement::Claim { name, predicate } => predicate(claim(claims, name).as_ref())
+                .then_some(())
+                .ok_or_else(|| Denial::ClaimRejected(name.clone())),
+            Requirement::All(requirements) => {
+                // every failure is reported, not only the first one
+                let denials = requirements
+                    .iter()
+                    .filter_map(|r| r.evaluate_claims(claims).err())
+                    .collect::<Vec<_>>();
+                if denials.is_empty() {
+                    Ok(())
+                } else {
+                    Err(Denial::All(denials))
+                }
+            }
+            Requirement::Any(requirements) => {
+                let mut denials = Vec::with_capacity(requirements.len());
+                for requirement in requirements {
+                    match requirement.evaluate_claims(claims) {
+                        Ok(()) => return Ok(()),
+                        Err(denial) => denials.push(denial),
+                    }
+                }
+                Err(Denial::Any(denials))
+            }
+        }
+    }
+}
+
+impl Debug for Requirement {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Requirement::RealmRole(role) => f.debug_tuple("RealmRole").field(role).finish(),
+            Requirement::ClientRole { client_id, role } => f
+                .debug_struct("ClientRole")
+                .field("client_id", client_id)
+                .field("role", role)
+                .finish(),
+            Requirement::Scope(scope) => f.debug_tuple("Scope").field(scope).finish(),
+            Requirement::Group(group) => f.debug_tuple("Group").field(group).finish(),
+            Requirement::Claim { name, .. } => f
+                .debug_struct("Claim")
+                .field("name", name)
+                .finish_non_exhaustive(),
+            Requirement::All(requirements) => f.debug_tuple("All").field(requirements).finish(),
+            Requirement::Any(requirements) => f.debug_tuple("Any").field(requirements).finish(),
+        }
+    }
+}
+
+/// The claim `name` as the token carried it. Absent typed claims serialize as `null`, which is
+/// reported as absent too.
+fn claim(claims: &KeycloakClaims, name: &str) -> Option<serde_json::Value> {
+    if let Some(value) = claims.extra.get(name) {
+        return Some(value.clone());
+    }
+    match serde_json::to_value(claims) {
+        Ok(serde_json::Value::Object(mut claims)) => {
+            claims.remove(name).filter(|value| !value.is_null())
+        }
+        _ => None,
+    }
+}
+
+fn groups(claims: &KeycloakClaims) -> impl Iterator<Item = &str> {
+    claims
+        .extra
+        .get("groups")
+        .and_then(|groups| groups.as_array())
+        .into_iter()
+        .flatten()
+        .filter_map(|group| group.as_str())
+}
+
+/// The group mapper sends full paths (`/parent/child`) or plain names depending on its settings.
+fn normalize_group(group: &str) -> &str {
+    group.strip_prefix('/').unwrap_or(group)
+}
+
+/// A `Requirement` known at compile time, used by the per-route guards of the web integrations.
+pub trait RequirementSource {
+    fn requirement() -> Requirement;
+}
+
+/// Why a `Requirement` was not met.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Denial {
+    MissingRealmRole(String),
+    MissingClientRole {
+        client_id: String,
+        role: String,
+    },
+    MissingScope(String),
+    MissingGroup(String),
+    ClaimRejected(String),
+    /// The requirements of an `All` that failed
+    All(Vec<Denial>),
+    /// None of the alternatives of an `Any` were met; holds why each of them failed
+    Any(Vec<Denial>),
+}
+
+impl Display for Denial {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Denial::MissingRealmRole(role) => write!(f, "missing realm role {}", role),
+            Denial::MissingClientRole { client_id, role } => {
+                write!(f, "missing client role {} on {}", role, client_id)
+            }
+            Denial::MissingScope(scope) => write!(f, "missing scope {}", scope),
+            Denial::MissingGroup(group) => write!(f, "not a member of group {}", group),
+            Denial::ClaimRejected(name) => write!(f, "claim {} rejected", name),
+            Denial::All(denials) => write_joined(f, denials, " and "),
+            Denial::Any(denials) => {
+                write!(f, "none of (")?;
+                write_joined(f, denials, ", ")?;
+                write!(f, ")")
+            }
+        }
+    }
+}
+
+fn write_joined(f: &mut Formatter<'_>, denials: &[Denial], separator: &str) -> std::fmt::Result {
+    for (i, denial) in denials.iter().enumerate() {
+        if i > 0 {
+            write!(f, "{}", separator)?;
+        }
+        write!(f, "{}", denial)?;
+    }
+    Ok(())
+}
+
+impl std::error::Error for Denial {}
@@ -0,0 +1,249 @@
+use std::{
+    future::Future,
+    marker::PhantomData,
+    pin::Pin,
+    task::{Context, Poll},
+};
+
+use axum::{
+    async_trait,
+    extract::{FromRequestParts, Request},
+    http::{header, request::Parts, HeaderValue, StatusCode},
+    response::{IntoResponse, Response},
+};
+use serde::de::DeserializeOwned;
+use tower_layer::Layer;
+use tower_service::Service;
+
+use super::{
+    verify_bearer, BearerError, KeycloakClaims, Requirement, RequirementSource, TokenVerifier,
+};
+
+/// A `tower::Layer` validating `Authorization: Bearer` tokens with a `TokenVerifier`.
+///
+/// Valid requests reach the inner service with the claims (decoded as `T`, and as
+/// `KeycloakClaims` when possible) in their extensions. Other requests are answered with an
+/// RFC 6750 `WWW-Authenticate` challenge.
+///
+/// ```no_run
+/// # async fn app(client: keycloak_oauth::client::KeycloakClient<keycloak_oauth::client::WithClientCredentials>) -> Result<(), keycloak_oauth::client::ClientError> {
+/// use axum::{routing::get, Router};
+/// use keycloak_oauth::client::{Authenticated, KeycloakAuthLayer, KeycloakClaims};
+///
+/// async fn me(Authenticated(claims): Authenticated<KeycloakClaims>) -> String {
+///     claims.preferred_username.unwrap_or_default()
+/// }
+///
+/// let app: Router = Router::new()
+///     .route("/me", get(me))
+///     .layer(KeycloakAuthLayer::new(client.token_verifier()?));
+/// # Ok(())
+/// # }
+/// ```
+pub struct KeycloakAuthLayer<T = KeycloakClaims> {
+    verifier: TokenVerifier,
+    requirement: Option<Requirement>,
+    realm: Option<String>,
+    _claims: PhantomData<fn() -> T>,
+}
+
+impl KeycloakAuthLayer<KeycloakClaims> {
+    pub fn new(verifier: TokenVerifier) -> Self {
+        Self {
+            verifier,
+            requirement: None,
+            realm: None,
+            _claims: PhantomData,
+        }
+    }
+}
+
+impl<T> KeycloakAuthLayer<T> {
+    /// Decodes the claims into the application's own type instead of `KeycloakClaims`.
+    pub fn with_claims<U>(self) -> KeycloakAuthLayer<U> {
+        KeycloakAuthLayer {
+            verifier: self.verifier,
+            requirement: self.requirement,
+            realm: self.realm,
+            _claims: PhantomData,
+        }
+    }
+
+    /// Rejects valid tokens that do not meet `requirement` with `403 insufficient_scope`.
+    pub fn require(mut self, requirement: Requirement) -> Self {
+        self.requirement = Some(requirement);
+        self
+    }
+
+    /// Realm advertised in the `WWW-Authenticate` challenge.
+    pub fn realm(mut self, realm: impl Into<String>) -> Self {
+        self.realm = Some(realm.into());
+        self
+    }
+}
+
+impl<T> Clone for KeycloakAuthLayer<T> {
+    fn clone(&self) -> Self {
+        Self {
+            verifier: self.verifier.clone(),
+            requirement: self.requirement.clone(),
+            realm: self.realm.clone(),
+            _claims: PhantomData,
+        }
+    }
+}
+
+impl<S, T> Layer<S> for KeycloakAuthLayer<T> {
+    type Service = KeycloakAuthService<S, T>;
+
+    fn layer(&self, inner: S) -> Self::Service {
+        KeycloakAuthService {
+            inner,
+            layer: self.clone(),
+        }
+    }
+}
+
+pub struct KeycloakAuthService<S, T> {
+    inner: S,
+    layer: KeycloakAuthLayer<T>,
+}
+
+impl<S: Clone, T> Clone for KeycloakAuthService<S, T> {
+    fn clone(&self) -> Self {
+        Self {
+            inner: self.inner.clone(),
+            layer: self.layer.clone(),
+        }
+    }
+}
+
+impl<S, T> Service<Request> for KeycloakAuthService<S, T>
+where
+    S: Service<Request, Response = Response> + Clone + Send + 'static,
+    S::Future: Send + 'static,
+    T: DeserializeOwned + Clone + Send + Sync + 'static,
+{
+    type Response = Response;
+    type Error = S::Error;
+    type Future = Pin<Box<dyn Future<Output = Result<Response, S::Error>> + Send>>;
+
+    fn poll_ready(&mut self, cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        self.inner.poll_ready(cx)
+    }
+
+    fn call(&mut self, mut request: Request) -> Self::Future {
+        // the clone is not ready yet, so the ready service is taken and the clone left behind
+        let clone = self.inner.clone();
+        let mut inner = std::mem::replace(&mut self.inner, clone);
+        let layer = self.layer.clone();
+
+        Box::pin(async move {
+            let authorization = request
+                .headers()
+                .get(header::AUTHORIZATION)
+                .map(|value| value.to_str().unwrap_or_default());
+
+            match verify_bearer::<T>(&layer.verifier, authorization, layer.requirement.as_ref())
+                .await
+            {
+                Ok((claims, keycloak_claims)) => {
+                    if let Some(keycloak_claims) = keycloak_claims {
+                        request.extensions_mut().insert(keycloak_claims);
+                    }
+                    request.extensions_mut().insert(claims);
+                    inner.call(request).await
+                }
+                Err(error) => Ok(bearer_response(&error, layer.realm.as_deref())),
+            }
+        })
+    }
+}
+
+fn bearer_response(error: &BearerError, realm: Option<&str>) -> Response {
+    let status = StatusCode::from_u16(error.status_code()).unwrap_or(StatusCode::UNAUTHORIZED);
+    let mut response = (status, error.to_string()).into_response();
+    if !error.is_challenge() {
+        return response;
+    }
+    if let Ok(challenge) = HeaderValue::from_str(&error.www_authenticate(realm)) {
+        response
+            .headers_mut()
+            .insert(header::WWW_AUTHENTICATE, challenge);
+    }
+    response
+}
+
+impl IntoResponse for BearerError {
+    fn into_response(self) -> Response {
+        bearer_response(&self, None)
+    }
+}
+
+/// Extracts the claims put in the request extensions by `KeycloakAuthLayer`.
+///
+/// Handlers behind the layer can take `Authenticated<KeycloakClaims>` (or the type given to
+/// `KeycloakAuthLayer::with_claims`); without the layer the request fails with a `500`, as the
+/// server is misconfigured.
+#[derive(Debug, Clone)]
+pub struct Authenticated<T = KeycloakClaims>(pub T);
+
+#[async_trait]
+impl<S, T> FromRequestParts<S> for Authenticated<T>
+where
+    S: Send + Sync,
+    T: Clone + Send + Sync + 'static,
+{
+    type Rejection = BearerError;
+
+    async fn from_request_parts(parts: &mut Parts, _state: &S) -> Result<Self, Self::Rejection> {
+        parts
+            .extensions
+            .get::<T>()
+            .cloned()
+            .map(Authenticated)
+            .ok_or_else(layer_missing)
+    }
+}
+
+fn layer_missing() -> BearerError {
+    BearerError::Misconfigured("KeycloakAuthLayer is not installed".into())
+}
+
+/// Extracts the `KeycloakClaims` put in the request extensions by `KeycloakAuthLayer` and checks
+/// them against `R`, rejecting the request with `403 insufficient_scope` when they fall short.
+///
+/// ```
+/// use keycloak_oauth::client::{Requirement, RequirementSource};
+///
+/// struct Admin;
+/// impl RequirementSource for Admin {
+///     fn requirement() -> Requirement {
+///         Requirement::realm_role("admin")
+///     }
+/// }
+/// // async fn handler(Authorized(claims, _): Authorized<Admin>) { ... }
+/// ```
+#[derive(Debug, Clone)]
+pub struct Authorized<R>(pub KeycloakClaims, pub PhantomData<R>);
+
+#[async_trait]
+impl<S, R> FromRequestParts<S> for Authorized<R>
+where
+    S: Send + Sync,
+    R: RequirementSource,
+{
+    type Rejection = BearerError;
+
+    async fn from_request_parts(parts: &mut Parts, _state: &S) -> Result<Self, Self::Rejection> {
+        let claims = parts
+            .extensions
+            .get::<KeycloakClaims>()
+            .cloned()
+            .ok_or_else(layer_missing)?;
+        R::requirement()
+            .evaluate_claims(&claims)
+            .map_err(BearerError::InsufficientScope)?;
+        Ok(Authorized(claims, PhantomData))
+    }
+}
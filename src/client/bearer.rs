@@ -0,0 +1,164 @@
+use std::fmt::{Display, Formatter};
+
+use serde::de::DeserializeOwned;
+
+use super::{Denial, KeycloakClaims, Requirement, TokenVerifier, VerifyJwtError};
+
+/// Why a request carrying (or missing) a bearer token was rejected, following RFC 6750.
+///
+/// Shared by the HTTP and gRPC integrations so every transport reports the same errors.
+#[derive(Debug, Clone)]
+pub enum BearerError {
+    /// No `Authorization` header; the challenge carries no error code
+    MissingToken,
+    /// The `Authorization` header is not a well formed bearer credential
+    InvalidRequest(String),
+    /// The token is expired, badly signed or otherwise invalid
+    InvalidToken(String),
+    /// The token is valid but does not meet the route's requirement
+    InsufficientScope(Denial),
+    /// The server cannot verify tokens, e.g. no verifier is configured. Not the client's fault,
+    /// so there is no challenge
+    Misconfigured(String),
+    /// The signing keys cannot be fetched right now; the token may well be valid
+    Unavailable(String),
+}
+
+impl BearerError {
+    /// HTTP status code for the error.
+    pub fn status_code(&self) -> u16 {
+        match self {
+            BearerError::MissingToken | BearerError::InvalidToken(_) => 401,
+            BearerError::InvalidRequest(_) => 400,
+            BearerError::InsufficientScope(_) => 403,
+            BearerError::Misconfigured(_) => 500,
+            BearerError::Unavailable(_) => 503,
+        }
+    }
+
+    /// The RFC 6750 error code, `None` for a missing token or a server error.
+    pub fn error_code(&self) -> Option<&'static str> {
+        match self {
+            BearerError::MissingToken
+            | BearerError::Misconfigured(_)
+            | BearerError::Unavailable(_) => None,
+            BearerError::InvalidRequest(_) => Some("invalid_request"),
+            BearerError::InvalidToken(_) => Some("invalid_token"),
+            BearerError::InsufficientScope(_) => Some("insufficient_scope"),
+        }
+    }
+
+    /// `true` when the client can do something about the error, and the response carries a
+    /// `WWW-Authenticate` challenge.
+    pub fn is_challenge(&self) -> bool {
+        self.status_code() < 500
+    }
+
+    /// Value of the `WWW-Authenticate` response header.
+    pub fn www_authenticate(&self, realm: Option<&str>) -> String {
+        let mut params = Vec::new();
+        if let Some(realm) = realm {
+            params.push(format!("realm=\"{}\"", escape(realm)));
+        }
+        if let Some(code) = self.error_code() {
+            params.push(format!("error=\"{}\"", code));
+            params.push(format!(
+                "error_description=\"{}\"",
+                escape(&self.to_string())
+            ));
+        }
+
+        if params.is_empty() {
+            "Bearer".to_string()
+        } else {
+            format!("Bearer {}", params.join(", "))
+        }
+    }
+}
+
+impl Display for BearerError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            BearerError::MissingToken => write!(f, "missing bearer token"),
+            BearerError::InvalidRequest(reason) => write!(f, "{}", reason),
+            BearerError::InvalidToken(reason) => write!(f, "{}", reason),
+            BearerError::InsufficientScope(denial) => write!(f, "{}", denial),
+            BearerError::Misconfigured(reason) | BearerError::Unavailable(reason) => {
+                write!(f, "{}", reason)
+            }
+        }
+    }
+}
+
+impl std::error::Error for BearerError {}
+
+/// Takes the token out of an `Authorization: Bearer <token>` header value.
+pub fn extract_bearer(authorization: Option<&str>) -> Result<&str, BearerError> {
+    let value = authorization.ok_or(BearerError::MissingToken)?;
+    let (scheme, token) = value
+        .split_once(' ')
+        .ok_or_else(|| BearerError::InvalidRequest("malformed authorization header".into()))?;
+    if !scheme.eq_ignore_ascii_case("bearer") {
+        return Err(BearerError::InvalidRequest(
+            "authorization scheme must be Bearer".into(),
+        ));
+    }
+    let token = token.trim();
+    if token.is_empty() {
+        return Err(BearerError::InvalidRequest("empty bearer token".into()));
+    }
+    Ok(token)
+}
+
+// quoted-string values cannot contain unescaped quotes or backslashes
+fn escape(value: &str) -> String {
+    value.replace('\\', "\\\\").replace('"', "\\\"")
+}
+
+/// Verifies the bearer token of an `Authorization` header value and checks `requirement`.
+///
+/// Returns the claims decoded as `T` along with the `KeycloakClaims` view of the same token,
+/// which is `None` when the token does not carry Keycloak's standard claims.
+pub async fn verify_bearer<T: DeserializeOwned>(
+    verifier: &TokenVerifier,
+    authorization: Option<&str>,
+    requirement: Option<&Requirement>,
+) -> Result<(T, Option<KeycloakClaims>), BearerError> {
+    let token = extract_bearer(authorization)?;
+    let token_data = verifier
+        .verify::<serde_json::Value>(token)
+        .await
+        .map_err(bearer_error)?;
+
+    let keycloak_claims = serde_json::from_value::<KeycloakClaims>(token_data.claims.clone()).ok();
+    if let Some(requirement) = requirement {
+        let claims = keycloak_claims
+            .as_ref()
+            .ok_or_else(|| BearerError::InvalidToken("token has no Keycloak claims".into()))?;
+        requirement
+            .evaluate_claims(claims)
+            .map_err(BearerError::InsufficientScope)?;
+    }
+
+    let claims = serde_json::from_value::<T>(token_data.claims)
+        .map_err(|e| BearerError::InvalidToken(e.to_string()))?;
+    Ok((claims, keycloak_claims))
+}
+
+// the description ends up in a response header, so internal details are left out
+fn bearer_error(error: VerifyJwtError) -> BearerError {
+    match error {
+        // Keycloak being unreachable says nothing about the token, so clients should keep it
+        VerifyJwtError::FetchJwksError(_) => {
+            BearerError::Unavailable("signing keys unavailable".into())
+        }
+        VerifyJwtError::NoAudienceError => {
+            BearerError::Misconfigured("no audience configured".into())
+        }
+        VerifyJwtError::DisallowedAlgorithmError(_) => {
+            BearerError::InvalidToken("signing algorithm not allowed".into())
+        }
+        VerifyJwtError::JwtDecodeError(e) => BearerError::InvalidToken(e.to_string()),
+        VerifyJwtError::InvalidKeyFormatError(e) => BearerError::InvalidToken(e),
+    }
+}
@@ -0,0 +1,107 @@
+use std::sync::Arc;
+
+use async_trait::async_trait;
+use http::Extensions;
+use reqwest::{header, Request, Response, StatusCode};
+use reqwest_middleware::{Middleware, Next};
+
+use super::AccessTokenProvider;
+
+/// A `reqwest_middleware::Middleware` authenticating requests with the token of an
+/// `AccessTokenProvider`, usually a `KeycloakClient`.
+///
+/// The access token is replaced ahead of its expiry (30 seconds by default) and sent as
+/// `Authorization: Bearer`: user flows refresh it, a client credentials client performs its
+/// grant again. When a response is `401` with an `invalid_token` challenge, the token is replaced
+/// and the request retried once; requests with a streaming body cannot be cloned and are not
+/// retried. Concurrent requests needing a new token wait for a single refresh.
+///
+/// ```no_run
+/// # async fn call(client: keycloak_oauth::client::KeycloakClient<keycloak_oauth::client::WithDeviceCredentials>) -> Result<(), reqwest_middleware::Error> {
+/// use keycloak_oauth::client::KeycloakMiddleware;
+///
+/// let http = reqwest_middleware::ClientBuilder::new(reqwest::Client::new())
+///     .with(KeycloakMiddleware::new(client))
+///     .build();
+/// let response = http.get("https://api.example.com/waves").send().await?;
+/// # Ok(())
+/// # }
+/// ```
+pub struct KeycloakMiddleware<P> {
+    provider: Arc<P>,
+    leeway: chrono::Duration,
+}
+
+impl<P: AccessTokenProvider> KeycloakMiddleware<P> {
+    /// Takes a `KeycloakClient`, a `TokenManager` or any other provider, also behind an `Arc`.
+    pub fn new(provider: P) -> Self {
+        Self {
+            provider: Arc::new(provider),
+            leeway: chrono::Duration::seconds(30),
+        }
+    }
+
+    /// How long before its expiry the access token is replaced.
+    pub fn with_refresh_leeway(mut self, leeway: chrono::Duration) -> Self {
+        self.leeway = leeway;
+        self
+    }
+}
+
+impl<P> Clone for KeycloakMiddleware<P> {
+    fn clone(&self) -> Self {
+        Self {
+            provider: Arc::clone(&self.provider),
+            leeway: self.leeway,
+        }
+    }
+}
+
+#[async_trait]
+impl<P: AccessTokenProvider + 'static> Middleware for KeycloakMiddleware<P> {
+    async fn handle(
+        &self,
+        mut request: Request,
+        extensions: &mut Extensions,
+        next: Next<'_>,
+    ) -> reqwest_middleware::Result<Response> {
+        let token = self
+            .provider
+            .fresh_access_token(self.leeway, None)
+            .await
+            .map_err(reqwest_middleware::Error::middleware)?;
+        set_bearer(&mut request, &token)?;
+
+        let retry = request.try_clone();
+        let response = next.clone().run(request, extensions).await?;
+        let mut retry = match retry {
+            Some(retry) if is_invalid_token(&response) => retry,
+            _ => return Ok(response),
+        };
+
+        let token = self
+            .provider
+            .fresh_access_token(self.leeway, Some(&token))
+            .await
+            .map_err(reqwest_middleware::Error::middleware)?;
+        set_bearer(&mut retry, &token)?;
+        next.run(retry, extensions).await
+    }
+}
+
+fn set_bearer(request: &mut Request, token: &str) -> reqwest_middleware::Result<()> {
+    let value = header::HeaderValue::from_str(&format!("Bearer {}", token))
+        .map_err(reqwest_middleware::Error::middleware)?;
+    request.headers_mut().insert(header::AUTHORIZATION, value);
+    Ok(())
+}
+
+fn is_invalid_token(response: &Response) -> bool {
+    response.status() == StatusCode::UNAUTHORIZED
+        && response
+            .headers()
+            .get_all(header::WWW_AUTHENTICATE)
+            .iter()
+            .filter_map(|value| value.to_str().ok())
+            .any(|challenge| challenge.contains("invalid_token"))
+}
@@ -1,10 +1,14 @@
-use std::collections::HashMap;
+use std::time::Duration;
 
 use dotenv::dotenv;
+use jsonwebtoken::Algorithm;
 use serde::Deserialize;
 
-#[derive(Debug, Deserialize)]
+use super::{ClientError, OpenIdConfiguration, ValidationPolicy};
+
+#[derive(Debug, Clone, Default, Deserialize)]
 pub struct ClientConfiguration {
+    pub server_url: Option<String>,
     pub client_id: Option<String>,
     pub client_secret: Option<String>,
     pub auth_url: Option<String>,
@@ -16,42 +20,53 @@ pub struct ClientConfiguration {
     pub scopes: Vec<String>,
     pub username: Option<String>,
     pub password: Option<String>,
+    pub redirect_port: Option<u16>,
+    /// How long the authorization code flow waits for the browser's redirect. Defaults to five
+    /// minutes
+    pub authorization_timeout: Option<Duration>,
+    pub issuer: Option<String>,
+    pub introspection_url: Option<String>,
+    pub revocation_url: Option<String>,
+    pub end_session_url: Option<String>,
+    pub userinfo_url: Option<String>,
+    pub grant_types_supported: Vec<String>,
+    pub signing_algorithms: Vec<String>,
+    pub validation_policy: ValidationPolicy,
+    /// Algorithms accepted when verifying tokens. Defaults to the realm's advertised signing
+    /// algorithms after discovery, or `RS256` otherwise
+    pub allowed_algorithms: Vec<Algorithm>,
 }
 
 impl ClientConfiguration {
     pub fn from_env() -> Self {
-        let names = vec![
-            "client_id",
-            "client_secret",
-            "auth_url",
-            "token_url",
-            "device_authorization_url",
-            "token_cache_path",
-            "jwks_url",
-            "realm",
-            "username",
-            "password",
-            "scopes",
-        ];
-        let mut vars = HashMap::new();
-        for v in &names {
-            vars.insert(*v, format!("KK_{}", v.to_uppercase()));
-        }
-
         dotenv().ok();
-        let client_id: Option<String> = std::env::var(vars.get("client_id").expect("work")).ok();
-        let client_secret: Option<String> =
-            std::env::var(vars.get("client_secret").expect("work")).ok();
-        let auth_url: Option<String> = std::env::var(vars.get("auth_url").expect("work")).ok();
-        let token_url: Option<String> = std::env::var(vars.get("token_url").expect("work")).ok();
-        let device_authorization_url: Option<String> =
-            std::env::var(vars.get("device_authorization_url").expect("work")).ok();
-        let token_cache_path: Option<String> =
-            std::env::var(vars.get("token_cache_path").expect("work")).ok();
-        let jwks_url: Option<String> = std::env::var(vars.get("jwks_url").expect("work")).ok();
-        let realm: Option<String> = std::env::var(vars.get("realm").expect("work")).ok();
-        let username: Option<String> = std::env::var(vars.get("username").expect("work")).ok();
-        let password: Option<String> = std::env::var(vars.get("password").expect("work")).ok();
+        let var = |name: &str| std::env::var(format!("KK_{}", name.to_uppercase())).ok();
+
+        let server_url = var("server_url");
+        let client_id = var("client_id");
+        let client_secret = var("client_secret");
+        let auth_url = var("auth_url");
+        let token_url = var("token_url");
+        let device_authorization_url = var("device_authorization_url");
+        let token_cache_path = var("token_cache_path");
+        let jwks_url = var("jwks_url");
+        let realm = var("realm");
+        let username = var("username");
+        let password = var("password");
+
+        let redirect_port: Option<u16> = var("redirect_port").and_then(|p| p.parse().ok());
+        let authorization_timeout = var("authorization_timeout")
+            .and_then(|secs| secs.parse().ok())
+            .map(Duration::from_secs);
+
+        let validation_policy = match var("validation_policy").as_deref() {
+            Some("online") => ValidationPolicy::Online,
+            _ => ValidationPolicy::Local,
+        };
+
+        let allowed_algorithms = var("allowed_algorithms")
+            .map(|algs| parse_algorithms(algs.split(',')))
+            .unwrap_or_default();
 
         let scopes = std::env::var("scopes").ok();
         let scopes = match scopes {
@@ -68,6 +83,7 @@ impl ClientConfiguration {
         };
 
         ClientConfiguration {
+            server_url,
             client_id,
             client_secret,
             auth_url,
@@ -79,6 +95,70 @@ impl ClientConfiguration {
             scopes,
             username,
             password,
+            redirect_port,
+            authorization_timeout,
+            issuer: None,
+            introspection_url: None,
+            revocation_url: None,
+            end_session_url: None,
+            userinfo_url: None,
+            grant_types_supported: Vec::new(),
+            signing_algorithms: Vec::new(),
+            validation_policy,
+            allowed_algorithms,
         }
     }
+
+    /// Builds the configuration from the realm's OIDC discovery document.
+    ///
+    /// Every endpoint, the issuer, the supported grant types and the signing algorithms come
+    /// from `.well-known/openid-configuration`; everything else (client secret, scopes, cache
+    /// path, ...) is still read from the environment.
+    pub async fn discover(
+        server_url: impl Into<String>,
+        realm: impl Into<String>,
+    ) -> Result<Self, ClientError> {
+        let server_url = server_url.into();
+        let realm = realm.into();
+        let discovered = OpenIdConfiguration::fetch(&server_url, &realm)
+            .await
+            .map_err(ClientError::DiscoveryError)?;
+
+        let mut config = ClientConfiguration::from_env();
+        config.server_url = Some(server_url);
+        config.realm = Some(realm);
+        config.apply_discovery(discovered);
+        Ok(config)
+    }
+
+    pub fn apply_discovery(&mut self, discovered: OpenIdConfiguration) {
+        self.issuer = Some(discovered.issuer);
+        self.auth_url = Some(discovered.authorization_endpoint);
+        self.token_url = Some(discovered.token_endpoint);
+        self.jwks_url = Some(discovered.jwks_uri);
+        self.device_authorization_url = discovered.device_authorization_endpoint;
+        self.introspection_url = discovered.introspection_endpoint;
+        self.revocation_url = discovered.revocation_endpoint;
+        self.end_session_url = discovered.end_session_endpoint;
+        self.userinfo_url = discovered.userinfo_endpoint;
+        self.grant_types_supported = discovered.grant_types_supported;
+        self.signing_algorithms = discovered.id_token_signing_alg_values_supported;
+        if self.allowed_algorithms.is_empty() {
+            self.allowed_algorithms = parse_algorithms(self.signing_algorithms.iter());
+        }
+    }
+}
+
+/// Parses algorithm names, skipping unknown ones and the symmetric `HS*` algorithms, which can
+/// never be verified with a realm's public keys.
+fn parse_algorithms<I, S>(names: I) -> Vec<Algorithm>
+where
+    I: IntoIterator<Item = S>,
+    S: AsRef<str>,
+{
+    names
+        .into_iter()
+        .filter_map(|name| name.as_ref().trim().parse::<Algorithm>().ok())
+        .filter(|alg| !matches!(alg, Algorithm::HS256 | Algorithm::HS384 | Algorithm::HS512))
+        .collect()
 }
@@ -24,9 +24,60 @@ impl ResourceOwnerPasswordCredential {
     }
 }
 
+#[derive(Clone)]
+pub struct ClientCredentials {
+    pub client_id: String,
+    pub client_secret: String,
+}
+
+impl Credential for ClientCredentials {}
+
+impl Debug for ClientCredentials {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("ClientCredentials")
+            .field("client_id", &self.client_id)
+            .finish_non_exhaustive()
+    }
+}
+
+impl ClientCredentials {
+    pub fn new(
+        client_id: impl Into<String>,
+        client_secret: impl Into<String>,
+    ) -> ClientCredentials {
+        ClientCredentials {
+            client_id: client_id.into(),
+            client_secret: client_secret.into(),
+        }
+    }
+}
+
 #[derive(Debug, Clone)]
 pub struct DeviceCodeCredential {
     pub client_id: String,
 }
 
 impl Credential for DeviceCodeCredential {}
+
+#[derive(Debug, Clone)]
+pub struct AuthorizationCodeCredential {
+    pub client_id: String,
+    /// Port for the loopback redirect listener. `None` lets the OS pick an ephemeral port.
+    pub redirect_port: Option<u16>,
+}
+
+impl Credential for AuthorizationCodeCredential {}
+
+impl AuthorizationCodeCredential {
+    pub fn new(client_id: impl Into<String>) -> AuthorizationCodeCredential {
+        AuthorizationCodeCredential {
+            client_id: client_id.into(),
+            redirect_port: None,
+        }
+    }
+
+    pub fn with_redirect_port(mut self, port: u16) -> Self {
+        self.redirect_port = Some(port);
+        self
+    }
+}
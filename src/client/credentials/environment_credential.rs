@@ -5,7 +5,10 @@ use std::{
 
 use dotenv::dotenv;
 
-use super::{credential_types::ResourceOwnerPasswordCredential, DeviceCodeCredential};
+use super::{
+    credential_types::ResourceOwnerPasswordCredential, AuthorizationCodeCredential,
+    ClientCredentials, DeviceCodeCredential,
+};
 #[derive(Clone)]
 pub struct EnvironmentCredential;
 
@@ -23,6 +26,12 @@ impl EnvironmentCredential {
     pub fn device_credential() -> Result<DeviceCodeCredential, VarError> {
         EnvironmentCredential::try_device_env()
     }
+    pub fn client_credentials() -> Result<ClientCredentials, VarError> {
+        EnvironmentCredential::try_client_credentials_env()
+    }
+    pub fn authorization_code_credential() -> Result<AuthorizationCodeCredential, VarError> {
+        EnvironmentCredential::try_authorization_code_env()
+    }
 
     fn try_username_password_env() -> Result<ResourceOwnerPasswordCredential, VarError> {
         dotenv().ok();
@@ -40,9 +49,30 @@ impl EnvironmentCredential {
     }
     fn try_device_env() -> Result<DeviceCodeCredential, VarError> {
         dotenv().ok();
-        println!("clientid");
         let client_id = std::env::var("KK_CLIENT_ID")?;
 
         Ok(DeviceCodeCredential { client_id })
     }
+    fn try_client_credentials_env() -> Result<ClientCredentials, VarError> {
+        dotenv().ok();
+        let client_id = std::env::var("KK_CLIENT_ID")?;
+        let client_secret = std::env::var("KK_CLIENT_SECRET")?;
+
+        Ok(ClientCredentials {
+            client_id,
+            client_secret,
+        })
+    }
+    fn try_authorization_code_env() -> Result<AuthorizationCodeCredential, VarError> {
+        dotenv().ok();
+        let client_id = std::env::var("KK_CLIENT_ID")?;
+        let redirect_port = std::env::var("KK_REDIRECT_PORT")
+            .ok()
+            .and_then(|p| p.parse::<u16>().ok());
+
+        Ok(AuthorizationCodeCredential {
+            client_id,
+            redirect_port,
+        })
+    }
 }
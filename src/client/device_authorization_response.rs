@@ -22,20 +22,21 @@ impl PollDeviceCodeEvent {
             PollDeviceCodeEvent::SlowDown => "slow_down",
         }
     }
+    /// Describes the event on stderr, leaving stdout to the application.
     pub fn as_message(&self) {
         match self {
             PollDeviceCodeEvent::AuthorizationPending => {
-                println!("authorization_pending, continuing")
+                eprintln!("authorization_pending, continuing")
             }
             PollDeviceCodeEvent::AuthorizationDeclined => {
-                println!("authorization_declined! exiting loop")
+                eprintln!("authorization_declined! exiting loop")
             }
             PollDeviceCodeEvent::BadVerificationCode => {
-                println!("bad_verification_code! continuing")
+                eprintln!("bad_verification_code! continuing")
             }
-            PollDeviceCodeEvent::ExpiredToken => println!("expired_token, exiting loop"),
-            PollDeviceCodeEvent::AccessDenied => println!("access_denied, exiting loop"),
-            PollDeviceCodeEvent::SlowDown => println!("slow_down! adding 5 sec to interval"),
+            PollDeviceCodeEvent::ExpiredToken => eprintln!("expired_token, exiting loop"),
+            PollDeviceCodeEvent::AccessDenied => eprintln!("access_denied, exiting loop"),
+            PollDeviceCodeEvent::SlowDown => eprintln!("slow_down! adding 5 sec to interval"),
         }
     }
 }
@@ -49,9 +50,10 @@ impl From<StandardErrorResponse<DeviceCodeErrorResponseType>> for PollDeviceCode
             }
             DeviceCodeErrorResponseType::SlowDown => PollDeviceCodeEvent::SlowDown,
             DeviceCodeErrorResponseType::ExpiredToken => PollDeviceCodeEvent::ExpiredToken,
+            // any other error (invalid_client, invalid_grant, ...) ends polling like a denial
             DeviceCodeErrorResponseType::Basic(basic_error_response_type) => {
                 PollDeviceCodeEvent::from_str(basic_error_response_type.to_string().as_str())
-                    .expect("parsed")
+                    .unwrap_or(PollDeviceCodeEvent::AccessDenied)
             }
         };
 
@@ -0,0 +1,38 @@
+use serde::{Deserialize, Serialize};
+
+/// The subset of a realm's `.well-known/openid-configuration` document used by the client.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct OpenIdConfiguration {
+    pub issuer: String,
+    pub authorization_endpoint: String,
+    pub token_endpoint: String,
+    pub jwks_uri: String,
+    pub device_authorization_endpoint: Option<String>,
+    pub introspection_endpoint: Option<String>,
+    pub revocation_endpoint: Option<String>,
+    pub end_session_endpoint: Option<String>,
+    pub userinfo_endpoint: Option<String>,
+    #[serde(default)]
+    pub grant_types_supported: Vec<String>,
+    #[serde(default)]
+    pub id_token_signing_alg_values_supported: Vec<String>,
+}
+
+impl OpenIdConfiguration {
+    /// Url of the discovery document for `realm` on the Keycloak server at `server_url`.
+    pub fn url(server_url: &str, realm: &str) -> String {
+        format!(
+            "{}/realms/{}/.well-known/openid-configuration",
+            server_url.trim_end_matches('/'),
+            realm
+        )
+    }
+
+    pub async fn fetch(server_url: &str, realm: &str) -> Result<Self, reqwest::Error> {
+        reqwest::get(Self::url(server_url, realm))
+            .await?
+            .error_for_status()?
+            .json::<OpenIdConfiguration>()
+            .await
+    }
+}
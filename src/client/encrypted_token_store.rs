@@ -0,0 +1,299 @@
+use std::{
+    fmt::{Debug, Formatter},
+    path::{Path, PathBuf},
+    sync::{
+        atomic::{AtomicU64, Ordering},
+        Arc, Mutex,
+    },
+};
+
+use argon2::Argon2;
+use async_trait::async_trait;
+use base64::{engine::general_purpose::STANDARD, Engine};
+use chacha20poly1305::{
+    aead::{rand_core::RngCore, Aead, AeadCore, KeyInit, OsRng, Payload},
+    ChaCha20Poly1305, Key, Nonce,
+};
+use tokio::io::AsyncWriteExt;
+
+use super::{CachedToken, ClientError, StoreLock, TokenStore};
+
+const MAGIC: &[u8; 4] = b"KKT1";
+const SALT_LEN: usize = 16;
+const NONCE_LEN: usize = 12;
+const KEY_LEN: usize = 32;
+
+/// Where the encryption key of an `EncryptedFileTokenStore` comes from.
+#[derive(Clone)]
+pub enum TokenKey {
+    /// Name of an environment variable holding a base64 encoded 32 byte key
+    Env(String),
+    /// File holding a 32 byte key, either raw or base64 encoded
+    File(PathBuf),
+    /// Passphrase stretched into a key with Argon2id, using a random salt stored with the token
+    Passphrase(String),
+}
+
+impl Debug for TokenKey {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            TokenKey::Env(name) => f.debug_tuple("Env").field(name).finish(),
+            TokenKey::File(path) => f.debug_tuple("File").field(path).finish(),
+            TokenKey::Passphrase(_) => f.debug_tuple("Passphrase").field(&"..").finish(),
+        }
+    }
+}
+
+impl TokenKey {
+    async fn derive(&self, salt: &[u8]) -> Result<Key, ClientError> {
+        let key = match self {
+            TokenKey::Env(name) => {
+                let encoded = std::env::var(name).map_err(|e| {
+                    ClientError::TokenKeyError(format!("cannot read {}: {}", name, e))
+                })?;
+                decode_key(encoded.trim().as_bytes())?
+            }
+            TokenKey::File(path) => {
+                let data = tokio::fs::read(path).await.map_err(|e| {
+                    ClientError::TokenKeyError(format!("cannot read {}: {}", path.display(), e))
+                })?;
+                decode_key(&data)?
+            }
+            TokenKey::Passphrase(passphrase) => {
+                // Argon2 takes tens of milliseconds on purpose, keep it off the runtime's workers
+                let passphrase = passphrase.clone();
+                let salt = salt.to_vec();
+                tokio::task::spawn_blocking(move || {
+                    let mut key = [0u8; KEY_LEN];
+                    Argon2::default()
+                        .hash_password_into(passphrase.as_bytes(), &salt, &mut key)
+                        .map(|()| key)
+                        .map_err(|e| ClientError::TokenKeyError(e.to_string()))
+                })
+                .await
+                .map_err(|e| ClientError::TokenKeyError(e.to_string()))??
+            }
+        };
+        Ok(Key::from(key))
+    }
+}
+
+/// Accepts the key either as raw bytes or base64 encoded.
+fn decode_key(data: &[u8]) -> Result<[u8; KEY_LEN], ClientError> {
+    if let Ok(key) = <[u8; KEY_LEN]>::try_from(data) {
+        return Ok(key);
+    }
+    let trimmed = std::str::from_utf8(data)
+        .map(str::trim)
+        .map_err(|_| ClientError::TokenKeyError("key is neither 32 bytes nor base64".into()))?;
+    let decoded = STANDARD
+        .decode(trimmed)
+        .map_err(|e| ClientError::TokenKeyError(e.to_string()))?;
+    <[u8; KEY_LEN]>::try_from(decoded.as_slice())
+        .map_err(|_| ClientError::TokenKeyError(format!("key must be {} bytes", KEY_LEN)))
+}
+
+/// Writes the token to `path` encrypted with ChaCha20-Poly1305.
+///
+/// The file holds a header (magic and salt), the nonce and the ciphertext. The header is
+/// authenticated along with the token, so any modification of the file, as well as a wrong key,
+/// is reported as `ClientError::TokenDecryptionError`. Files are replaced atomically and written
+/// with `0600` permissions.
+///
+/// A key stretched from a passphrase is kept along with its salt, so the passphrase is only
+/// stretched again when another process rewrote the file with a new salt.
+#[derive(Debug, Clone)]
+pub struct EncryptedFileTokenStore {
+    path: PathBuf,
+    key: TokenKey,
+    derived: Arc<Mutex<Option<DerivedKey>>>,
+}
+
+/// A key and the salt it was derived with.
+#[derive(Clone)]
+struct DerivedKey {
+    salt: [u8; SALT_LEN],
+    key: Key,
+}
+
+impl Debug for DerivedKey {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("DerivedKey").finish_non_exhaustive()
+    }
+}
+
+impl EncryptedFileTokenStore {
+    pub fn new(path: impl Into<PathBuf>, key: TokenKey) -> Self {
+        Self {
+            path: path.into(),
+            key,
+            derived: Arc::default(),
+        }
+    }
+
+    pub fn path(&self) -> &PathBuf {
+        &self.path
+    }
+
+    /// The key for `salt`. A passphrase is only stretched when `salt` is not the one it was
+    /// stretched with last; keys from the environment or a file are read every time, so they
+    /// can be rotated.
+    async fn key(&self, salt: [u8; SALT_LEN]) -> Result<Key, ClientError> {
+        if !matches!(self.key, TokenKey::Passphrase(_)) {
+            return self.key.derive(&salt).await;
+        }
+        if let Some(derived) = self.cached_key() {
+            if derived.salt == salt {
+                return Ok(derived.key);
+            }
+        }
+        let key = self.key.derive(&salt).await?;
+        *self.derived.lock().unwrap_or_else(|e| e.into_inner()) = Some(DerivedKey { salt, key });
+        Ok(key)
+    }
+
+    fn cached_key(&self) -> Option<DerivedKey> {
+        self.derived
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .clone()
+    }
+
+    async fn encrypt(&self, plaintext: &[u8]) -> Result<Vec<u8>, ClientError> {
+        // the nonce is fresh for every write, so the salt of the derived key can be reused
+        let salt = match self.cached_key() {
+            Some(derived) => derived.salt,
+            None => {
+                let mut salt = [0u8; SALT_LEN];
+                OsRng.fill_bytes(&mut salt);
+                salt
+            }
+        };
+
+        let cipher = ChaCha20Poly1305::new(&self.key(salt).await?);
+        let nonce = ChaCha20Poly1305::generate_nonce(&mut OsRng);
+
+        let mut header = MAGIC.to_vec();
+        header.extend_from_slice(&salt);
+        let ciphertext = cipher
+            .encrypt(
+                &nonce,
+                Payload {
+                    msg: plaintext,
+                    aad: &header,
+                },
+            )
+            .map_err(|_| ClientError::TokenKeyError("encryption failed".into()))?;
+
+        let mut data = header;
+        data.extend_from_slice(&nonce);
+        data.extend_from_slice(&ciphertext);
+        Ok(data)
+    }
+
+    async fn decrypt(&self, data: &[u8]) -> Result<Vec<u8>, ClientError> {
+        let header_len = MAGIC.len() + SALT_LEN;
+        if data.len() < header_len + NONCE_LEN || &data[..MAGIC.len()] != MAGIC {
+            return Err(ClientError::TokenDecryptionError);
+        }
+        let (header, rest) = data.split_at(header_len);
+        let (nonce, ciphertext) = rest.split_at(NONCE_LEN);
+
+        let salt = header[MAGIC.len()..]
+            .try_into()
+            .map_err(|_| ClientError::TokenDecryptionError)?;
+        let cipher = ChaCha20Poly1305::new(&self.key(salt).await?);
+        cipher
+            .decrypt(
+                Nonce::from_slice(nonce),
+                Payload {
+                    msg: ciphertext,
+                    aad: header,
+                },
+            )
+            .map_err(|_| ClientError::TokenDecryptionError)
+    }
+}
+
+#[async_trait]
+impl TokenStore for EncryptedFileTokenStore {
+    async fn load(&self) -> Result<Option<CachedToken>, ClientError> {
+        let data = match tokio::fs::read(&self.path).await {
+            Ok(data) => data,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(None),
+            Err(e) => return Err(e.into()),
+        };
+        let plaintext = self.decrypt(&data).await?;
+        let cached_token: CachedToken =
+            serde_json::from_slice(&plaintext).map_err(|_| ClientError::TokenDecryptionError)?;
+        Ok(Some(cached_token))
+    }
+
+    async fn save(&self, token: &CachedToken) -> Result<(), ClientError> {
+        let serialized = serde_json::to_vec(token)?;
+        let data = self.encrypt(&serialized).await?;
+        write_atomically(&self.path, &data).await
+    }
+
+    async fn clear(&self) -> Result<(), ClientError> {
+        match tokio::fs::remove_file(&self.path).await {
+            Ok(()) => Ok(()),
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(()),
+            Err(e) => Err(e.into()),
+        }
+    }
+
+    async fn lock(&self) -> Result<Option<StoreLock>, ClientError> {
+        StoreLock::acquire(&self.path).await.map(Some)
+    }
+}
+
+/// Distinguishes the temporary files of concurrent saves within one process.
+static TMP_COUNTER: AtomicU64 = AtomicU64::new(0);
+
+/// Writes `data` to a temporary file next to `path` and renames it over `path`, so readers never
+/// see a partially written token.
+async fn write_atomically(path: &Path, data: &[u8]) -> Result<(), ClientError> {
+    let dir = match path.parent() {
+        Some(parent) if !parent.as_os_str().is_empty() => parent.to_path_buf(),
+        _ => PathBuf::from("."),
+    };
+    tokio::fs::create_dir_all(&dir).await?;
+
+    let file_name = path
+        .file_name()
+        .map(|n| n.to_string_lossy().into_owned())
+        .unwrap_or_else(|| "token".into());
+    let tmp_path = dir.join(format!(
+        ".{}.{}.{}.tmp",
+        file_name,
+        std::process::id(),
+        TMP_COUNTER.fetch_add(1, Ordering::Relaxed)
+    ));
+
+    let mut options = tokio::fs::OpenOptions::new();
+    options.write(true).create(true).truncate(true);
+    #[cfg(unix)]
+    options.mode(0o600);
+
+    let mut file = options.open(&tmp_path).await?;
+    let written = async {
+        // the mode only applies to newly created files
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::PermissionsExt;
+            file.set_permissions(std::fs::Permissions::from_mode(0o600))
+                .await?;
+        }
+        file.write_all(data).await?;
+        file.sync_all().await?;
+        drop(file);
+        tokio::fs::rename(&tmp_path, path).await
+    }
+    .await;
+    if let Err(e) = written {
+        let _ = tokio::fs::remove_file(&tmp_path).await;
+        return Err(e.into());
+    }
+    Ok(())
+}
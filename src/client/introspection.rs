@@ -0,0 +1,59 @@
+use std::collections::HashMap;
+
+use serde::{Deserialize, Serialize};
+
+/// Roles granted for a realm or a single client.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct RoleAccess {
+    #[serde(default)]
+    pub roles: Vec<String>,
+}
+
+/// Response of Keycloak's `token/introspect` endpoint (RFC 7662).
+///
+/// Only `active` is guaranteed; every other field is absent for inactive tokens.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct IntrospectionResponse {
+    pub active: bool,
+    pub scope: Option<String>,
+    pub username: Option<String>,
+    pub client_id: Option<String>,
+    pub token_type: Option<String>,
+    pub exp: Option<i64>,
+    pub iat: Option<i64>,
+    pub sub: Option<String>,
+    pub iss: Option<String>,
+    pub realm_access: Option<RoleAccess>,
+    #[serde(default)]
+    pub resource_access: HashMap<String, RoleAccess>,
+}
+
+impl IntrospectionResponse {
+    /// Realm roles of the token.
+    pub fn roles(&self) -> &[String] {
+        self.realm_access
+            .as_ref()
+            .map(|access| access.roles.as_slice())
+            .unwrap_or_default()
+    }
+
+    /// Roles of the token on `client_id`.
+    pub fn client_roles(&self, client_id: &str) -> &[String] {
+        self.resource_access
+            .get(client_id)
+            .map(|access| access.roles.as_slice())
+            .unwrap_or_default()
+    }
+}
+
+/// How `KeycloakClient::verify_access_token` decides a token is valid.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ValidationPolicy {
+    /// Only check the signature and claims against the realm's JWKS
+    #[default]
+    Local,
+    /// After the local checks pass, also ask Keycloak whether the token is still active, so
+    /// tokens revoked by a logout or an admin are rejected
+    Online,
+}
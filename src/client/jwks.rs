@@ -4,14 +4,84 @@ use std::{
     time::{Duration, Instant},
 };
 
+use jsonwebtoken::{Algorithm, DecodingKey};
+use serde::{Deserialize, Serialize};
+
 use super::VerifyJwtError;
 
+/// A public key from the realm's JWKS endpoint (RFC 7517).
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Jwk {
+    pub kid: String,
+    pub kty: String,
+    pub alg: Option<String>,
+    #[serde(rename = "use")]
+    pub key_use: Option<String>,
+    pub crv: Option<String>,
+    pub n: Option<String>,
+    pub e: Option<String>,
+    pub x: Option<String>,
+    pub y: Option<String>,
+    #[serde(default)]
+    pub x5c: Vec<String>,
+}
+
+impl Jwk {
+    /// The signing algorithm declared by the key, if any.
+    pub fn algorithm(&self) -> Option<Algorithm> {
+        self.alg.as_deref().and_then(|alg| alg.parse().ok())
+    }
+
+    /// Whether the key can verify signatures made with `algorithm`.
+    pub fn supports(&self, algorithm: Algorithm) -> bool {
+        if let Some(alg) = self.algorithm() {
+            return alg == algorithm;
+        }
+        match algorithm {
+            Algorithm::RS256
+            | Algorithm::RS384
+            | Algorithm::RS512
+            | Algorithm::PS256
+            | Algorithm::PS384
+            | Algorithm::PS512 => self.kty == "RSA",
+            Algorithm::ES256 => self.kty == "EC" && self.crv.as_deref() == Some("P-256"),
+            Algorithm::ES384 => self.kty == "EC" && self.crv.as_deref() == Some("P-384"),
+            Algorithm::EdDSA => self.kty == "OKP" && self.crv.as_deref() == Some("Ed25519"),
+            Algorithm::HS256 | Algorithm::HS384 | Algorithm::HS512 => false,
+        }
+    }
+
+    /// Builds the key used to verify signatures.
+    pub fn decoding_key(&self) -> Result<DecodingKey, VerifyJwtError> {
+        let key = match self.kty.as_str() {
+            "RSA" => {
+                let n = self.n.as_deref().ok_or("RSA key is missing n")?;
+                let e = self.e.as_deref().ok_or("RSA key is missing e")?;
+                DecodingKey::from_rsa_components(n, e)?
+            }
+            "EC" => {
+                let x = self.x.as_deref().ok_or("EC key is missing x")?;
+                let y = self.y.as_deref().ok_or("EC key is missing y")?;
+                DecodingKey::from_ec_components(x, y)?
+            }
+            "OKP" => {
+                let x = self.x.as_deref().ok_or("OKP key is missing x")?;
+                DecodingKey::from_ed_components(x)?
+            }
+            _ => return Err("Unsupported key type".into()),
+        };
+        Ok(key)
+    }
+}
+
 //build key cache - this will be refreshed based on the implementation
 #[derive(Debug)]
 pub struct KeyCache {
-    keys: HashMap<String, String>,
+    keys: HashMap<String, Jwk>,
     last_updated: Instant,
     first: bool,
+    // keys given up front are never replaced by a fetch
+    fixed: bool,
 }
 impl Default for KeyCache {
     fn default() -> Self {
@@ -25,9 +95,25 @@ impl KeyCache {
             keys: HashMap::new(),
             last_updated: Instant::now(),
             first: true,
+            fixed: false,
         }
     }
 
+    /// A cache holding a static set of keys, which never fetches the JWKS endpoint. Useful for
+    /// tests with locally minted tokens and for deployments with pinned keys.
+    pub fn from_keys(keys: impl IntoIterator<Item = Jwk>) -> Self {
+        Self {
+            keys: keys.into_iter().map(|jwk| (jwk.kid.clone(), jwk)).collect(),
+            last_updated: Instant::now(),
+            first: false,
+            fixed: true,
+        }
+    }
+
+    pub fn into_shared(self) -> SharedKeyCache {
+        Arc::new(tokio::sync::Mutex::new(self))
+    }
+
     fn is_expired(&self) -> bool {
         self.last_updated.elapsed() > Duration::from_secs(24 * 60 * 60)
     }
@@ -50,16 +136,16 @@ impl std::error::Error for FetchError {}
 pub async fn fetch_and_cache_jwks(
     jwks_url: &str,
     cache: SharedKeyCache,
-) -> Result<HashMap<String, String>, VerifyJwtError> {
-    let mut cache_guard = cache.try_lock().map_err(FetchError::Lock)?;
-    println!("cache guard before update: {:#?}", cache_guard);
+) -> Result<HashMap<String, Jwk>, VerifyJwtError> {
+    let mut cache_guard = cache.lock().await;
 
-    if cache_guard.first || cache_guard.is_expired() {
+    if !cache_guard.fixed && (cache_guard.first || cache_guard.is_expired()) {
         let client = reqwest::Client::new();
         let resp = client
             .get(jwks_url)
             .send()
             .await
+            .and_then(reqwest::Response::error_for_status)
             .map_err(FetchError::Reqwest)?;
         let resp_json = resp
             .json::<serde_json::Value>()
@@ -71,19 +157,17 @@ pub async fn fetch_and_cache_jwks(
         if let Some(keys) = resp_json.get("keys") {
             if let Some(keys_array) = keys.as_array() {
                 for key in keys_array {
-                    if let (Some(kid), Some(n), Some(e)) = (
-                        key.get("kid").and_then(|k| k.as_str()),
-                        key.get("n").and_then(|n| n.as_str()),
-                        key.get("e").and_then(|e| e.as_str()),
-                    ) {
-                        cache_guard
-                            .keys
-                            .insert(kid.to_string(), format!("{}|{}", n, e));
+                    // encryption keys and keys we cannot parse are skipped
+                    let Ok(jwk) = serde_json::from_value::<Jwk>(key.clone()) else {
+                        continue;
+                    };
+                    if jwk.key_use.as_deref().is_some_and(|u| u != "sig") {
+                        continue;
                     }
+                    cache_guard.keys.insert(jwk.kid.clone(), jwk);
                 }
             }
         }
-        println!("Fetched jwks: {:#?}", cache_guard.keys);
         cache_guard.last_updated = Instant::now();
         if cache_guard.first {
             cache_guard.first = false
@@ -1,15 +1,66 @@
-use jsonwebtoken::{decode, decode_header, Algorithm, DecodingKey, TokenData, Validation};
-use serde::{Deserialize, Serialize};
-
-use super::jwks::{fetch_and_cache_jwks, FetchError, SharedKeyCache};
-
-#[derive(Serialize, Deserialize)]
-pub struct Claims {
-    sub: String,
-    exp: usize,
-    iat: usize,
-    iss: String,
-    aud: Vec<String>,
+use std::collections::HashMap;
+
+use jsonwebtoken::{decode, decode_header, Algorithm, TokenData, Validation};
+use serde::{de::DeserializeOwned, Deserialize, Serialize};
+use serde_with::{serde_as, OneOrMany};
+
+use super::{
+    jwks::{fetch_and_cache_jwks, FetchError, SharedKeyCache},
+    RoleAccess,
+};
+
+/// Claims of a Keycloak access token.
+///
+/// Claims added by custom protocol mappers end up in `extra`.
+#[serde_as]
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct KeycloakClaims {
+    pub sub: Option<String>,
+    pub exp: i64,
+    pub iat: i64,
+    pub iss: String,
+    /// Keycloak sends a single string when the token has one audience
+    #[serde_as(as = "OneOrMany<_>")]
+    #[serde(default)]
+    pub aud: Vec<String>,
+    pub azp: Option<String>,
+    pub scope: Option<String>,
+    pub sid: Option<String>,
+    pub preferred_username: Option<String>,
+    pub email: Option<String>,
+    pub name: Option<String>,
+    pub realm_access: Option<RoleAccess>,
+    #[serde(default)]
+    pub resource_access: HashMap<String, RoleAccess>,
+    #[serde(flatten)]
+    pub extra: HashMap<String, serde_json::Value>,
+}
+
+/// Kept for code written against the original claims type.
+pub type Claims = KeycloakClaims;
+
+impl KeycloakClaims {
+    pub fn realm_roles(&self) -> &[String] {
+        self.realm_access
+            .as_ref()
+            .map(|access| access.roles.as_slice())
+            .unwrap_or_default()
+    }
+
+    pub fn client_roles(&self, client_id: &str) -> &[String] {
+        self.resource_access
+            .get(client_id)
+            .map(|access| access.roles.as_slice())
+            .unwrap_or_default()
+    }
+
+    /// The space separated `scope` claim as a list.
+    pub fn scopes(&self) -> Vec<&str> {
+        self.scope
+            .as_deref()
+            .map(|scope| scope.split_whitespace().collect())
+            .unwrap_or_default()
+    }
 }
 
 #[derive(Debug)]
@@ -17,6 +68,9 @@ pub enum VerifyJwtError {
     FetchJwksError(FetchError),
     JwtDecodeError(jsonwebtoken::errors::Error),
     InvalidKeyFormatError(String),
+    DisallowedAlgorithmError(Algorithm),
+    /// The verifier was given no audience and was not told to accept any
+    NoAudienceError,
 }
 impl std::fmt::Display for VerifyJwtError {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
@@ -42,13 +96,41 @@ impl From<&str> for VerifyJwtError {
     }
 }
 
-pub async fn verify_jwt(
+/// Verifies `token` against the realm's JWKS and decodes its claims into `T`, which can be
+/// `KeycloakClaims` or any application specific struct.
+///
+/// The token's `aud` has to contain one of `audience`, so an empty list rejects every token.
+pub async fn verify_jwt<T: DeserializeOwned>(
     token: &str,
     jwks_url: &str,
     cache: SharedKeyCache,
     audience: &[&str],
     issuer: &[&str],
-) -> Result<TokenData<Claims>, VerifyJwtError> {
+    allowed_algorithms: &[Algorithm],
+) -> Result<TokenData<T>, VerifyJwtError> {
+    if audience.is_empty() {
+        return Err(VerifyJwtError::NoAudienceError);
+    }
+    verify(
+        token,
+        jwks_url,
+        cache,
+        Some(audience),
+        issuer,
+        allowed_algorithms,
+    )
+    .await
+}
+
+/// `verify_jwt`, skipping the audience check when `audience` is `None`.
+async fn verify<T: DeserializeOwned>(
+    token: &str,
+    jwks_url: &str,
+    cache: SharedKeyCache,
+    audience: Option<&[&str]>,
+    issuer: &[&str],
+    allowed_algorithms: &[Algorithm],
+) -> Result<TokenData<T>, VerifyJwtError> {
     // Fetch JWKS from cache or update it if needed
     let keys = fetch_and_cache_jwks(jwks_url, cache).await?;
 
@@ -56,26 +138,112 @@ pub async fn verify_jwt(
     let header = decode_header(token)?;
     let kid = header.kid.ok_or("Missing kid in JWT header")?;
 
-    // Get the corresponding public key from the JWKS cache
-    let key_data = keys.get(&kid).ok_or("No matching public key found")?;
-    let (n, e) = key_data.split_once('|').ok_or("Invalid key format")?;
+    // Only algorithms on the allow-list are accepted, whatever the token claims
+    if !allowed_algorithms.contains(&header.alg) {
+        return Err(VerifyJwtError::DisallowedAlgorithmError(header.alg));
+    }
 
-    // Convert the public key components (n, e) to a decoding key
-    let decoding_key = DecodingKey::from_rsa_components(n, e)?;
+    // Get the corresponding public key from the JWKS cache
+    let jwk = keys.get(&kid).ok_or("No matching public key found")?;
+    if !jwk.supports(header.alg) {
+        return Err("Key does not match the token algorithm".into());
+    }
+    let decoding_key = jwk.decoding_key()?;
 
     // Validate and decode the JWT
-    let mut validation = Validation::new(Algorithm::RS256);
+    let mut validation = Validation::new(header.alg);
+    match audience {
+        Some(audience) => validation.set_audience(audience),
+        None => validation.validate_aud = false,
+    }
+    if !issuer.is_empty() {
+        validation.set_issuer(issuer);
+    }
 
-    validation.set_audience(audience);
-    validation.set_issuer(issuer);
+    Ok(decode::<T>(token, &decoding_key, &validation)?)
+}
+
+/// Everything needed to verify tokens of one realm: the JWKS (and its cache), the expected
+/// audience and issuer, and the accepted algorithms.
+///
+/// Cloning is cheap and clones share the key cache, so one verifier can be handed to every
+/// request handler.
+///
+/// Every token of a realm is signed with the same keys, whichever client it was issued to, so
+/// the audience is what keeps tokens for other clients out. A verifier rejects every token until
+/// `audience` is set, or `any_audience` explicitly opts out of the check.
+#[derive(Debug, Clone)]
+pub struct TokenVerifier {
+    pub jwks_url: String,
+    pub cache: SharedKeyCache,
+    pub audience: Vec<String>,
+    /// Skips the audience check, set by `any_audience`
+    pub any_audience: bool,
+    pub issuer: Vec<String>,
+    pub allowed_algorithms: Vec<Algorithm>,
+}
 
-    match decode::<Claims>(token, &decoding_key, &validation) {
-        Ok(token_data) => Ok(token_data),
-        Err(e) => {
-            eprintln!("{:?}", e);
-            Err(VerifyJwtError::InvalidKeyFormatError(e.to_string()))
+impl TokenVerifier {
+    /// Accepts `RS256` tokens until `allowed_algorithms` is set.
+    pub fn new(jwks_url: impl Into<String>, cache: SharedKeyCache) -> Self {
+        Self {
+            jwks_url: jwks_url.into(),
+            cache,
+            audience: Vec::new(),
+            any_audience: false,
+            issuer: Vec::new(),
+            allowed_algorithms: vec![Algorithm::RS256],
         }
     }
 
-    // Token is valid, return the claims
+    pub fn audience<I, S>(mut self, audience: I) -> Self
+    where
+        I: IntoIterator<Item = S>,
+        S: Into<String>,
+    {
+        self.audience = audience.into_iter().map(Into::into).collect();
+        self.any_audience = false;
+        self
+    }
+
+    /// Accepts tokens issued to any client of the realm, e.g. to show who a token belongs to.
+    pub fn any_audience(mut self) -> Self {
+        self.audience.clear();
+        self.any_audience = true;
+        self
+    }
+
+    pub fn issuer<I, S>(mut self, issuer: I) -> Self
+    where
+        I: IntoIterator<Item = S>,
+        S: Into<String>,
+    {
+        self.issuer = issuer.into_iter().map(Into::into).collect();
+        self
+    }
+
+    pub fn allowed_algorithms(mut self, algorithms: &[Algorithm]) -> Self {
+        self.allowed_algorithms = algorithms.to_vec();
+        self
+    }
+
+    pub async fn verify<T: DeserializeOwned>(
+        &self,
+        token: &str,
+    ) -> Result<TokenData<T>, VerifyJwtError> {
+        let audience = self.audience.iter().map(String::as_str).collect::<Vec<_>>();
+        let issuer = self.issuer.iter().map(String::as_str).collect::<Vec<_>>();
+        if audience.is_empty() && !self.any_audience {
+            return Err(VerifyJwtError::NoAudienceError);
+        }
+        verify(
+            token,
+            &self.jwks_url,
+            self.cache.clone(),
+            (!self.any_audience).then_some(audience.as_slice()),
+            &issuer,
+            &self.allowed_algorithms,
+        )
+        .await
+    }
 }
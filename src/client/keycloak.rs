@@ -1,15 +1,19 @@
-use jsonwebtoken::TokenData;
+use jsonwebtoken::{Algorithm, TokenData};
 use oauth2::{
-    basic::BasicErrorResponseType, reqwest::async_http_client, ClientSecret,
-    DeviceAuthorizationResponse, EmptyExtraDeviceAuthorizationFields, RequestTokenError,
-    ResourceOwnerPassword, ResourceOwnerUsername, Scope, StandardErrorResponse,
-    StandardTokenResponse, TokenResponse, TokenUrl,
+    AuthorizationCode, ClientSecret, CsrfToken, DeviceAuthorizationResponse,
+    EmptyExtraDeviceAuthorizationFields, PkceCodeChallenge, RedirectUrl, RequestTokenError,
+    ResourceOwnerPassword, ResourceOwnerUsername, Scope, StandardTokenResponse, TokenResponse,
+    TokenUrl,
 };
-use serde::{Deserialize, Serialize};
+use serde::{de::DeserializeOwned, Deserialize, Serialize};
 use serde_with::{serde_as, DisplayFromStr};
-use std::{fs, marker::PhantomData, path::Path, sync::Arc};
+use std::{marker::PhantomData, sync::Arc, time::Duration};
 use thiserror::Error;
-use tokio::sync::Mutex;
+use tokio::{
+    io::{AsyncBufReadExt, AsyncWriteExt, BufReader},
+    net::{TcpListener, TcpStream},
+    sync::Mutex,
+};
 
 use oauth2::{
     basic::{BasicClient, BasicTokenType},
@@ -21,23 +25,31 @@ use crate::client::PollDeviceCodeEvent;
 use super::{
     config::ClientConfiguration,
     jwks::{KeyCache, SharedKeyCache},
-    verify_jwt, AppConfig, Claims, DeviceCodeCredential, ResourceOwnerPasswordCredential,
-    VerifyJwtError, WithDeviceCredentials, WithOwnerCredentials,
+    keycloak_error::{status_category, StatusRecorder},
+    AppConfig, AuthorizationCodeCredential, ClientCredentials, Credential, Denial,
+    DeviceCodeCredential, ErrorCategory, FileTokenStore, IntrospectionResponse, KeycloakClaims,
+    KeycloakError, MemoryTokenStore, ResourceOwnerPasswordCredential, TokenStore, TokenVerifier,
+    ValidationPolicy, VerifyJwtError, WithAuthorizationCode, WithClientCredentials,
+    WithDeviceCredentials, WithOwnerCredentials,
 };
 
+/// How long the authorization code flow waits for the redirect unless configured otherwise.
+const DEFAULT_AUTHORIZATION_TIMEOUT: Duration = Duration::from_secs(300);
+
 #[derive(Error, Debug)]
 pub enum ClientError {
     #[error("Token cache error: {0}")]
     TokenCacheError(#[from] serde_json::Error),
 
-    #[error("OAuth2 Request Token error: {0}")]
-    OAuth2RequestTokenError(
-        #[from]
-        RequestTokenError<
-            oauth2::reqwest::Error<reqwest::Error>,
-            StandardErrorResponse<BasicErrorResponseType>,
-        >,
-    ),
+    #[error("Keycloak error: {0}")]
+    KeycloakError(KeycloakError),
+
+    #[error("Unexpected response from Keycloak (HTTP {}): {message}", status.map_or("unknown".into(), |s| s.to_string()))]
+    InvalidResponseError {
+        status: Option<u16>,
+        message: String,
+    },
+
     #[error("I/O error: {0}")]
     IoError(#[from] std::io::Error),
 
@@ -49,6 +61,144 @@ pub enum ClientError {
 
     #[error("Missing credentials for password grant. Check the KK_USER and KK_PASSWORD in your .env file")]
     NoPresentCredentialsError,
+
+    #[error("Authorization callback error: {0}")]
+    AuthorizationCallbackError(String),
+
+    #[error("No authorization callback arrived within {0:?}")]
+    AuthorizationTimeoutError(Duration),
+
+    #[error("OIDC discovery error: {0}")]
+    DiscoveryError(reqwest::Error),
+
+    #[error("Token cache key error: {0}")]
+    TokenKeyError(String),
+
+    #[error("Token cache could not be decrypted. The key is wrong or the file was tampered with")]
+    TokenDecryptionError,
+
+    #[error("HTTP request error: {0}")]
+    HttpError(reqwest::Error),
+
+    #[error("Missing client secret. Check KK_CLIENT_SECRET in your .env file")]
+    NoClientSecretError,
+
+    #[error("The token is not active")]
+    InactiveTokenError,
+
+    #[error("Access denied: {0}")]
+    AccessDeniedError(#[from] Denial),
+
+    /// Keycloak rejected the refresh token; the error tells whether the token or its whole
+    /// session expired
+    #[error("The refresh token has expired. Please authenticate again. ({0})")]
+    RefreshTokenExpiredError(KeycloakError),
+
+    #[error("Invalid URL: {0}")]
+    InvalidUrlError(String),
+
+    #[error("Missing configuration: {0} is not set")]
+    MissingConfigError(&'static str),
+
+    #[error("The client id {app_config:?} does not match the credential's {credential:?}")]
+    ClientIdMismatchError {
+        app_config: String,
+        credential: String,
+    },
+
+    #[error("Could not reach Keycloak: {0}")]
+    TransportError(String),
+
+    #[error("Admin API error ({status}): {message}")]
+    AdminApiError { status: u16, message: String },
+
+    #[error("Invalid realm representation: {0}")]
+    InvalidRealmRepresentationError(String),
+
+    #[cfg(feature = "secret-service")]
+    #[error("No Secret Service provider is running on the session bus")]
+    SecretServiceUnavailable,
+
+    #[cfg(feature = "secret-service")]
+    #[error("Secret Service error: {0}")]
+    SecretServiceError(String),
+}
+
+impl ClientError {
+    /// What the caller can do about the error, `None` when it does not fit any category
+    /// (e.g. a corrupt token cache).
+    pub fn category(&self) -> Option<ErrorCategory> {
+        match self {
+            ClientError::KeycloakError(e) => Some(e.category()),
+            ClientError::TransportError(_) => Some(ErrorCategory::Retryable),
+            ClientError::InvalidResponseError { status, .. } => status.map(status_category),
+            ClientError::HttpError(e) | ClientError::DiscoveryError(e) => {
+                e.status().map(|status| status_category(status.as_u16()))
+            }
+            ClientError::AdminApiError { status, .. } => Some(status_category(*status)),
+            ClientError::NoValidTokenError
+            | ClientError::RefreshTokenExpiredError(_)
+            | ClientError::InactiveTokenError
+            | ClientError::AuthorizationTimeoutError(_) => {
+                Some(ErrorCategory::ReauthenticationRequired)
+            }
+            ClientError::AccessDeniedError(_) => Some(ErrorCategory::Forbidden),
+            ClientError::MissingConfigError(_)
+            | ClientError::ClientIdMismatchError { .. }
+            | ClientError::InvalidUrlError(_)
+            | ClientError::NoClientSecretError
+            | ClientError::NoPresentCredentialsError => Some(ErrorCategory::Misconfiguration),
+            _ => None,
+        }
+    }
+
+    pub fn is_retryable(&self) -> bool {
+        self.category() == Some(ErrorCategory::Retryable)
+    }
+
+    /// `true` when only signing in again helps.
+    pub fn requires_reauthentication(&self) -> bool {
+        self.category() == Some(ErrorCategory::ReauthenticationRequired)
+    }
+}
+
+impl From<reqwest::Error> for ClientError {
+    fn from(error: reqwest::Error) -> Self {
+        if error.is_connect() || error.is_timeout() || error.is_request() {
+            ClientError::TransportError(error.to_string())
+        } else {
+            ClientError::HttpError(error)
+        }
+    }
+}
+
+/// Passes successful responses through and turns the others into `ClientError::KeycloakError`,
+/// or `ClientError::InvalidResponseError` when the body is not an OAuth error.
+async fn check_response(response: reqwest::Response) -> Result<reqwest::Response, ClientError> {
+    let status = response.status();
+    if status.is_success() {
+        return Ok(response);
+    }
+    let body = response.bytes().await?;
+    Err(match KeycloakError::from_body(status.as_u16(), &body) {
+        Some(error) => ClientError::KeycloakError(error),
+        None => ClientError::InvalidResponseError {
+            status: Some(status.as_u16()),
+            message: String::from_utf8_lossy(&body).into_owned(),
+        },
+    })
+}
+
+fn invalid_url(url: &str, error: impl std::fmt::Display) -> ClientError {
+    ClientError::InvalidUrlError(format!("{}: {}", url, error))
+}
+
+/// A file store at `token_cache_path` when one is configured, an in-memory store otherwise.
+fn default_token_store(config: &ClientConfiguration) -> Arc<dyn TokenStore> {
+    match &config.token_cache_path {
+        Some(path) => Arc::new(FileTokenStore::new(path)),
+        None => Arc::new(MemoryTokenStore::new()),
+    }
 }
 
 type MyStandardTokenResponse = oauth2::StandardTokenResponse<EmptyExtraTokenFields, BasicTokenType>;
@@ -57,72 +207,146 @@ pub struct KeycloakClient<C> {
     pub inner: BasicClient,
     pub config: ClientConfiguration,
     pub cache: SharedKeyCache,
+    pub store: Arc<dyn TokenStore>,
+    /// Held while refreshing, so concurrent callers share a single refresh
+    refresh_lock: Arc<Mutex<()>>,
     pub _marker: PhantomData<C>,
 }
-impl From<AppConfig<DeviceCodeCredential>> for KeycloakClient<WithDeviceCredentials> {
-    fn from(value: AppConfig<DeviceCodeCredential>) -> Self {
-        let config = ClientConfiguration::from_env();
+impl<C> KeycloakClient<C> {
+    /// Builds a client from `app_config`, failing when an endpoint or secret is missing or
+    /// malformed. Same as `KeycloakClient::try_from`.
+    pub fn try_new<Cr: Credential>(app_config: AppConfig<Cr>) -> Result<Self, ClientError>
+    where
+        Self: TryFrom<AppConfig<Cr>, Error = ClientError>,
+    {
+        Self::try_from(app_config)
+    }
 
-        let token_url = if value.token_url.is_some() {
-            value.token_url.clone().expect("token")
-        } else {
-            config
-                .token_url
-                .clone()
-                .expect("No token_url in the config. Add token_url")
-        };
+    /// The oauth2 client every flow is built on. The client id and token URL set on the
+    /// `AppConfig` take precedence over the configured ones.
+    fn build(
+        client_id: String,
+        client_secret: Option<String>,
+        auth_url: String,
+        token_url: Option<String>,
+        mut config: ClientConfiguration,
+    ) -> Result<Self, ClientError> {
+        // token verification, introspection and revocation name the client the flows use
+        config.client_id = Some(client_id.clone());
+        let token_url = token_url
+            .or_else(|| config.token_url.clone())
+            .ok_or(ClientError::MissingConfigError("token_url"))?;
 
         let inner = BasicClient::new(
-            ClientId::new(value.client_id.clone()),
-            Some(ClientSecret::new(
-                config
-                    .client_secret
-                    .clone()
-                    .expect("should have client secret"),
-            )),
-            AuthUrl::new(value.auth_url.clone()).expect("Invalid auth endpoint"),
-            Some(TokenUrl::new(token_url).expect("Invalid token")),
+            ClientId::new(client_id),
+            client_secret.map(ClientSecret::new),
+            AuthUrl::new(auth_url.clone()).map_err(|e| invalid_url(&auth_url, e))?,
+            Some(TokenUrl::new(token_url.clone()).map_err(|e| invalid_url(&token_url, e))?),
         );
         let cache = Arc::new(Mutex::new(KeyCache::new()));
+        let store = default_token_store(&config);
 
-        KeycloakClient {
+        Ok(KeycloakClient {
             inner,
             cache,
+            store,
             config,
+            refresh_lock: Arc::new(Mutex::new(())),
             _marker: PhantomData,
-        }
+        })
     }
 }
-impl From<AppConfig<ResourceOwnerPasswordCredential>> for KeycloakClient<WithOwnerCredentials> {
-    fn from(value: AppConfig<ResourceOwnerPasswordCredential>) -> Self {
-        let config = ClientConfiguration::from_env();
 
-        let token_url = if value.token_url.is_some() {
-            value.token_url.clone().expect("token")
-        } else {
-            config
-                .token_url
-                .clone()
-                .expect("No token_url in the config. Add token_url")
-        };
+impl TryFrom<AppConfig<DeviceCodeCredential>> for KeycloakClient<WithDeviceCredentials> {
+    type Error = ClientError;
 
-        let inner = BasicClient::new(
-            ClientId::new(value.client_id.clone()),
+    fn try_from(value: AppConfig<DeviceCodeCredential>) -> Result<Self, Self::Error> {
+        let config = value
+            .configuration
+            .unwrap_or_else(ClientConfiguration::from_env);
+        // public clients have no secret, confidential ones send it with every request
+        let client_secret = config.client_secret.clone();
+
+        Self::build(
+            value.client_id,
+            client_secret,
+            value.auth_url,
+            value.token_url,
+            config,
+        )
+    }
+}
+impl TryFrom<AppConfig<ResourceOwnerPasswordCredential>> for KeycloakClient<WithOwnerCredentials> {
+    type Error = ClientError;
+
+    fn try_from(value: AppConfig<ResourceOwnerPasswordCredential>) -> Result<Self, Self::Error> {
+        let config = value
+            .configuration
+            .unwrap_or_else(ClientConfiguration::from_env);
+
+        Self::build(
+            value.client_id,
             None,
-            AuthUrl::new(value.auth_url.clone()).expect("Invalid auth endpoint"),
-            Some(TokenUrl::new(token_url).expect("Invalid token")),
-        );
-        let cache = Arc::new(Mutex::new(KeyCache::new()));
+            value.auth_url,
+            value.token_url,
+            config,
+        )
+    }
+}
+impl TryFrom<AppConfig<AuthorizationCodeCredential>> for KeycloakClient<WithAuthorizationCode> {
+    type Error = ClientError;
 
-        KeycloakClient {
-            inner,
-            cache,
+    fn try_from(value: AppConfig<AuthorizationCodeCredential>) -> Result<Self, Self::Error> {
+        let mut config = value
+            .configuration
+            .unwrap_or_else(ClientConfiguration::from_env);
+        if value.credential.redirect_port.is_some() {
+            config.redirect_port = value.credential.redirect_port;
+        }
+
+        // public clients have no secret, confidential ones send it on the code exchange
+        let client_secret = config.client_secret.clone();
+        Self::build(
+            value.client_id,
+            client_secret,
+            value.auth_url,
+            value.token_url,
             config,
-            _marker: PhantomData,
+        )
+    }
+}
+impl TryFrom<AppConfig<ClientCredentials>> for KeycloakClient<WithClientCredentials> {
+    type Error = ClientError;
+
+    fn try_from(value: AppConfig<ClientCredentials>) -> Result<Self, Self::Error> {
+        if value.client_id != value.credential.client_id {
+            return Err(ClientError::ClientIdMismatchError {
+                app_config: value.client_id,
+                credential: value.credential.client_id,
+            });
         }
+        // the credential's secret is used for introspection and revocation rather than the
+        // environment's
+        let mut config = value
+            .configuration
+            .unwrap_or_else(ClientConfiguration::from_env);
+        config.client_secret = Some(value.credential.client_secret.clone());
+
+        Self::build(
+            value.client_id,
+            Some(value.credential.client_secret),
+            value.auth_url,
+            value.token_url,
+            config,
+        )
     }
 }
 impl KeycloakClient<WithDeviceCredentials> {
+    /// Returns the cached access token, refreshing it once it has expired.
+    pub async fn verify_and_refresh_access_token(&self) -> Result<String, ClientError> {
+        self.refresh_expired_token().await
+    }
+
     pub async fn initiate_device_flow(
         &self,
     ) -> Result<DeviceAuthorizationResponse<EmptyExtraDeviceAuthorizationFields>, ClientError> {
@@ -133,30 +357,27 @@ impl KeycloakClient<WithDeviceCredentials> {
             .map(|s| Scope::new(s.clone()))
             .collect::<Vec<_>>();
 
+        let device_authorization_url = self
+            .config
+            .device_authorization_url
+            .clone()
+            .ok_or(ClientError::MissingConfigError("device_authorization_url"))?;
+        let device_authorization_url =
+            DeviceAuthorizationUrl::new(device_authorization_url.clone())
+                .map_err(|e| invalid_url(&device_authorization_url, e))?;
+
+        let http = StatusRecorder::default();
         let device_auth_request = self
             .inner
             .clone()
-            .set_device_authorization_url(
-                DeviceAuthorizationUrl::new(
-                    self.config
-                        .device_authorization_url
-                        .clone()
-                        .expect("Device authorization url cannot be found. Check .env!"),
-                )
-                .expect("Invalid dev endpoint"),
-            )
+            .set_device_authorization_url(device_authorization_url)
             .exchange_device_code()
-            .expect("works?")
+            .map_err(|_| ClientError::MissingConfigError("device_authorization_url"))?
             .add_scopes(scopes)
-            .request_async(async_http_client)
+            .request_async(|request| http.send(request))
             .await
-            .expect("device auth");
+            .map_err(|e| http.error(e))?;
 
-        println!(
-            "Open this url {} \nand enter the code: {}",
-            **device_auth_request.verification_uri(),
-            device_auth_request.user_code().secret()
-        );
         Ok(device_auth_request)
     }
     pub async fn poll_for_token(
@@ -166,11 +387,12 @@ impl KeycloakClient<WithDeviceCredentials> {
         >,
     ) -> Result<MyStandardTokenResponse, ClientError> {
         let mut attempts = 0;
-        let max_attempts = (device_auth_response.expires_in().as_secs()
-            / device_auth_response.interval().as_secs()) as usize;
+        // a server may send an interval of 0, poll at most once a second
+        let mut interval = device_auth_response.interval().as_secs().max(1);
+        let max_attempts = (device_auth_response.expires_in().as_secs() / interval) as usize;
 
-        let mut interval = device_auth_response.interval().as_secs();
-        loop {
+        let http = StatusRecorder::default();
+        while attempts < max_attempts {
             tokio::time::sleep(tokio::time::Duration::from_secs(interval)).await;
             attempts += 1;
 
@@ -178,47 +400,60 @@ impl KeycloakClient<WithDeviceCredentials> {
                 .inner
                 .exchange_device_access_token(device_auth_response)
                 .request_async(
-                    async_http_client,
+                    |request| http.send(request),
                     tokio::time::sleep,
                     Some(tokio::time::Duration::from_secs(10)),
                 )
                 .await
             {
                 Ok(token) => {
-                    self.cache_token(&token)?;
+                    self.cache_token(&token).await?;
                     return Ok(token);
                 }
-                Err(err) => match err {
-                    RequestTokenError::ServerResponse(e) => {
-                        let poll_event = PollDeviceCodeEvent::from(e);
-                        poll_event.as_message();
-                        match poll_event {
-                            PollDeviceCodeEvent::AuthorizationPending => continue,
-                            PollDeviceCodeEvent::AuthorizationDeclined => break,
-                            PollDeviceCodeEvent::BadVerificationCode => continue,
-                            PollDeviceCodeEvent::ExpiredToken => break,
-                            PollDeviceCodeEvent::AccessDenied => break,
-                            PollDeviceCodeEvent::SlowDown => {
-                                interval += 5_u64;
-                                continue;
-                            }
+                Err(RequestTokenError::ServerResponse(e)) => {
+                    // the loop stays silent, callers may own stdout (e.g. kubectl plugins)
+                    match PollDeviceCodeEvent::from(e.clone()) {
+                        PollDeviceCodeEvent::AuthorizationPending => continue,
+                        PollDeviceCodeEvent::BadVerificationCode => continue,
+                        PollDeviceCodeEvent::SlowDown => {
+                            interval += 5_u64;
+                            continue;
+                        }
+                        PollDeviceCodeEvent::AuthorizationDeclined
+                        | PollDeviceCodeEvent::ExpiredToken
+                        | PollDeviceCodeEvent::AccessDenied => {
+                            return Err(http.error(RequestTokenError::ServerResponse(e)))
                         }
                     }
-                    _ => PollDeviceCodeEvent::AccessDenied.as_message(),
-                },
-            }
-            if attempts >= max_attempts {
-                eprintln!("Maximum polling attempts reached. Exiting.");
-                break;
+                }
+                Err(e) => return Err(http.error(e)),
             }
         }
-
-        Err(ClientError::OAuth2RequestTokenError(
-            oauth2::RequestTokenError::Other("Polling timeout".into()),
-        ))
+        Err(ClientError::KeycloakError(KeycloakError::new(
+            "expired_token",
+            Some("Polling timeout".into()),
+        )))
     }
+    /// Runs the device flow, telling the user on stderr where to enter the code.
     pub async fn authenticate(&self) -> Result<MyStandardTokenResponse, ClientError> {
+        self.authenticate_with_prompt(|device_auth_response| {
+            eprintln!(
+                "Open this url {} \nand enter the code: {}",
+                **device_auth_response.verification_uri(),
+                device_auth_response.user_code().secret()
+            )
+        })
+        .await
+    }
+
+    /// Runs the device flow, handing the verification URL and user code to `prompt` before
+    /// polling for the token, e.g. to show them in a UI.
+    pub async fn authenticate_with_prompt(
+        &self,
+        prompt: impl FnOnce(&DeviceAuthorizationResponse<EmptyExtraDeviceAuthorizationFields>) + Send,
+    ) -> Result<MyStandardTokenResponse, ClientError> {
         let device_auth_response = self.initiate_device_flow().await?;
+        prompt(&device_auth_response);
 
         let token = self.poll_for_token(&device_auth_response).await?;
         Ok(token)
@@ -226,32 +461,37 @@ impl KeycloakClient<WithDeviceCredentials> {
 }
 
 impl KeycloakClient<WithOwnerCredentials> {
+    /// Returns the cached access token, refreshing it once it has expired.
+    pub async fn verify_and_refresh_access_token(&self) -> Result<String, ClientError> {
+        self.refresh_expired_token().await
+    }
+
     pub async fn initiate_password_flow(
         &self,
     ) -> Result<StandardTokenResponse<EmptyExtraTokenFields, BasicTokenType>, ClientError> {
-        if self.config.username.is_none() || self.config.password.is_none() {
+        let (Some(username), Some(password)) = (&self.config.username, &self.config.password)
+        else {
             return Err(ClientError::NoPresentCredentialsError);
         };
 
-        let username =
-            ResourceOwnerUsername::new(self.config.username.clone().expect("Should have username"));
-        let password =
-            ResourceOwnerPassword::new(self.config.password.clone().expect("Should have password"));
+        let username = ResourceOwnerUsername::new(username.clone());
+        let password = ResourceOwnerPassword::new(password.clone());
         let scopes = self
             .config
             .scopes
             .iter()
             .map(|s| Scope::new(s.clone()))
             .collect::<Vec<_>>();
+        let http = StatusRecorder::default();
         let owner_credentials = self
             .inner
             .exchange_password(&username, &password)
             .add_scopes(scopes)
-            .request_async(async_http_client)
+            .request_async(|request| http.send(request))
             .await
-            .expect("password grant");
+            .map_err(|e| http.error(e))?;
 
-        self.cache_token(&owner_credentials)?;
+        self.cache_token(&owner_credentials).await?;
 
         Ok(owner_credentials)
     }
@@ -262,8 +502,240 @@ impl KeycloakClient<WithOwnerCredentials> {
     }
 }
 
+impl KeycloakClient<WithAuthorizationCode> {
+    /// Returns the cached access token, refreshing it once it has expired.
+    pub async fn verify_and_refresh_access_token(&self) -> Result<String, ClientError> {
+        self.refresh_expired_token().await
+    }
+
+    /// Runs the Authorization Code flow with PKCE.
+    ///
+    /// A listener is bound on `127.0.0.1` (on `redirect_port` or an ephemeral port) and used as
+    /// the redirect uri. The authorization url is handed to `prompt`, which should have the user
+    /// open it in a browser, and the code received on the callback is exchanged for a token.
+    ///
+    /// Callbacks that do not carry the state of the authorization url are answered with an error
+    /// page and ignored. The wait ends with `AuthorizationTimeoutError` after
+    /// `authorization_timeout`, and dropping the future cancels it.
+    pub async fn initiate_authorization_code_flow(
+        &self,
+        prompt: impl FnOnce(&oauth2::url::Url) + Send,
+    ) -> Result<MyStandardTokenResponse, ClientError> {
+        let listener =
+            TcpListener::bind(("127.0.0.1", self.config.redirect_port.unwrap_or(0))).await?;
+        let port = listener.local_addr()?.port();
+        let redirect_url = format!("http://127.0.0.1:{}/callback", port);
+        let redirect_url =
+            RedirectUrl::new(redirect_url.clone()).map_err(|e| invalid_url(&redirect_url, e))?;
+
+        let scopes = self
+            .config
+            .scopes
+            .iter()
+            .map(|s| Scope::new(s.clone()))
+            .collect::<Vec<_>>();
+
+        let (pkce_challenge, pkce_verifier) = PkceCodeChallenge::new_random_sha256();
+        let client = self.inner.clone().set_redirect_uri(redirect_url);
+        let (authorize_url, csrf_state) = client
+            .authorize_url(CsrfToken::new_random)
+            .add_scopes(scopes)
+            .set_pkce_challenge(pkce_challenge)
+            .url();
+
+        prompt(&authorize_url);
+
+        let timeout = self
+            .config
+            .authorization_timeout
+            .unwrap_or(DEFAULT_AUTHORIZATION_TIMEOUT);
+        let code =
+            tokio::time::timeout(timeout, wait_for_authorization_code(&listener, &csrf_state))
+                .await
+                .map_err(|_| ClientError::AuthorizationTimeoutError(timeout))??;
+
+        let http = StatusRecorder::default();
+        let token = client
+            .exchange_code(code)
+            .set_pkce_verifier(pkce_verifier)
+            .request_async(|request| http.send(request))
+            .await
+            .map_err(|e| http.error(e))?;
+
+        self.cache_token(&token).await?;
+
+        Ok(token)
+    }
+
+    /// Runs the flow, telling the user on stderr which url to open.
+    pub async fn authenticate(&self) -> Result<MyStandardTokenResponse, ClientError> {
+        self.authenticate_with_prompt(|authorize_url| {
+            eprintln!(
+                "Open this url in your browser to log in:\n{}",
+                authorize_url
+            )
+        })
+        .await
+    }
+
+    /// Runs the flow, handing the authorization url to `prompt`, e.g. to open a browser.
+    pub async fn authenticate_with_prompt(
+        &self,
+        prompt: impl FnOnce(&oauth2::url::Url) + Send,
+    ) -> Result<MyStandardTokenResponse, ClientError> {
+        self.initiate_authorization_code_flow(prompt).await
+    }
+}
+
+impl KeycloakClient<WithClientCredentials> {
+    /// Returns the cached access token. Once it has expired it is refreshed when Keycloak
+    /// issued a refresh token along with it, and replaced by performing the grant again
+    /// otherwise.
+    pub async fn verify_and_refresh_access_token(&self) -> Result<String, ClientError> {
+        match self.store.load().await? {
+            Some(cached) if cached.refresh_token.is_some() => self.refresh_expired_token().await,
+            _ => self.access_token().await,
+        }
+    }
+
+    pub async fn initiate_client_credentials_flow(
+        &self,
+    ) -> Result<MyStandardTokenResponse, ClientError> {
+        let scopes = self
+            .config
+            .scopes
+            .iter()
+            .map(|s| Scope::new(s.clone()))
+            .collect::<Vec<_>>();
+
+        let http = StatusRecorder::default();
+        let token = self
+            .inner
+            .exchange_client_credentials()
+            .add_scopes(scopes)
+            .request_async(|request| http.send(request))
+            .await
+            .map_err(|e| http.error(e))?;
+
+        self.cache_token(&token).await?;
+
+        Ok(token)
+    }
+    pub async fn authenticate(&self) -> Result<MyStandardTokenResponse, ClientError> {
+        let token = self.initiate_client_credentials_flow().await?;
+
+        Ok(token)
+    }
+
+    /// Returns the cached access token, performing the grant again once it has expired.
+    ///
+    /// Keycloak does not issue refresh tokens for the `client_credentials` grant, so an expired
+    /// token is replaced by a new one instead of being refreshed.
+    pub async fn access_token(&self) -> Result<String, ClientError> {
+        self.renew_if_needed(chrono::Duration::zero(), None).await
+    }
+
+    /// Like `refresh_if_needed`, performing the grant again instead of refreshing. Callers
+    /// racing each other share a single grant.
+    pub async fn renew_if_needed(
+        &self,
+        min_validity: chrono::Duration,
+        rejected: Option<&str>,
+    ) -> Result<String, ClientError> {
+        let cached_token = self.store.load().await?;
+        if let Some(token) = usable_token(cached_token.as_ref(), min_validity, rejected) {
+            return Ok(token);
+        }
+
+        let _renewing = self.refresh_lock.lock().await;
+        let current = self.store.load().await?;
+        // another caller got a new token while this one waited
+        let replaced = current.as_ref().map(|t| &t.access_token)
+            != cached_token.as_ref().map(|t| &t.access_token);
+        if replaced {
+            if let Some(token) = usable_token(current.as_ref(), min_validity, None) {
+                return Ok(token);
+            }
+        }
+        Ok(self.authenticate().await?.access_token().secret().clone())
+    }
+}
+
+/// Accepts connections on the loopback listener until a `/callback` redirect carrying `expected`
+/// as its state arrives and returns its code.
+async fn wait_for_authorization_code(
+    listener: &TcpListener,
+    expected: &CsrfToken,
+) -> Result<AuthorizationCode, ClientError> {
+    loop {
+        let (mut stream, _) = listener.accept().await?;
+        let mut reader = BufReader::new(&mut stream);
+        let mut request_line = String::new();
+        reader.read_line(&mut request_line).await?;
+
+        // request line looks like `GET /callback?code=...&state=... HTTP/1.1`
+        let path = request_line.split_whitespace().nth(1).unwrap_or("/");
+        let url = oauth2::url::Url::parse(&format!("http://127.0.0.1{}", path))
+            .map_err(|e| ClientError::AuthorizationCallbackError(e.to_string()))?;
+
+        if url.path() != "/callback" {
+            stream
+                .write_all(b"HTTP/1.1 404 Not Found\r\nContent-Length: 0\r\n\r\n")
+                .await?;
+            continue;
+        }
+
+        let mut code = None;
+        let mut state = None;
+        let mut error = None;
+        for (key, value) in url.query_pairs() {
+            match key.as_ref() {
+                "code" => code = Some(AuthorizationCode::new(value.into_owned())),
+                "state" => state = Some(CsrfToken::new(value.into_owned())),
+                "error" => error = Some(value.into_owned()),
+                _ => {}
+            }
+        }
+
+        if state.as_ref().map(CsrfToken::secret) != Some(expected.secret()) {
+            respond(&mut stream, "400 Bad Request", "Unexpected login state.").await?;
+            continue;
+        }
+
+        let body = if error.is_some() {
+            "Login failed. You can close this window."
+        } else {
+            "Login complete. You can close this window."
+        };
+        respond(&mut stream, "200 OK", body).await?;
+
+        if let Some(error) = error {
+            return Err(ClientError::AuthorizationCallbackError(error));
+        }
+        return code.ok_or_else(|| {
+            ClientError::AuthorizationCallbackError("Missing code in the redirect".into())
+        });
+    }
+}
+
+async fn respond(stream: &mut TcpStream, status: &str, body: &str) -> std::io::Result<()> {
+    let response = format!(
+        "HTTP/1.1 {}\r\nContent-Type: text/plain\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}",
+        status,
+        body.len(),
+        body
+    );
+    stream.write_all(response.as_bytes()).await
+}
+
 impl<C> KeycloakClient<C> {
-    pub fn cache_token(
+    /// Replaces the token store chosen from the configuration.
+    pub fn with_token_store(mut self, store: impl TokenStore + 'static) -> Self {
+        self.store = Arc::new(store);
+        self
+    }
+
+    pub async fn cache_token(
         &self,
         token: &oauth2::StandardTokenResponse<EmptyExtraTokenFields, BasicTokenType>,
     ) -> Result<(), ClientError> {
@@ -278,110 +750,328 @@ impl<C> KeycloakClient<C> {
             refresh_token: token.refresh_token().map(|rt| rt.secret().clone()),
         };
 
-        let serialized = serde_json::to_string_pretty(&cached_token)?;
-        std::fs::write(
-            &self
-                .config
-                .token_cache_path
-                .clone()
-                .expect("Could not find the cache path in .env"),
-            serialized,
-        )?;
-        Ok(())
+        self.store.save(&cached_token).await
     }
-    pub fn load_cached_token(&self) -> Result<CachedToken, ClientError> {
-        if !Path::exists(Path::new(
-            &self
-                .config
-                .token_cache_path
-                .clone()
-                .expect("Could not find the cache path in .env"),
-        )) {
-            return Err(ClientError::IoError(std::io::Error::new(
+    pub async fn load_cached_token(&self) -> Result<CachedToken, ClientError> {
+        match self.store.load().await? {
+            Some(cached_token) => Ok(cached_token),
+            None => Err(ClientError::IoError(std::io::Error::new(
                 std::io::ErrorKind::NotFound,
                 "token cache not found",
-            )));
+            ))),
         }
-
-        let data = fs::read_to_string(Path::new(
-            &self
-                .config
-                .token_cache_path
-                .clone()
-                .expect("Could not find the cache path in .env"),
-        ))?;
-        let cached_token: CachedToken = serde_json::from_str(&data)?;
-        Ok(cached_token)
+    }
+    pub async fn clear_cached_token(&self) -> Result<(), ClientError> {
+        self.store.clear().await
     }
 
     /// Verifies the passed access token
-    pub async fn verify_access_token(&self, token: &str) -> Result<TokenData<Claims>, ClientError> {
+    pub async fn verify_access_token(
+        &self,
+        token: &str,
+    ) -> Result<TokenData<KeycloakClaims>, ClientError> {
+        self.verify_access_token_as(token).await
+    }
+
+    /// Verifies the passed access token and decodes its claims into `T`.
+    pub async fn verify_access_token_as<T: DeserializeOwned>(
+        &self,
+        token: &str,
+    ) -> Result<TokenData<T>, ClientError> {
+        let token_data = self
+            .token_verifier()?
+            .verify::<T>(token)
+            .await
+            .map_err(ClientError::JwtVerificationError)?;
+
+        if self.config.validation_policy == ValidationPolicy::Online
+            && !self.introspect(token).await?.active
+        {
+            return Err(ClientError::InactiveTokenError);
+        }
+
+        Ok(token_data)
+    }
+
+    /// A verifier sharing this client's JWKS cache, for validating tokens outside the client
+    /// (e.g. in HTTP middleware).
+    pub fn token_verifier(&self) -> Result<TokenVerifier, ClientError> {
         let jwks_url = self
             .config
             .jwks_url
             .clone()
-            .expect("Could not find jwks url, check .env");
+            .ok_or(ClientError::MissingConfigError("jwks_url"))?;
         let client_id = self
             .config
             .client_id
             .clone()
-            .expect("Cannot find client_id, token verification failed. Check .env");
+            .ok_or(ClientError::MissingConfigError("client_id"))?;
+        // a discovered issuer takes precedence over the hand-configured realm
         let realm = self
             .config
-            .realm
+            .issuer
             .clone()
-            .expect("Cannot find realm, token verification failed. Check .env");
-        verify_jwt(
-            token,
-            &jwks_url,
-            self.cache.clone(),
-            &[&client_id],
-            &[&realm],
-        )
-        .await
-        .map_err(ClientError::JwtVerificationError)
+            .or_else(|| self.config.realm.clone())
+            .ok_or(ClientError::MissingConfigError("realm"))?;
+
+        let mut verifier = TokenVerifier::new(jwks_url, self.cache.clone())
+            .audience([client_id])
+            .issuer([realm]);
+        if !self.config.allowed_algorithms.is_empty() {
+            verifier = verifier.allowed_algorithms(&self.config.allowed_algorithms);
+        }
+        Ok(verifier)
     }
 
-    pub async fn verify_and_refresh_access_token(&self) -> Result<String, ClientError> {
-        match self.load_cached_token() {
+    /// Restricts the signing algorithms `verify_access_token` accepts.
+    pub fn with_allowed_algorithms(mut self, algorithms: &[Algorithm]) -> Self {
+        self.config.allowed_algorithms = algorithms.to_vec();
+        self
+    }
+
+    /// Sets how long the authorization code flow waits for the browser's redirect.
+    pub fn with_authorization_timeout(mut self, timeout: Duration) -> Self {
+        self.config.authorization_timeout = Some(timeout);
+        self
+    }
+
+    /// Sets how `verify_access_token` validates tokens.
+    pub fn with_validation_policy(mut self, policy: ValidationPolicy) -> Self {
+        self.config.validation_policy = policy;
+        self
+    }
+
+    /// Asks Keycloak whether `token` is still active (RFC 7662).
+    ///
+    /// The request is authenticated with the client id and secret, so this only works for
+    /// confidential clients.
+    pub async fn introspect(&self, token: &str) -> Result<IntrospectionResponse, ClientError> {
+        let client_secret = self
+            .config
+            .client_secret
+            .clone()
+            .ok_or(ClientError::NoClientSecretError)?;
+        let introspection_url = self
+            .config
+            .introspection_url
+            .clone()
+            .or_else(|| self.openid_connect_url("token/introspect"))
+            .ok_or(ClientError::MissingConfigError("introspection_url"))?;
+
+        let response = reqwest::Client::new()
+            .post(introspection_url)
+            .basic_auth(self.inner.client_id().as_str(), Some(client_secret))
+            .form(&[("token", token), ("token_type_hint", "access_token")])
+            .send()
+            .await?;
+        let response = check_response(response)
+            .await?
+            .json::<IntrospectionResponse>()
+            .await?;
+
+        Ok(response)
+    }
+
+    /// Revokes `token` (RFC 7009). `token_type_hint` is `"access_token"` or `"refresh_token"`.
+    pub async fn revoke_token(
+        &self,
+        token: &str,
+        token_type_hint: Option<&str>,
+    ) -> Result<(), ClientError> {
+        let revocation_url = self
+            .config
+            .revocation_url
+            .clone()
+            .or_else(|| self.openid_connect_url("revoke"))
+            .ok_or(ClientError::MissingConfigError("revocation_url"))?;
+
+        let mut form = vec![("token", token)];
+        if let Some(hint) = token_type_hint {
+            form.push(("token_type_hint", hint));
+        }
+        let response = self
+            .client_authenticated_post(revocation_url, form)
+            .send()
+            .await?;
+        check_response(response).await?;
+        Ok(())
+    }
+
+    /// Ends the user's session in Keycloak with the cached refresh token and clears the token
+    /// cache. Without a cached token only the cache is cleared.
+    pub async fn logout(&self) -> Result<(), ClientError> {
+        let cached_token = match self.load_cached_token().await {
+            Ok(cached_token) => Some(cached_token),
+            Err(ClientError::IoError(e)) if e.kind() == std::io::ErrorKind::NotFound => None,
+            Err(e) => return Err(e),
+        };
+
+        if let Some(refresh_token) = cached_token.and_then(|t| t.refresh_token) {
+            self.end_session(&[("refresh_token", refresh_token.as_str())])
+                .await?;
+        }
+
+        self.clear_cached_token().await
+    }
+
+    /// Ends the session identified by an ID token and clears the token cache.
+    pub async fn logout_with_id_token_hint(&self, id_token: &str) -> Result<(), ClientError> {
+        self.end_session(&[("id_token_hint", id_token)]).await?;
+        self.clear_cached_token().await
+    }
+
+    async fn end_session(&self, params: &[(&str, &str)]) -> Result<(), ClientError> {
+        let end_session_url = self
+            .config
+            .end_session_url
+            .clone()
+            .or_else(|| self.openid_connect_url("logout"))
+            .ok_or(ClientError::MissingConfigError("end_session_url"))?;
+
+        let response = self
+            .client_authenticated_post(end_session_url, params.to_vec())
+            .send()
+            .await?;
+        check_response(response).await?;
+        Ok(())
+    }
+
+    /// Builds a form POST authenticated as the client: with basic auth when a secret is
+    /// configured, with `client_id` in the form for public clients.
+    fn client_authenticated_post<'a>(
+        &'a self,
+        url: String,
+        mut form: Vec<(&'a str, &'a str)>,
+    ) -> reqwest::RequestBuilder {
+        let request = reqwest::Client::new().post(url);
+        match &self.config.client_secret {
+            Some(secret) => request
+                .basic_auth(self.inner.client_id().as_str(), Some(secret))
+                .form(&form),
+            None => {
+                form.push(("client_id", self.inner.client_id().as_str()));
+                request.form(&form)
+            }
+        }
+    }
+
+    /// Keycloak serves every OIDC endpoint next to the token endpoint
+    /// (`.../protocol/openid-connect/token`), so undiscovered endpoints are derived from it.
+    fn openid_connect_url(&self, endpoint: &str) -> Option<String> {
+        self.inner.token_url().map(|url| {
+            let base = url.as_str().trim_end_matches('/');
+            let base = base.strip_suffix("/token").unwrap_or(base);
+            format!("{}/{}", base, endpoint)
+        })
+    }
+
+    /// The cached access token, refreshed once it has expired.
+    async fn refresh_expired_token(&self) -> Result<String, ClientError> {
+        match self.load_cached_token().await {
             Ok(cached_token) => {
                 if cached_token.expires_at <= chrono::Utc::now() {
                     //token is expired
-                    if let Some(refresh_token_str) = cached_token.refresh_token {
-                        let new_token = match self
-                            .inner
-                            .exchange_refresh_token(&RefreshToken::new(refresh_token_str))
-                            .request_async(async_http_client)
-                            .await
-                        {
-                            Ok(token) => token,
-                            Err(_e) => return Err(ClientError::NoValidTokenError),
-                        };
-                        self.cache_token(&new_token)?;
-                        Ok(new_token.access_token().secret().clone())
-                    } else {
-                        Err(ClientError::NoValidTokenError)
-                    }
+                    self.refresh_cached_token(cached_token).await
                 } else {
                     //token is still valid
                     Ok(cached_token.access_token)
                 }
             }
-            Err(e) => match &e {
-                ClientError::IoError(error) => {
-                    if error.kind() == std::io::ErrorKind::NotFound {
-                        Err(ClientError::NoValidTokenError)
-                    } else {
-                        Err(e)
-                    }
+            Err(e) => Err(not_found_as_no_valid_token(e)),
+        }
+    }
+
+    /// Returns the cached access token unless it expires within `min_validity` or is `rejected`
+    /// (e.g. after a server answered `401`), in which case it is refreshed. Callers racing each
+    /// other share a single refresh.
+    pub async fn refresh_if_needed(
+        &self,
+        min_validity: chrono::Duration,
+        rejected: Option<&str>,
+    ) -> Result<String, ClientError> {
+        let cached_token = self
+            .load_cached_token()
+            .await
+            .map_err(not_found_as_no_valid_token)?;
+        match usable_token(Some(&cached_token), min_validity, rejected) {
+            Some(token) => Ok(token),
+            None => self.refresh_cached_token(cached_token).await,
+        }
+    }
+
+    /// Exchanges the cached refresh token for a new access token, even if the cached access
+    /// token has not expired yet. Callers racing each other share a single refresh.
+    pub async fn refresh_access_token(&self) -> Result<String, ClientError> {
+        let cached_token = self
+            .load_cached_token()
+            .await
+            .map_err(not_found_as_no_valid_token)?;
+        self.refresh_cached_token(cached_token).await
+    }
+
+    /// Refreshes `stale` unless another task or process sharing the store already replaced it.
+    ///
+    /// With refresh token rotation a refresh token is only accepted once, so refreshes are
+    /// serialized (across processes through the store's lock) and the store is read again once
+    /// the lock is held.
+    async fn refresh_cached_token(&self, stale: CachedToken) -> Result<String, ClientError> {
+        let _refreshing = self.refresh_lock.lock().await;
+        let _store_lock = self.store.lock().await?;
+
+        let cached_token = self
+            .store
+            .load()
+            .await?
+            .ok_or(ClientError::NoValidTokenError)?;
+        if cached_token.access_token != stale.access_token {
+            return Ok(cached_token.access_token);
+        }
+
+        let refresh_token_str = cached_token
+            .refresh_token
+            .ok_or(ClientError::NoValidTokenError)?;
+        let http = StatusRecorder::default();
+        let new_token = self
+            .inner
+            .exchange_refresh_token(&RefreshToken::new(refresh_token_str))
+            .request_async(|request| http.send(request))
+            .await
+            .map_err(|e| match http.error(e) {
+                // Keycloak answers invalid_grant once the refresh token or its session has
+                // expired, the error's code tells which
+                ClientError::KeycloakError(e) if e.error == "invalid_grant" => {
+                    ClientError::RefreshTokenExpiredError(e)
                 }
-                _ => Err(e),
-            },
+                e => e,
+            })?;
+        self.cache_token(&new_token).await?;
+        Ok(new_token.access_token().secret().clone())
+    }
+}
+
+/// The access token of `token` if it stays valid for `min_validity` and is not `rejected`.
+fn usable_token(
+    token: Option<&CachedToken>,
+    min_validity: chrono::Duration,
+    rejected: Option<&str>,
+) -> Option<String> {
+    token
+        .filter(|t| !t.expires_within(min_validity) && rejected != Some(t.access_token.as_str()))
+        .map(|t| t.access_token.clone())
+}
+
+/// An empty token store means there is no token to use, not an I/O failure.
+fn not_found_as_no_valid_token(error: ClientError) -> ClientError {
+    match &error {
+        ClientError::IoError(e) if e.kind() == std::io::ErrorKind::NotFound => {
+            ClientError::NoValidTokenError
         }
+        _ => error,
     }
 }
+
 #[serde_as]
-#[derive(Debug, Serialize, Deserialize)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct CachedToken {
     pub access_token: String,
     #[serde_as(as = "DisplayFromStr")]
@@ -389,7 +1079,16 @@ pub struct CachedToken {
     pub refresh_token: Option<String>,
 }
 
+impl CachedToken {
+    /// Returns `true` when the access token expires within `leeway` from now (or already has).
+    pub fn expires_within(&self, leeway: chrono::Duration) -> bool {
+        self.expires_at - leeway <= chrono::Utc::now()
+    }
+}
+
 pub enum Flow {
     DeviceAuthorization,
     OwnerCredentials,
+    AuthorizationCode,
+    ClientCredentials,
 }
@@ -0,0 +1,218 @@
+use std::{
+    fmt,
+    sync::{
+        atomic::{AtomicU16, Ordering},
+        Arc,
+    },
+};
+
+use oauth2::{
+    reqwest::{async_http_client, AsyncHttpClientError},
+    ErrorResponseType, HttpRequest, HttpResponse, RequestTokenError, StandardErrorResponse,
+};
+use serde::Deserialize;
+
+use super::ClientError;
+
+/// An OAuth error response from Keycloak (RFC 6749 section 5.2) and the HTTP status it came
+/// with.
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize)]
+pub struct KeycloakError {
+    /// The error code, e.g. `invalid_grant`
+    pub error: String,
+    /// Keycloak's explanation, e.g. `Session not active`
+    pub error_description: Option<String>,
+    pub error_uri: Option<String>,
+    /// `None` when the error was not read from a response
+    #[serde(skip)]
+    pub status: Option<u16>,
+}
+
+/// The error codes Keycloak answers with, `invalid_grant` split by its description.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum KeycloakErrorCode {
+    /// `invalid_grant` "Session not active": the SSO session ended, through a logout or its idle
+    /// or maximum lifespan
+    SessionNotActive,
+    /// `invalid_grant` "Token is not active": the refresh token itself expired
+    TokenNotActive,
+    /// Any other `invalid_grant`, such as wrong user credentials or a reused refresh token
+    InvalidGrant,
+    InvalidClient,
+    /// The client may not use this grant, e.g. direct access grants are disabled for it
+    UnauthorizedClient,
+    /// A requested scope is unknown or not assigned to the client
+    InvalidScope,
+    InvalidRequest,
+    UnsupportedGrantType,
+    AccessDenied,
+    AuthorizationPending,
+    SlowDown,
+    /// The device code expired before the user signed in
+    ExpiredToken,
+    TemporarilyUnavailable,
+    ServerError,
+    Other(String),
+}
+
+/// What a caller can do about an error.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ErrorCategory {
+    /// Keycloak or the network failed; the same request may succeed later
+    Retryable,
+    /// The tokens or the session are gone, the user has to sign in again
+    ReauthenticationRequired,
+    /// The client, its secret, grants or scopes do not match the realm's configuration
+    Misconfiguration,
+    /// The request was understood and refused
+    Forbidden,
+}
+
+impl KeycloakError {
+    pub fn new(error: impl Into<String>, error_description: Option<String>) -> Self {
+        Self {
+            error: error.into(),
+            error_description,
+            error_uri: None,
+            status: None,
+        }
+    }
+
+    /// Parses an error response body, `None` when it is not an OAuth error.
+    pub fn from_body(status: u16, body: &[u8]) -> Option<Self> {
+        let mut error: Self = serde_json::from_slice(body).ok()?;
+        error.status = Some(status);
+        Some(error)
+    }
+
+    pub fn code(&self) -> KeycloakErrorCode {
+        let description = self
+            .error_description
+            .as_deref()
+            .unwrap_or_default()
+            .to_ascii_lowercase();
+        match self.error.as_str() {
+            // also matches "Offline session not active"
+            "invalid_grant" if description.contains("session not active") => {
+                KeycloakErrorCode::SessionNotActive
+            }
+            "invalid_grant" if description.contains("token is not active") => {
+                KeycloakErrorCode::TokenNotActive
+            }
+            "invalid_grant" => KeycloakErrorCode::InvalidGrant,
+            "invalid_client" => KeycloakErrorCode::InvalidClient,
+            "unauthorized_client" => KeycloakErrorCode::UnauthorizedClient,
+            "invalid_scope" => KeycloakErrorCode::InvalidScope,
+            "invalid_request" => KeycloakErrorCode::InvalidRequest,
+            "unsupported_grant_type" => KeycloakErrorCode::UnsupportedGrantType,
+            "access_denied" => KeycloakErrorCode::AccessDenied,
+            "authorization_pending" => KeycloakErrorCode::AuthorizationPending,
+            "slow_down" => KeycloakErrorCode::SlowDown,
+            "expired_token" => KeycloakErrorCode::ExpiredToken,
+            "temporarily_unavailable" => KeycloakErrorCode::TemporarilyUnavailable,
+            "server_error" => KeycloakErrorCode::ServerError,
+            other => KeycloakErrorCode::Other(other.to_string()),
+        }
+    }
+
+    pub fn category(&self) -> ErrorCategory {
+        match self.code() {
+            KeycloakErrorCode::SessionNotActive
+            | KeycloakErrorCode::TokenNotActive
+            | KeycloakErrorCode::InvalidGrant
+            | KeycloakErrorCode::ExpiredToken => ErrorCategory::ReauthenticationRequired,
+            KeycloakErrorCode::InvalidClient
+            | KeycloakErrorCode::UnauthorizedClient
+            | KeycloakErrorCode::InvalidScope
+            | KeycloakErrorCode::InvalidRequest
+            | KeycloakErrorCode::UnsupportedGrantType => ErrorCategory::Misconfiguration,
+            KeycloakErrorCode::AccessDenied => ErrorCategory::Forbidden,
+            KeycloakErrorCode::AuthorizationPending
+            | KeycloakErrorCode::SlowDown
+            | KeycloakErrorCode::TemporarilyUnavailable
+            | KeycloakErrorCode::ServerError => ErrorCategory::Retryable,
+            KeycloakErrorCode::Other(_) => self
+                .status
+                .map(status_category)
+                .unwrap_or(ErrorCategory::Misconfiguration),
+        }
+    }
+}
+
+impl fmt::Display for KeycloakError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.error)?;
+        if let Some(description) = &self.error_description {
+            write!(f, ": {}", description)?;
+        }
+        if let Some(status) = self.status {
+            write!(f, " (HTTP {})", status)?;
+        }
+        Ok(())
+    }
+}
+
+impl std::error::Error for KeycloakError {}
+
+/// The category of a failed response judged by its status alone.
+pub(crate) fn status_category(status: u16) -> ErrorCategory {
+    match status {
+        401 => ErrorCategory::ReauthenticationRequired,
+        403 => ErrorCategory::Forbidden,
+        408 | 429 | 500..=599 => ErrorCategory::Retryable,
+        _ => ErrorCategory::Misconfiguration,
+    }
+}
+
+/// Sends oauth2's token requests through `async_http_client`, remembering the status of the
+/// last response so errors can report it; oauth2 itself drops it.
+#[derive(Debug, Clone, Default)]
+pub(crate) struct StatusRecorder(Arc<AtomicU16>);
+
+impl StatusRecorder {
+    pub(crate) async fn send(
+        &self,
+        request: HttpRequest,
+    ) -> Result<HttpResponse, AsyncHttpClientError> {
+        let response = async_http_client(request).await?;
+        self.0
+            .store(response.status_code.as_u16(), Ordering::Relaxed);
+        Ok(response)
+    }
+
+    fn status(&self) -> Option<u16> {
+        match self.0.load(Ordering::Relaxed) {
+            0 => None,
+            status => Some(status),
+        }
+    }
+
+    /// Converts the error of a request sent through `send`.
+    pub(crate) fn error<T>(
+        &self,
+        error: RequestTokenError<AsyncHttpClientError, StandardErrorResponse<T>>,
+    ) -> ClientError
+    where
+        T: ErrorResponseType + AsRef<str>,
+    {
+        match error {
+            RequestTokenError::ServerResponse(response) => {
+                ClientError::KeycloakError(KeycloakError {
+                    error: response.error().as_ref().to_string(),
+                    error_description: response.error_description().cloned(),
+                    error_uri: response.error_uri().cloned(),
+                    status: self.status(),
+                })
+            }
+            RequestTokenError::Request(e) => ClientError::TransportError(e.to_string()),
+            RequestTokenError::Parse(e, _) => ClientError::InvalidResponseError {
+                status: self.status(),
+                message: e.to_string(),
+            },
+            RequestTokenError::Other(message) => ClientError::InvalidResponseError {
+                status: self.status(),
+                message,
+            },
+        }
+    }
+}
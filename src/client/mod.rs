@@ -1,16 +1,55 @@
+mod access_token_provider;
+#[cfg(feature = "actix")]
+mod actix_middleware;
 mod app_config;
 mod application_builder;
+mod authorization;
+#[cfg(feature = "axum")]
+mod axum_layer;
+mod bearer;
+#[cfg(feature = "reqwest-middleware")]
+mod bearer_middleware;
 mod config;
 mod credentials;
 mod device_authorization_response;
+mod discovery;
+mod encrypted_token_store;
+mod introspection;
 mod jwks;
 mod jwt_verification;
 mod keycloak;
+mod keycloak_error;
+#[cfg(feature = "secret-service")]
+mod secret_service_token_store;
+mod token_manager;
+mod token_store;
+#[cfg(feature = "tonic")]
+mod tonic_interceptor;
 
+pub use access_token_provider::*;
+#[cfg(feature = "actix")]
+pub use actix_middleware::*;
 pub use app_config::*;
 pub use application_builder::*;
+pub use authorization::*;
+#[cfg(feature = "axum")]
+pub use axum_layer::*;
+pub use bearer::*;
+#[cfg(feature = "reqwest-middleware")]
+pub use bearer_middleware::*;
 pub use config::*;
 pub use credentials::*;
 pub use device_authorization_response::*;
+pub use discovery::*;
+pub use encrypted_token_store::*;
+pub use introspection::*;
+pub use jwks::{Jwk, KeyCache, SharedKeyCache};
 pub use jwt_verification::*;
 pub use keycloak::*;
+pub use keycloak_error::{ErrorCategory, KeycloakError, KeycloakErrorCode};
+#[cfg(feature = "secret-service")]
+pub use secret_service_token_store::*;
+pub use token_manager::*;
+pub use token_store::*;
+#[cfg(feature = "tonic")]
+pub use tonic_interceptor::*;
@@ -0,0 +1,143 @@
+use std::collections::HashMap;
+
+use async_trait::async_trait;
+use secret_service::{EncryptionType, SecretService};
+
+use super::{CachedToken, ClientConfiguration, ClientError, TokenStore};
+
+const APPLICATION: &str = "keycloak-oauth";
+
+/// Keeps the token in the freedesktop Secret Service (gnome-keyring, KWallet, KeePassXC, ...).
+///
+/// Tokens are stored in the default collection under the attributes `application`, `server`,
+/// `realm`, `client_id` and `username`, so several accounts can live side by side. The service
+/// is reached on the session bus (`DBUS_SESSION_BUS_ADDRESS`); when no provider is running every
+/// call returns `ClientError::SecretServiceUnavailable`, which callers can use to fall back to
+/// another store.
+#[derive(Debug, Clone)]
+pub struct SecretServiceTokenStore {
+    server: String,
+    realm: String,
+    client_id: String,
+    username: String,
+}
+
+impl SecretServiceTokenStore {
+    pub fn new(
+        server: impl Into<String>,
+        realm: impl Into<String>,
+        client_id: impl Into<String>,
+        username: impl Into<String>,
+    ) -> Self {
+        Self {
+            server: server.into(),
+            realm: realm.into(),
+            client_id: client_id.into(),
+            username: username.into(),
+        }
+    }
+
+    /// Builds the attribute set from the configured server, realm, client id and username.
+    ///
+    /// The username is only configured for the password flow. Other flows have to name the
+    /// account with `for_account`, otherwise every user of the client on this machine would
+    /// share (and overwrite) one keyring item, so a missing username is an error.
+    pub fn from_config(config: &ClientConfiguration) -> Result<Self, ClientError> {
+        let username = config
+            .username
+            .clone()
+            .ok_or(ClientError::MissingConfigError("username"))?;
+        Ok(Self::for_account(config, username))
+    }
+
+    /// Builds the attribute set from the configured server, realm and client id, keyed on
+    /// `account`, e.g. the user's login name or the `sub` of their token. Missing values are
+    /// stored as empty strings.
+    pub fn for_account(config: &ClientConfiguration, account: impl Into<String>) -> Self {
+        Self::new(
+            config.server_url.clone().unwrap_or_default(),
+            config.realm.clone().unwrap_or_default(),
+            config.client_id.clone().unwrap_or_default(),
+            account,
+        )
+    }
+
+    /// Returns `true` when a Secret Service provider answers on the session bus.
+    pub async fn is_available() -> bool {
+        SecretService::connect(EncryptionType::Dh).await.is_ok()
+    }
+
+    fn attributes(&self) -> HashMap<&str, &str> {
+        HashMap::from([
+            ("application", APPLICATION),
+            ("server", self.server.as_str()),
+            ("realm", self.realm.as_str()),
+            ("client_id", self.client_id.as_str()),
+            ("username", self.username.as_str()),
+        ])
+    }
+
+    fn label(&self) -> String {
+        format!(
+            "Keycloak token for {}@{} ({})",
+            self.username, self.realm, self.client_id
+        )
+    }
+}
+
+impl From<secret_service::Error> for ClientError {
+    fn from(error: secret_service::Error) -> Self {
+        match error {
+            secret_service::Error::Unavailable => ClientError::SecretServiceUnavailable,
+            e => ClientError::SecretServiceError(e.to_string()),
+        }
+    }
+}
+
+#[async_trait]
+impl TokenStore for SecretServiceTokenStore {
+    async fn load(&self) -> Result<Option<CachedToken>, ClientError> {
+        let service = SecretService::connect(EncryptionType::Dh).await?;
+        let result = service.search_items(self.attributes()).await?;
+
+        let item = match (result.unlocked.first(), result.locked.first()) {
+            (Some(item), _) => item,
+            (None, Some(item)) => {
+                item.unlock().await?;
+                item
+            }
+            (None, None) => return Ok(None),
+        };
+
+        let secret = item.get_secret().await?;
+        let cached_token: CachedToken = serde_json::from_slice(&secret)?;
+        Ok(Some(cached_token))
+    }
+
+    async fn save(&self, token: &CachedToken) -> Result<(), ClientError> {
+        let service = SecretService::connect(EncryptionType::Dh).await?;
+        let collection = service.get_default_collection().await?;
+        collection.ensure_unlocked().await?;
+
+        let serialized = serde_json::to_vec(token)?;
+        collection
+            .create_item(
+                &self.label(),
+                self.attributes(),
+                &serialized,
+                true,
+                "application/json",
+            )
+            .await?;
+        Ok(())
+    }
+
+    async fn clear(&self) -> Result<(), ClientError> {
+        let service = SecretService::connect(EncryptionType::Dh).await?;
+        let result = service.search_items(self.attributes()).await?;
+        for item in result.unlocked.iter().chain(result.locked.iter()) {
+            item.delete().await?;
+        }
+        Ok(())
+    }
+}
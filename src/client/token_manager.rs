@@ -0,0 +1,273 @@
+use std::{sync::Arc, time::Duration};
+
+use base64::{engine::general_purpose::URL_SAFE_NO_PAD, Engine};
+use chrono::{DateTime, Utc};
+use tokio::{
+    sync::{watch, Notify},
+    task::JoinHandle,
+};
+
+use super::{AccessTokenProvider, ClientError, KeycloakClient};
+
+/// The token published by a `TokenManager`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum TokenState {
+    /// The current access token. It stays published while refreshes are failing, so check
+    /// `expires_at` when that matters.
+    Active {
+        access_token: String,
+        expires_at: DateTime<Utc>,
+    },
+    /// There is no token, or the refresh token has expired. The user has to sign in again,
+    /// after which `TokenManager::resume` picks up the new token. Never published for client
+    /// credentials clients, which perform their grant again instead.
+    ReauthenticationRequired,
+}
+
+impl TokenState {
+    pub fn access_token(&self) -> Option<&str> {
+        match self {
+            TokenState::Active { access_token, .. } => Some(access_token),
+            TokenState::ReauthenticationRequired => None,
+        }
+    }
+}
+
+/// When a `TokenManager` refreshes and how it retries.
+#[derive(Debug, Clone)]
+pub struct RefreshPolicy {
+    /// Fraction of the token's lifetime after which it is refreshed, between 0 and 1
+    pub refresh_fraction: f64,
+    /// Delay before retrying a failed refresh, doubled after every failure
+    pub min_backoff: Duration,
+    pub max_backoff: Duration,
+}
+
+impl Default for RefreshPolicy {
+    fn default() -> Self {
+        Self {
+            refresh_fraction: 0.75,
+            min_backoff: Duration::from_secs(1),
+            max_backoff: Duration::from_secs(60),
+        }
+    }
+}
+
+impl RefreshPolicy {
+    pub fn with_refresh_fraction(mut self, fraction: f64) -> Self {
+        self.refresh_fraction = fraction.clamp(0.0, 1.0);
+        self
+    }
+
+    pub fn with_backoff(mut self, min: Duration, max: Duration) -> Self {
+        self.min_backoff = min;
+        self.max_backoff = max.max(min);
+        self
+    }
+}
+
+/// Owns a `KeycloakClient` and keeps its token fresh from a background task.
+///
+/// The token is refreshed once `refresh_fraction` of its lifetime has passed, counted from when
+/// the manager first saw it, and published on a `tokio::sync::watch` channel. Failed refreshes
+/// are retried with exponential backoff. A client credentials client performs its grant again
+/// instead of refreshing. For the user flows, when there is no refresh token or it has expired,
+/// the manager publishes `TokenState::ReauthenticationRequired` and waits for `resume`.
+///
+/// ```no_run
+/// # async fn run(client: keycloak_oauth::client::KeycloakClient<keycloak_oauth::client::WithDeviceCredentials>) -> Result<(), keycloak_oauth::client::ClientError> {
+/// use keycloak_oauth::client::{RefreshPolicy, TokenManager, TokenState};
+///
+/// let manager = TokenManager::start(client, RefreshPolicy::default()).await;
+/// let mut tokens = manager.subscribe();
+/// while tokens.changed().await.is_ok() {
+///     if *tokens.borrow() == TokenState::ReauthenticationRequired {
+///         manager.client().authenticate().await?;
+///         manager.resume();
+///     }
+/// }
+/// # Ok(())
+/// # }
+/// ```
+pub struct TokenManager<C> {
+    client: Arc<KeycloakClient<C>>,
+    receiver: watch::Receiver<TokenState>,
+    resume: Arc<Notify>,
+    task: JoinHandle<()>,
+}
+
+impl<C: Send + Sync + 'static> TokenManager<C>
+where
+    KeycloakClient<C>: AccessTokenProvider,
+{
+    /// Loads the cached token and starts the refresh task. A client credentials client without
+    /// a cached token performs its grant first.
+    pub async fn start(client: impl Into<Arc<KeycloakClient<C>>>, policy: RefreshPolicy) -> Self {
+        let client = client.into();
+        let mut state = load_state(&client).await;
+        if state == TokenState::ReauthenticationRequired {
+            // failures are retried by the refresh task
+            if let Ok(access_token) = client.access_token().await {
+                state = active_state(&client, access_token, &policy).await;
+            }
+        }
+        let (sender, receiver) = watch::channel(state);
+        let resume = Arc::new(Notify::new());
+        let task = tokio::spawn(refresh_loop(
+            Arc::clone(&client),
+            policy,
+            sender,
+            Arc::clone(&resume),
+        ));
+
+        Self {
+            client,
+            receiver,
+            resume,
+            task,
+        }
+    }
+}
+
+impl<C> TokenManager<C> {
+    pub fn client(&self) -> &KeycloakClient<C> {
+        &self.client
+    }
+
+    /// A receiver notified every time a token is published.
+    pub fn subscribe(&self) -> watch::Receiver<TokenState> {
+        self.receiver.clone()
+    }
+
+    pub fn current(&self) -> TokenState {
+        self.receiver.borrow().clone()
+    }
+
+    /// Returns the current access token, if any.
+    pub fn access_token(&self) -> Result<String, ClientError> {
+        self.receiver
+            .borrow()
+            .access_token()
+            .map(str::to_string)
+            .ok_or(ClientError::NoValidTokenError)
+    }
+
+    /// Reloads the cached token after the user signed in again.
+    pub fn resume(&self) {
+        self.resume.notify_one();
+    }
+}
+
+impl<C> Drop for TokenManager<C> {
+    fn drop(&mut self) {
+        self.task.abort();
+    }
+}
+
+async fn refresh_loop<C>(
+    client: Arc<KeycloakClient<C>>,
+    policy: RefreshPolicy,
+    sender: watch::Sender<TokenState>,
+    resume: Arc<Notify>,
+) where
+    KeycloakClient<C>: AccessTokenProvider,
+{
+    let mut state = sender.borrow().clone();
+    loop {
+        publish(&sender, state.clone());
+        let access_token = match state {
+            TokenState::Active {
+                access_token,
+                expires_at,
+            } => {
+                let lifetime = (expires_at - Utc::now()).to_std().unwrap_or_default();
+                tokio::time::sleep(lifetime.mul_f64(policy.refresh_fraction)).await;
+                Some(access_token)
+            }
+            TokenState::ReauthenticationRequired => None,
+        };
+        state = renew(&client, access_token.as_deref(), &policy, &sender, &resume).await;
+    }
+}
+
+/// Replaces `stale`, or gets a first token, retrying with backoff, and returns the state to
+/// publish next. Refreshes the user flows cannot recover from publish
+/// `TokenState::ReauthenticationRequired` and wait for `resume`, which reloads the cache.
+async fn renew<C>(
+    client: &KeycloakClient<C>,
+    stale: Option<&str>,
+    policy: &RefreshPolicy,
+    sender: &watch::Sender<TokenState>,
+    resume: &Notify,
+) -> TokenState
+where
+    KeycloakClient<C>: AccessTokenProvider,
+{
+    let mut backoff = policy.min_backoff;
+    loop {
+        match client
+            .fresh_access_token(chrono::Duration::zero(), stale)
+            .await
+        {
+            Ok(access_token) => return active_state(client, access_token, policy).await,
+            Err(e) if e.requires_reauthentication() => {
+                publish(sender, TokenState::ReauthenticationRequired);
+                resume.notified().await;
+                return load_state(client).await;
+            }
+            Err(_) => {
+                tokio::time::sleep(backoff).await;
+                backoff = (backoff * 2).min(policy.max_backoff);
+            }
+        }
+    }
+}
+
+/// `access_token` with its expiry, taken from the cache or else from the token's `exp` claim.
+/// A token whose expiry cannot be told, e.g. an opaque one handed out without a cache, is taken
+/// to expire after `max_backoff`.
+async fn active_state<C>(
+    client: &KeycloakClient<C>,
+    access_token: String,
+    policy: &RefreshPolicy,
+) -> TokenState {
+    let expires_at = match client.load_cached_token().await {
+        Ok(cached) if cached.access_token == access_token => cached.expires_at,
+        _ => jwt_expiry(&access_token).unwrap_or_else(|| {
+            Utc::now() + chrono::Duration::from_std(policy.max_backoff).unwrap_or_default()
+        }),
+    };
+    TokenState::Active {
+        access_token,
+        expires_at,
+    }
+}
+
+/// The `exp` claim of a JWT, read without verifying it: only used to schedule the refresh.
+fn jwt_expiry(token: &str) -> Option<DateTime<Utc>> {
+    let payload = URL_SAFE_NO_PAD.decode(token.split('.').nth(1)?).ok()?;
+    let claims: serde_json::Value = serde_json::from_slice(&payload).ok()?;
+    DateTime::from_timestamp(claims.get("exp")?.as_i64()?, 0)
+}
+
+async fn load_state<C>(client: &KeycloakClient<C>) -> TokenState {
+    match client.load_cached_token().await {
+        Ok(cached_token) => TokenState::Active {
+            access_token: cached_token.access_token,
+            expires_at: cached_token.expires_at,
+        },
+        Err(_) => TokenState::ReauthenticationRequired,
+    }
+}
+
+// subscribers are only woken up when the state actually changes
+fn publish(sender: &watch::Sender<TokenState>, state: TokenState) {
+    sender.send_if_modified(|current| {
+        if *current == state {
+            false
+        } else {
+            *current = state;
+            true
+        }
+    });
+}
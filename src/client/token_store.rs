@@ -0,0 +1,164 @@
+use std::{
+    fs::{File, OpenOptions},
+    path::{Path, PathBuf},
+};
+
+use async_trait::async_trait;
+use tokio::sync::Mutex;
+
+use super::{CachedToken, ClientError};
+
+/// Storage for the token obtained by the flows.
+///
+/// `KeycloakClient` saves every new token through its store and loads it again in
+/// `verify_and_refresh_access_token`, so the store decides where (and if) credentials live.
+#[async_trait]
+pub trait TokenStore: Send + Sync {
+    /// Returns the stored token, or `None` when nothing has been stored yet.
+    async fn load(&self) -> Result<Option<CachedToken>, ClientError>;
+
+    async fn save(&self, token: &CachedToken) -> Result<(), ClientError>;
+
+    async fn clear(&self) -> Result<(), ClientError>;
+
+    /// Takes a lock held across a token refresh, so processes sharing the store do not refresh
+    /// with the same refresh token. Stores only used by one process return `None`.
+    async fn lock(&self) -> Result<Option<StoreLock>, ClientError> {
+        Ok(None)
+    }
+}
+
+/// An exclusive lock on a `<token file>.lock` file, released when dropped.
+#[derive(Debug)]
+pub struct StoreLock {
+    _file: File,
+}
+
+impl StoreLock {
+    /// Waits for the lock next to the token file at `path`.
+    pub async fn acquire(path: &Path) -> Result<Self, ClientError> {
+        let mut lock_path = path.as_os_str().to_owned();
+        lock_path.push(".lock");
+        let lock_path = PathBuf::from(lock_path);
+        if let Some(parent) = lock_path.parent() {
+            if !parent.as_os_str().is_empty() {
+                tokio::fs::create_dir_all(parent).await?;
+            }
+        }
+
+        let file = tokio::task::spawn_blocking(move || {
+            let file = OpenOptions::new()
+                .create(true)
+                .truncate(false)
+                .write(true)
+                .open(&lock_path)?;
+            file.lock()?;
+            Ok::<_, std::io::Error>(file)
+        })
+        .await
+        .map_err(std::io::Error::other)??;
+
+        Ok(Self { _file: file })
+    }
+}
+
+/// Keeps the token in memory for the lifetime of the client. Nothing touches the disk.
+#[derive(Debug, Default)]
+pub struct MemoryTokenStore {
+    token: Mutex<Option<CachedToken>>,
+}
+
+impl MemoryTokenStore {
+    pub fn new() -> Self {
+        Self::default()
+    }
+}
+
+#[async_trait]
+impl TokenStore for MemoryTokenStore {
+    async fn load(&self) -> Result<Option<CachedToken>, ClientError> {
+        Ok(self.token.lock().await.clone())
+    }
+
+    async fn save(&self, token: &CachedToken) -> Result<(), ClientError> {
+        *self.token.lock().await = Some(token.clone());
+        Ok(())
+    }
+
+    async fn clear(&self) -> Result<(), ClientError> {
+        *self.token.lock().await = None;
+        Ok(())
+    }
+}
+
+/// Writes the token as plain JSON to `path`.
+#[derive(Debug, Clone)]
+pub struct FileTokenStore {
+    path: PathBuf,
+}
+
+impl FileTokenStore {
+    pub fn new(path: impl Into<PathBuf>) -> Self {
+        Self { path: path.into() }
+    }
+
+    pub fn path(&self) -> &PathBuf {
+        &self.path
+    }
+}
+
+#[async_trait]
+impl TokenStore for FileTokenStore {
+    async fn load(&self) -> Result<Option<CachedToken>, ClientError> {
+        let data = match tokio::fs::read_to_string(&self.path).await {
+            Ok(data) => data,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(None),
+            Err(e) => return Err(e.into()),
+        };
+        let cached_token: CachedToken = serde_json::from_str(&data)?;
+        Ok(Some(cached_token))
+    }
+
+    async fn save(&self, token: &CachedToken) -> Result<(), ClientError> {
+        if let Some(parent) = self.path.parent() {
+            if !parent.as_os_str().is_empty() {
+                tokio::fs::create_dir_all(parent).await?;
+            }
+        }
+        let serialized = serde_json::to_string_pretty(token)?;
+        tokio::fs::write(&self.path, serialized).await?;
+        Ok(())
+    }
+
+    async fn clear(&self) -> Result<(), ClientError> {
+        match tokio::fs::remove_file(&self.path).await {
+            Ok(()) => Ok(()),
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(()),
+            Err(e) => Err(e.into()),
+        }
+    }
+
+    async fn lock(&self) -> Result<Option<StoreLock>, ClientError> {
+        StoreLock::acquire(&self.path).await.map(Some)
+    }
+}
+
+/// Never keeps a token. Every call to `verify_and_refresh_access_token` will report that no
+/// valid token is available.
+#[derive(Debug, Default, Clone, Copy)]
+pub struct NoopTokenStore;
+
+#[async_trait]
+impl TokenStore for NoopTokenStore {
+    async fn load(&self) -> Result<Option<CachedToken>, ClientError> {
+        Ok(None)
+    }
+
+    async fn save(&self, _token: &CachedToken) -> Result<(), ClientError> {
+        Ok(())
+    }
+
+    async fn clear(&self) -> Result<(), ClientError> {
+        Ok(())
+    }
+}
@@ -0,0 +1,274 @@
+use std::{
+    future::Future,
+    marker::PhantomData,
+    pin::Pin,
+    sync::Arc,
+    task::{Context, Poll},
+};
+
+use serde::de::DeserializeOwned;
+use tonic::{
+    body::BoxBody,
+    codegen::http::{self, header, HeaderValue},
+    Status,
+};
+use tower_layer::Layer;
+use tower_service::Service;
+
+use super::{
+    verify_bearer, AccessTokenProvider, BearerError, KeycloakClaims, Requirement, TokenVerifier,
+};
+
+type BoxError = Box<dyn std::error::Error + Send + Sync>;
+
+// tonic's `Interceptor` trait is synchronous, while refreshing a token or fetching signing keys
+// is not, so both interceptors are tower layers around the channel or the server's routes.
+
+/// Client interceptor attaching an access token to every outgoing call.
+///
+/// The token comes from an `AccessTokenProvider`, usually a `KeycloakClient`: user flows refresh
+/// an expired token, a client credentials client performs its grant again. When no token can
+/// be obtained the call fails with `Status::unauthenticated` without reaching the server.
+///
+/// ```no_run
+/// # async fn connect(client: keycloak_oauth::client::KeycloakClient<keycloak_oauth::client::WithClientCredentials>) {
+/// use keycloak_oauth::client::GrpcTokenLayer;
+///
+/// // let channel = tonic::transport::Channel::from_static("http://[::1]:50051").connect().await?;
+/// // let channel = tower::ServiceBuilder::new().layer(GrpcTokenLayer::new(client)).service(channel);
+/// // let mut greeter = GreeterClient::new(channel);
+/// let layer = GrpcTokenLayer::new(client);
+/// # }
+/// ```
+pub struct GrpcTokenLayer<P> {
+    provider: Arc<P>,
+}
+
+impl<P: AccessTokenProvider> GrpcTokenLayer<P> {
+    /// Takes a `KeycloakClient`, a `TokenManager` or any other provider, also behind an `Arc`.
+    pub fn new(provider: P) -> Self {
+        Self {
+            provider: Arc::new(provider),
+        }
+    }
+}
+
+impl<P> Clone for GrpcTokenLayer<P> {
+    fn clone(&self) -> Self {
+        Self {
+            provider: Arc::clone(&self.provider),
+        }
+    }
+}
+
+impl<S, P> Layer<S> for GrpcTokenLayer<P> {
+    type Service = GrpcTokenService<S, P>;
+
+    fn layer(&self, inner: S) -> Self::Service {
+        GrpcTokenService {
+            inner,
+            provider: Arc::clone(&self.provider),
+        }
+    }
+}
+
+pub struct GrpcTokenService<S, P> {
+    inner: S,
+    provider: Arc<P>,
+}
+
+impl<S: Clone, P> Clone for GrpcTokenService<S, P> {
+    fn clone(&self) -> Self {
+        Self {
+            inner: self.inner.clone(),
+            provider: Arc::clone(&self.provider),
+        }
+    }
+}
+
+impl<S, P, B> Service<http::Request<B>> for GrpcTokenService<S, P>
+where
+    S: Service<http::Request<B>> + Clone + Send + 'static,
+    S::Future: Send + 'static,
+    S::Error: Into<BoxError>,
+    P: AccessTokenProvider + 'static,
+    B: Send + 'static,
+{
+    type Response = S::Response;
+    type Error = BoxError;
+    type Future = Pin<Box<dyn Future<Output = Result<S::Response, BoxError>> + Send>>;
+
+    fn poll_ready(&mut self, cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        self.inner.poll_ready(cx).map_err(Into::into)
+    }
+
+    fn call(&mut self, mut request: http::Request<B>) -> Self::Future {
+        // the clone is not ready yet, so the ready service is taken and the clone left behind
+        let clone = self.inner.clone();
+        let mut inner = std::mem::replace(&mut self.inner, clone);
+        let provider = Arc::clone(&self.provider);
+
+        Box::pin(async move {
+            // tonic recovers a boxed `Status` as the call's status, as long as it is boxed once
+            let token = provider
+                .access_token()
+                .await
+                .map_err(|e| -> BoxError { Box::new(Status::unauthenticated(e.to_string())) })?;
+            let value =
+                HeaderValue::from_str(&format!("Bearer {}", token)).map_err(|_| -> BoxError {
+                    Box::new(Status::internal("access token is not a valid header"))
+                })?;
+            request.headers_mut().insert(header::AUTHORIZATION, value);
+
+            inner.call(request).await.map_err(Into::into)
+        })
+    }
+}
+
+/// Server interceptor validating the `authorization` metadata of incoming calls.
+///
+/// Valid calls reach the service with the claims (decoded as `T`, and as `KeycloakClaims` when
+/// possible) in the request extensions, readable with `tonic::Request::extensions` or
+/// `grpc_claims`. Other calls are answered with `UNAUTHENTICATED`, or `PERMISSION_DENIED` when
+/// the token does not meet the layer's requirement.
+///
+/// ```no_run
+/// # fn serve(verifier: keycloak_oauth::client::TokenVerifier) {
+/// use keycloak_oauth::client::GrpcAuthLayer;
+///
+/// // tonic::transport::Server::builder()
+/// //     .layer(GrpcAuthLayer::new(verifier))
+/// //     .add_service(GreeterServer::new(greeter))
+/// let layer = GrpcAuthLayer::new(verifier);
+/// # }
+/// ```
+pub struct GrpcAuthLayer<T = KeycloakClaims> {
+    verifier: TokenVerifier,
+    requirement: Option<Requirement>,
+    _claims: PhantomData<fn() -> T>,
+}
+
+impl GrpcAuthLayer<KeycloakClaims> {
+    pub fn new(verifier: TokenVerifier) -> Self {
+        Self {
+            verifier,
+            requirement: None,
+            _claims: PhantomData,
+        }
+    }
+}
+
+impl<T> GrpcAuthLayer<T> {
+    /// Decodes the claims into the application's own type instead of `KeycloakClaims`.
+    pub fn with_claims<U>(self) -> GrpcAuthLayer<U> {
+        GrpcAuthLayer {
+            verifier: self.verifier,
+            requirement: self.requirement,
+            _claims: PhantomData,
+        }
+    }
+
+    /// Rejects valid tokens that do not meet `requirement` with `PERMISSION_DENIED`.
+    pub fn require(mut self, requirement: Requirement) -> Self {
+        self.requirement = Some(requirement);
+        self
+    }
+}
+
+impl<T> Clone for GrpcAuthLayer<T> {
+    fn clone(&self) -> Self {
+        Self {
+            verifier: self.verifier.clone(),
+            requirement: self.requirement.clone(),
+            _claims: PhantomData,
+        }
+    }
+}
+
+impl<S, T> Layer<S> for GrpcAuthLayer<T> {
+    type Service = GrpcAuthService<S, T>;
+
+    fn layer(&self, inner: S) -> Self::Service {
+        GrpcAuthService {
+            inner,
+            layer: self.clone(),
+        }
+    }
+}
+
+pub struct GrpcAuthService<S, T> {
+    inner: S,
+    layer: GrpcAuthLayer<T>,
+}
+
+impl<S: Clone, T> Clone for GrpcAuthService<S, T> {
+    fn clone(&self) -> Self {
+        Self {
+            inner: self.inner.clone(),
+            layer: self.layer.clone(),
+        }
+    }
+}
+
+impl<S, T, B> Service<http::Request<B>> for GrpcAuthService<S, T>
+where
+    S: Service<http::Request<B>, Response = http::Response<BoxBody>> + Clone + Send + 'static,
+    S::Future: Send + 'static,
+    T: DeserializeOwned + Clone + Send + Sync + 'static,
+    B: Send + 'static,
+{
+    type Response = http::Response<BoxBody>;
+    type Error = S::Error;
+    type Future = Pin<Box<dyn Future<Output = Result<Self::Response, S::Error>> + Send>>;
+
+    fn poll_ready(&mut self, cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        self.inner.poll_ready(cx)
+    }
+
+    fn call(&mut self, mut request: http::Request<B>) -> Self::Future {
+        let clone = self.inner.clone();
+        let mut inner = std::mem::replace(&mut self.inner, clone);
+        let layer = self.layer.clone();
+
+        Box::pin(async move {
+            let authorization = request
+                .headers()
+                .get(header::AUTHORIZATION)
+                .map(|value| value.to_str().unwrap_or_default());
+
+            match verify_bearer::<T>(&layer.verifier, authorization, layer.requirement.as_ref())
+                .await
+            {
+                Ok((claims, keycloak_claims)) => {
+                    if let Some(keycloak_claims) = keycloak_claims {
+                        request.extensions_mut().insert(keycloak_claims);
+                    }
+                    request.extensions_mut().insert(claims);
+                    inner.call(request).await
+                }
+                Err(error) => Ok(Status::from(error).into_http()),
+            }
+        })
+    }
+}
+
+impl From<BearerError> for Status {
+    fn from(error: BearerError) -> Self {
+        match error {
+            BearerError::InsufficientScope(_) => Status::permission_denied(error.to_string()),
+            BearerError::Misconfigured(_) => Status::internal(error.to_string()),
+            BearerError::Unavailable(_) => Status::unavailable(error.to_string()),
+            _ => Status::unauthenticated(error.to_string()),
+        }
+    }
+}
+
+/// The `KeycloakClaims` that `GrpcAuthLayer` put in the extensions of a call.
+// `Status` is what tonic handlers return, so it is not boxed here
+#[allow(clippy::result_large_err)]
+pub fn grpc_claims<M>(request: &tonic::Request<M>) -> Result<&KeycloakClaims, Status> {
+    request
+        .extensions()
+        .get::<KeycloakClaims>()
+        .ok_or_else(|| Status::from(BearerError::MissingToken))
+}
@@ -0,0 +1,140 @@
+//! `KeycloakAuth` and the extractors in an actix-web app, with locally minted tokens.
+
+#![cfg(feature = "actix")]
+
+mod common;
+
+use actix_web::{
+    http::{header, StatusCode},
+    test::{call_service, init_service, read_body, TestRequest},
+    web, App,
+};
+use common::tokens::*;
+use jsonwebtoken::Algorithm;
+use keycloak_oauth::client::{AuthenticatedUser, KeycloakAuth, Requirement};
+use serde_json::{json, Value};
+
+async fn me(user: AuthenticatedUser) -> String {
+    user.0.preferred_username.unwrap_or_default()
+}
+
+struct Answer {
+    status: StatusCode,
+    challenge: Option<String>,
+    body: String,
+}
+
+/// Calls `/me` on an app wrapped in `auth`, or on a bare app when `auth` is `None`.
+async fn call(auth: Option<KeycloakAuth>, authorization: Option<String>) -> Answer {
+    let mut request = TestRequest::get().uri("/me");
+    if let Some(authorization) = authorization {
+        request = request.insert_header((header::AUTHORIZATION, authorization));
+    }
+    let response = match auth {
+        Some(auth) => {
+            let app = init_service(
+                App::new()
+                    .wrap(auth.realm("test"))
+                    .route("/me", web::get().to(me)),
+            )
+            .await;
+            call_service(&app, request.to_request())
+                .await
+                .map_into_boxed_body()
+        }
+        None => {
+            let app = init_service(App::new().route("/me", web::get().to(me))).await;
+            call_service(&app, request.to_request()).await
+        }
+    };
+    let status = response.status();
+    let challenge = response
+        .headers()
+        .get(header::WWW_AUTHENTICATE)
+        .map(|value| value.to_str().unwrap().to_string());
+    let body = read_body(response).await;
+    Answer {
+        status,
+        challenge,
+        body: String::from_utf8(body.to_vec()).unwrap(),
+    }
+}
+
+async fn call_with(auth: KeycloakAuth, claims: &Value) -> Answer {
+    let token = mint(Algorithm::RS256, claims);
+    call(Some(auth), Some(format!("Bearer {}", token))).await
+}
+
+#[tokio::test]
+async fn valid_tokens_reach_the_handler() {
+    let answer = call_with(KeycloakAuth::new(verifier()), &claims()).await;
+    assert_eq!(answer.status, StatusCode::OK);
+    assert_eq!(answer.body, "alice");
+}
+
+#[tokio::test]
+async fn missing_token_gets_a_bare_challenge() {
+    let answer = call(Some(KeycloakAuth::new(verifier())), None).await;
+    assert_eq!(answer.status, StatusCode::UNAUTHORIZED);
+    assert_eq!(answer.challenge.as_deref(), Some(r#"Bearer realm="test""#));
+}
+
+#[tokio::test]
+async fn invalid_token_is_unauthorized() {
+    let expired = {
+        let mut claims = claims();
+        claims["exp"] = json!(chrono::Utc::now().timestamp() - 120);
+        claims
+    };
+    let answer = call_with(KeycloakAuth::new(verifier()), &expired).await;
+    assert_eq!(answer.status, StatusCode::UNAUTHORIZED);
+    assert!(answer
+        .challenge
+        .unwrap()
+        .starts_with(r#"Bearer realm="test", error="invalid_token""#));
+
+    let answer = call(
+        Some(KeycloakAuth::new(verifier())),
+        Some("Bearer not-a-jwt".into()),
+    )
+    .await;
+    assert_eq!(answer.status, StatusCode::UNAUTHORIZED);
+}
+
+#[tokio::test]
+async fn unmet_requirement_is_forbidden() {
+    let auth = KeycloakAuth::new(verifier()).require(Requirement::realm_role("admin"));
+    let answer = call_with(auth, &claims()).await;
+    assert_eq!(answer.status, StatusCode::FORBIDDEN);
+    assert!(answer
+        .challenge
+        .unwrap()
+        .starts_with(r#"Bearer realm="test", error="insufficient_scope""#));
+}
+
+#[tokio::test]
+async fn extractor_verifies_with_app_data() {
+    let app = init_service(
+        App::new()
+            .app_data(web::Data::new(verifier()))
+            .route("/me", web::get().to(me)),
+    )
+    .await;
+    let request = TestRequest::get()
+        .uri("/me")
+        .insert_header((
+            header::AUTHORIZATION,
+            format!("Bearer {}", mint(Algorithm::ES256, &claims())),
+        ))
+        .to_request();
+    let response = call_service(&app, request).await;
+    assert_eq!(response.status(), StatusCode::OK);
+}
+
+#[tokio::test]
+async fn extractor_without_a_verifier_is_a_server_error() {
+    let token = mint(Algorithm::RS256, &claims());
+    let answer = call(None, Some(format!("Bearer {}", token))).await;
+    assert_eq!(answer.status, StatusCode::INTERNAL_SERVER_ERROR);
+    assert_eq!(answer.challenge, None);
+}
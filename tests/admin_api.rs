@@ -0,0 +1,306 @@
+//! `KeycloakAdmin` against a stub server: the URLs it builds and how answers are mapped.
+
+mod common;
+
+use common::*;
+use keycloak_oauth::{
+    admin::{
+        KeycloakAdmin, MapperOwner, Page, ProtocolMapperRepresentation, ScopeKind, UserQuery,
+        UserRepresentation,
+    },
+    client::{ClientError, ErrorCategory},
+};
+use serde_json::json;
+
+fn token_route() -> Route {
+    Route::json(
+        "/token",
+        200,
+        json!({"access_token": "admin-token", "token_type": "Bearer", "expires_in": 300}),
+    )
+}
+
+/// An admin client for the stub's realm and the admin API requests it sent, without the grant.
+async fn admin(routes: Vec<Route>) -> (KeycloakAdmin, impl Fn() -> Vec<String>) {
+    let (server, requests) =
+        recording_stub_server(routes.into_iter().chain([token_route()]).collect()).await;
+    let admin = KeycloakAdmin::from_client(service_client(configuration(&server))).unwrap();
+    let admin_requests = move || {
+        requests
+            .lines()
+            .into_iter()
+            .filter(|line| line.contains("/admin/"))
+            .collect()
+    };
+    (admin, admin_requests)
+}
+
+fn alice() -> UserRepresentation {
+    UserRepresentation {
+        username: Some("alice".into()),
+        ..Default::default()
+    }
+}
+
+#[tokio::test]
+async fn queries_and_pages_go_in_the_query_string() {
+    let (admin, requests) = admin(vec![
+        Route::json("/users", 200, json!([{"id": "1", "username": "alice"}])),
+        Route::json("/groups", 200, json!([])),
+    ])
+    .await;
+
+    let users = admin
+        .users(&UserQuery::new().search("alice").page(20, 10))
+        .await
+        .unwrap();
+    assert_eq!(users[0].username.as_deref(), Some("alice"));
+    admin.user_groups("1", Page::new(0, 5)).await.unwrap();
+
+    assert_eq!(
+        requests(),
+        [
+            "GET /admin/realms/test/users?search=alice&first=20&max=10",
+            "GET /admin/realms/test/users/1/groups?first=0&max=5",
+        ]
+    );
+}
+
+#[tokio::test]
+async fn path_segments_are_percent_encoded() {
+    let (admin, requests) = admin(vec![]).await;
+
+    // nothing answers, so the role is missing
+    assert!(admin.realm_role("ops/on call").await.unwrap().is_none());
+    admin.realm("other realm").user("1").await.unwrap();
+
+    assert_eq!(
+        requests(),
+        [
+            "GET /admin/realms/test/roles/ops%2Fon%20call",
+            "GET /admin/realms/other%20realm/users/1",
+        ]
+    );
+}
+
+#[tokio::test]
+async fn created_ids_come_from_the_location_header() {
+    let (admin, requests) = admin(vec![Route::json("/users", 201, json!({})).with_header(
+        "Location",
+        "http://keycloak/admin/realms/test/users/5f0c2b1e",
+    )])
+    .await;
+
+    assert_eq!(admin.create_user(&alice()).await.unwrap(), "5f0c2b1e");
+    assert_eq!(requests(), ["POST /admin/realms/test/users"]);
+}
+
+#[tokio::test]
+async fn creations_without_a_location_are_errors() {
+    let (admin, _) = admin(vec![Route::json("/users", 201, json!({}))]).await;
+
+    let error = admin.create_user(&alice()).await.unwrap_err();
+    assert!(
+        matches!(error, ClientError::AdminApiError { status: 201, .. }),
+        "{:?}",
+        error
+    );
+}
+
+#[tokio::test]
+async fn missing_resources_are_not_found() {
+    let (admin, _) = admin(vec![Route::json(
+        "/users/1",
+        404,
+        json!({"error": "User not found"}),
+    )])
+    .await;
+
+    assert!(admin.user("1").await.unwrap().is_none());
+    let error = admin.delete_user("1").await.unwrap_err();
+    assert!(
+        matches!(&error, ClientError::AdminApiError { status: 404, message } if message == "User not found"),
+        "{:?}",
+        error
+    );
+}
+
+#[tokio::test]
+async fn conflicts_carry_keycloaks_message() {
+    let (admin, _) = admin(vec![Route::json(
+        "/users",
+        409,
+        json!({"errorMessage": "User exists with same username"}),
+    )])
+    .await;
+
+    let error = admin.create_user(&alice()).await.unwrap_err();
+    assert!(
+        matches!(&error, ClientError::AdminApiError { status: 409, message } if message == "User exists with same username"),
+        "{:?}",
+        error
+    );
+    assert_eq!(error.category(), Some(ErrorCategory::Misconfiguration));
+}
+
+#[tokio::test]
+async fn unauthorized_requests_require_reauthentication() {
+    let (admin, _) = admin(vec![Route::html("/users", 401, "Unauthorized")]).await;
+
+    let error = admin.users(&UserQuery::new()).await.unwrap_err();
+    assert!(
+        matches!(&error, ClientError::AdminApiError { status: 401, message } if message == "Unauthorized"),
+        "{:?}",
+        error
+    );
+    assert!(error.requires_reauthentication());
+}
+
+#[tokio::test]
+async fn clients_are_looked_up_by_client_id() {
+    let (admin, requests) = admin(vec![Route::json(
+        "/clients",
+        200,
+        json!([{"id": "8c1f", "clientId": "waves-api"}]),
+    )])
+    .await;
+
+    let client = admin
+        .client_by_client_id("waves-api")
+        .await
+        .unwrap()
+        .unwrap();
+    assert_eq!(client.client_id.as_deref(), Some("waves-api"));
+    assert_eq!(
+        admin.client_uuid("waves-api").await.unwrap().as_deref(),
+        Some("8c1f")
+    );
+    assert_eq!(
+        requests(),
+        [
+            "GET /admin/realms/test/clients?clientId=waves-api",
+            "GET /admin/realms/test/clients?clientId=waves-api",
+        ]
+    );
+}
+
+#[tokio::test]
+async fn unknown_client_ids_are_none() {
+    let (admin, _) = admin(vec![Route::json("/clients", 200, json!([]))]).await;
+    assert!(admin.client_by_client_id("gone").await.unwrap().is_none());
+    assert!(admin.client_uuid("gone").await.unwrap().is_none());
+}
+
+#[tokio::test]
+async fn regenerating_a_secret_posts_and_returns_the_new_one() {
+    let (admin, requests) = admin(vec![Route::json(
+        "/client-secret",
+        200,
+        json!({"type": "secret", "value": "n3w"}),
+    )])
+    .await;
+
+    let secret = admin.regenerate_client_secret("8c1f").await.unwrap();
+    assert_eq!(secret.value.as_deref(), Some("n3w"));
+    assert_eq!(
+        requests(),
+        ["POST /admin/realms/test/clients/8c1f/client-secret"]
+    );
+}
+
+#[tokio::test]
+async fn service_account_user_of_a_client() {
+    let (admin, requests) = admin(vec![Route::json(
+        "/service-account-user",
+        200,
+        json!({"id": "u1", "username": "service-account-waves-api"}),
+    )])
+    .await;
+
+    let user = admin.service_account_user("8c1f").await.unwrap();
+    assert_eq!(user.username.as_deref(), Some("service-account-waves-api"));
+    assert_eq!(
+        requests(),
+        ["GET /admin/realms/test/clients/8c1f/service-account-user"]
+    );
+}
+
+#[tokio::test]
+async fn protocol_mappers_live_under_their_owner() {
+    let (admin, requests) = admin(vec![
+        Route::json("/models", 201, json!({})).with_header(
+            "Location",
+            "http://keycloak/admin/realms/test/clients/8c1f/protocol-mappers/models/m1",
+        ),
+        Route::html("/models/m1", 204, ""),
+    ])
+    .await;
+    let mapper = ProtocolMapperRepresentation::audience("audience", "waves-api");
+
+    let id = admin
+        .create_protocol_mapper(MapperOwner::Client("8c1f"), &mapper)
+        .await
+        .unwrap();
+    assert_eq!(id, "m1");
+    admin
+        .update_protocol_mapper(MapperOwner::ClientScope("s1"), "m1", &mapper)
+        .await
+        .unwrap();
+    admin
+        .delete_protocol_mapper(MapperOwner::Client("8c1f"), "m1")
+        .await
+        .unwrap();
+
+    assert_eq!(
+        requests(),
+        [
+            "POST /admin/realms/test/clients/8c1f/protocol-mappers/models",
+            "PUT /admin/realms/test/client-scopes/s1/protocol-mappers/models/m1",
+            "DELETE /admin/realms/test/clients/8c1f/protocol-mappers/models/m1",
+        ]
+    );
+}
+
+#[tokio::test]
+async fn default_and_optional_scopes_have_their_own_paths() {
+    let (admin, requests) = admin(vec![
+        Route::html("/default-client-scopes/s1", 204, ""),
+        Route::html("/optional-client-scopes/s2", 204, ""),
+        Route::json(
+            "/optional-client-scopes",
+            200,
+            json!([{"id": "s2", "name": "phone"}]),
+        ),
+    ])
+    .await;
+
+    admin
+        .add_client_scope_to_client("8c1f", "s1", ScopeKind::Default)
+        .await
+        .unwrap();
+    admin
+        .remove_client_scope_from_client("8c1f", "s2", ScopeKind::Optional)
+        .await
+        .unwrap();
+    let optional = admin
+        .client_scopes_of_client("8c1f", ScopeKind::Optional)
+        .await
+        .unwrap();
+    assert_eq!(optional[0].name.as_deref(), Some("phone"));
+
+    assert_eq!(
+        requests(),
+        [
+            "PUT /admin/realms/test/clients/8c1f/default-client-scopes/s1",
+            "DELETE /admin/realms/test/clients/8c1f/optional-client-scopes/s2",
+            "GET /admin/realms/test/clients/8c1f/optional-client-scopes",
+        ]
+    );
+}
+
+#[test]
+fn server_urls_must_be_able_to_hold_a_path() {
+    let client = service_client(configuration("http://keycloak"));
+    let result = KeycloakAdmin::new(client, "mailto:admin@example.com", "test");
+    assert!(matches!(result, Err(ClientError::InvalidUrlError(_))));
+}
@@ -0,0 +1,169 @@
+//! Requirements evaluated against the claims of a token, and the denials they report.
+
+use keycloak_oauth::client::{Denial, KeycloakClaims, Requirement};
+use serde_json::json;
+
+fn claims() -> KeycloakClaims {
+    serde_json::from_value(json!({
+        "sub": "user-1",
+        "exp": 2000000000,
+        "iat": 1700000000,
+        "iss": "http://keycloak/realms/test",
+        "azp": "waves-ui",
+        "email": "alice@example.com",
+        "scope": "openid  profile waves:read",
+        "realm_access": {"roles": ["user"]},
+        "resource_access": {
+            "waves-api": {"roles": ["reader"]},
+            "other-api": {"roles": ["publisher"]}
+        },
+        "groups": ["/staff/ops"],
+        "tenant": "acme"
+    }))
+    .unwrap()
+}
+
+#[test]
+fn typed_claims_are_evaluated() {
+    let claims = claims();
+    assert!(
+        Requirement::claim("azp", |azp| azp == Some(&json!("waves-ui")))
+            .evaluate_claims(&claims)
+            .is_ok()
+    );
+    assert_eq!(
+        Requirement::claim("email", |email| email
+            .and_then(|email| email.as_str())
+            .is_some_and(|email| email.ends_with("@corp.example")))
+        .evaluate_claims(&claims),
+        Err(Denial::ClaimRejected("email".into()))
+    );
+}
+
+#[test]
+fn custom_claims_are_evaluated() {
+    assert!(
+        Requirement::claim("tenant", |tenant| tenant == Some(&json!("acme")))
+            .evaluate_claims(&claims())
+            .is_ok()
+    );
+}
+
+#[test]
+fn absent_claims_are_none() {
+    let claims = claims();
+    // typed, but not in this token
+    assert!(
+        Requirement::claim("preferred_username", |name| name.is_none())
+            .evaluate_claims(&claims)
+            .is_ok()
+    );
+    assert!(
+        Requirement::claim("department", |department| department.is_none())
+            .evaluate_claims(&claims)
+            .is_ok()
+    );
+}
+
+#[test]
+fn realm_roles_are_evaluated() {
+    let claims = claims();
+    assert!(Requirement::realm_role("user")
+        .evaluate_claims(&claims)
+        .is_ok());
+    assert_eq!(
+        Requirement::realm_role("admin").evaluate_claims(&claims),
+        Err(Denial::MissingRealmRole("admin".into()))
+    );
+}
+
+#[test]
+fn client_roles_belong_to_their_client() {
+    let claims = claims();
+    assert!(Requirement::client_role("waves-api", "reader")
+        .evaluate_claims(&claims)
+        .is_ok());
+    // held, but on another client
+    assert_eq!(
+        Requirement::client_role("waves-api", "publisher").evaluate_claims(&claims),
+        Err(Denial::MissingClientRole {
+            client_id: "waves-api".into(),
+            role: "publisher".into()
+        })
+    );
+}
+
+#[test]
+fn scopes_are_split_on_spaces() {
+    let claims = claims();
+    assert!(Requirement::scope("profile")
+        .evaluate_claims(&claims)
+        .is_ok());
+    assert!(Requirement::scope("waves:read")
+        .evaluate_claims(&claims)
+        .is_ok());
+    assert_eq!(
+        Requirement::scope("openid profile").evaluate_claims(&claims),
+        Err(Denial::MissingScope("openid profile".into()))
+    );
+}
+
+#[test]
+fn group_paths_match_bare_names() {
+    let claims = claims();
+    assert!(Requirement::group("staff/ops")
+        .evaluate_claims(&claims)
+        .is_ok());
+    assert!(Requirement::group("/staff/ops")
+        .evaluate_claims(&claims)
+        .is_ok());
+    assert_eq!(
+        Requirement::group("ops").evaluate_claims(&claims),
+        Err(Denial::MissingGroup("ops".into()))
+    );
+}
+
+#[test]
+fn all_reports_every_failure() {
+    let requirement = Requirement::realm_role("user")
+        .and(Requirement::realm_role("admin"))
+        .and(Requirement::scope("waves:write"));
+    assert_eq!(
+        requirement.evaluate_claims(&claims()),
+        Err(Denial::All(vec![
+            Denial::MissingRealmRole("admin".into()),
+            Denial::MissingScope("waves:write".into()),
+        ]))
+    );
+}
+
+#[test]
+fn any_nests_the_denials_of_every_alternative() {
+    let requirement = Requirement::realm_role("admin").or(Requirement::all([
+        Requirement::client_role("waves-api", "publisher"),
+        Requirement::scope("waves:write"),
+    ]));
+    let denial = requirement.evaluate_claims(&claims()).unwrap_err();
+    assert_eq!(
+        denial,
+        Denial::Any(vec![
+            Denial::MissingRealmRole("admin".into()),
+            Denial::All(vec![
+                Denial::MissingClientRole {
+                    client_id: "waves-api".into(),
+                    role: "publisher".into()
+                },
+                Denial::MissingScope("waves:write".into()),
+            ]),
+        ])
+    );
+    assert_eq!(
+        denial.to_string(),
+        "none of (missing realm role admin, missing client role publisher on waves-api and missing scope waves:write)"
+    );
+
+    assert!(requirement
+        .or(Requirement::realm_role("user"))
+        .evaluate_claims(&claims())
+        .is_ok());
+}
@@ -0,0 +1,163 @@
+//! The authorization code flow, with the browser played by a task following the prompted url.
+
+mod common;
+
+use std::{
+    collections::HashMap,
+    sync::{Arc, Mutex},
+    time::Duration,
+};
+
+use common::*;
+use keycloak_oauth::client::ClientError;
+use oauth2::{
+    url::{form_urlencoded, Url},
+    PkceCodeChallenge, PkceCodeVerifier, TokenResponse,
+};
+use serde_json::json;
+
+fn query(url: &Url) -> HashMap<String, String> {
+    url.query_pairs().into_owned().collect()
+}
+
+/// The redirect uri of `authorize_url` with `answer` and the url's state, unless `answer` has
+/// its own.
+fn callback(authorize_url: &Url, answer: &[(&str, &str)]) -> Url {
+    let params = query(authorize_url);
+    let mut callback = Url::parse(&params["redirect_uri"]).unwrap();
+    callback.query_pairs_mut().extend_pairs(answer);
+    if !answer.iter().any(|(name, _)| *name == "state") {
+        callback
+            .query_pairs_mut()
+            .append_pair("state", &params["state"]);
+    }
+    callback
+}
+
+/// Plays the browser: calls the redirect uri of `authorize_url` with `answer`.
+fn follow(authorize_url: &Url, answer: &[(&str, &str)]) {
+    let callback = callback(authorize_url, answer);
+    tokio::spawn(async move {
+        let _ = reqwest::get(callback).await;
+    });
+}
+
+async fn server() -> (String, Requests) {
+    recording_stub_server(vec![Route::json(
+        "/token",
+        200,
+        json!({"access_token": "signed-in", "token_type": "Bearer", "expires_in": 300}),
+    )])
+    .await
+}
+
+#[tokio::test]
+async fn the_code_is_exchanged_with_the_pkce_verifier() {
+    let (server, requests) = server().await;
+    let client = authorization_code_client(configuration(&server));
+
+    let prompted = Mutex::new(None);
+    let token = client
+        .authenticate_with_prompt(|authorize_url| {
+            follow(authorize_url, &[("code", "the-code")]);
+            *prompted.lock().unwrap() = Some(authorize_url.clone());
+        })
+        .await
+        .unwrap();
+
+    assert_eq!(token.access_token().secret(), "signed-in");
+    assert_eq!(
+        client.load_cached_token().await.unwrap().access_token,
+        "signed-in"
+    );
+
+    let authorize_url = prompted.into_inner().unwrap().unwrap();
+    assert!(authorize_url.as_str().starts_with(&format!(
+        "{}/realms/{}/protocol/openid-connect/auth?",
+        server, REALM
+    )));
+    let params = query(&authorize_url);
+    assert_eq!(params["response_type"], "code");
+    assert_eq!(params["client_id"], CLIENT_ID);
+    assert_eq!(params["code_challenge_method"], "S256");
+
+    let exchange: HashMap<String, String> =
+        form_urlencoded::parse(requests.body("/token").unwrap().as_bytes())
+            .into_owned()
+            .collect();
+    assert_eq!(exchange["grant_type"], "authorization_code");
+    assert_eq!(exchange["code"], "the-code");
+    assert_eq!(exchange["redirect_uri"], params["redirect_uri"]);
+    // the verifier sent with the code is the one the challenge was derived from
+    let verifier = PkceCodeVerifier::new(exchange["code_verifier"].clone());
+    assert_eq!(
+        PkceCodeChallenge::from_code_verifier_sha256(&verifier).as_str(),
+        params["code_challenge"]
+    );
+}
+
+#[tokio::test]
+async fn a_foreign_state_is_ignored_until_the_real_callback() {
+    let (server, requests) = server().await;
+    let client = authorization_code_client(configuration(&server));
+
+    let forged_status = Arc::new(Mutex::new(None));
+    let status = forged_status.clone();
+    let token = client
+        .authenticate_with_prompt(|authorize_url| {
+            let forged = callback(
+                authorize_url,
+                &[("error", "access_denied"), ("state", "forged")],
+            );
+            let genuine = callback(authorize_url, &[("code", "the-code")]);
+            tokio::spawn(async move {
+                let response = reqwest::get(forged).await.unwrap();
+                *status.lock().unwrap() = Some(response.status().as_u16());
+                let _ = reqwest::get(genuine).await;
+            });
+        })
+        .await
+        .unwrap();
+
+    assert_eq!(token.access_token().secret(), "signed-in");
+    assert_eq!(*forged_status.lock().unwrap(), Some(400));
+    assert!(requests.body("/token").unwrap().contains("code=the-code"));
+}
+
+#[tokio::test]
+async fn the_wait_gives_up_after_the_timeout() {
+    let (server, requests) = server().await;
+    let client = authorization_code_client(configuration(&server))
+        .with_authorization_timeout(Duration::from_millis(200));
+
+    let result = client
+        .authenticate_with_prompt(|authorize_url| {
+            follow(authorize_url, &[("code", "the-code"), ("state", "forged")]);
+        })
+        .await;
+
+    assert!(matches!(
+        result,
+        Err(ClientError::AuthorizationTimeoutError(timeout)) if timeout == Duration::from_millis(200)
+    ));
+    assert!(requests.lines().is_empty());
+    assert!(client.store.load().await.unwrap().is_none());
+}
+
+#[tokio::test]
+async fn a_denied_login_is_reported() {
+    let (server, requests) = server().await;
+    let client = authorization_code_client(configuration(&server));
+
+    let result = client
+        .authenticate_with_prompt(|authorize_url| {
+            follow(authorize_url, &[("error", "access_denied")]);
+        })
+        .await;
+
+    assert!(matches!(
+        result,
+        Err(ClientError::AuthorizationCallbackError(error)) if error == "access_denied"
+    ));
+    assert!(requests.lines().is_empty());
+}
@@ -0,0 +1,214 @@
+//! `KeycloakAuthLayer` in front of an axum router, with locally minted tokens.
+
+#![cfg(feature = "axum")]
+
+mod common;
+
+use axum::{
+    body::Body,
+    http::{header, Request, StatusCode},
+    routing::get,
+    Router,
+};
+use common::{stub_server, tokens::*, unreachable_url, Route, CLIENT_ID};
+use jsonwebtoken::Algorithm;
+use keycloak_oauth::client::{
+    Authenticated, KeyCache, KeycloakAuthLayer, KeycloakClaims, Requirement, TokenVerifier,
+};
+use serde_json::{json, Value};
+use tower_service::Service;
+
+async fn me(Authenticated(claims): Authenticated<KeycloakClaims>) -> String {
+    claims.preferred_username.unwrap_or_default()
+}
+
+fn app(layer: KeycloakAuthLayer) -> Router {
+    Router::new()
+        .route("/me", get(me))
+        .layer(layer.realm("test"))
+}
+
+struct Answer {
+    status: StatusCode,
+    challenge: Option<String>,
+    body: String,
+}
+
+async fn call(mut app: Router, authorization: Option<String>) -> Answer {
+    let mut request = Request::get("/me");
+    if let Some(authorization) = authorization {
+        request = request.header(header::AUTHORIZATION, authorization);
+    }
+    let response = app
+        .call(request.body(Body::empty()).unwrap())
+        .await
+        .unwrap();
+    let status = response.status();
+    let challenge = response
+        .headers()
+        .get(header::WWW_AUTHENTICATE)
+        .map(|value| value.to_str().unwrap().to_string());
+    let body = axum::body::to_bytes(response.into_body(), usize::MAX)
+        .await
+        .unwrap();
+    Answer {
+        status,
+        challenge,
+        body: String::from_utf8(body.to_vec()).unwrap(),
+    }
+}
+
+async fn call_with(layer: KeycloakAuthLayer, alg: Algorithm, claims: &Value) -> Answer {
+    call(app(layer), Some(format!("Bearer {}", mint(alg, claims)))).await
+}
+
+fn claims_with(changes: Value) -> Value {
+    let mut claims = claims();
+    for (key, value) in changes.as_object().unwrap() {
+        claims[key] = value.clone();
+    }
+    claims
+}
+
+fn assert_invalid_token(answer: &Answer) {
+    assert_eq!(answer.status, StatusCode::UNAUTHORIZED);
+    let challenge = answer.challenge.as_deref().unwrap();
+    assert!(
+        challenge.starts_with(r#"Bearer realm="test", error="invalid_token""#),
+        "{}",
+        challenge
+    );
+}
+
+#[tokio::test]
+async fn valid_tokens_reach_the_handler() {
+    for alg in [Algorithm::RS256, Algorithm::ES256] {
+        let answer = call_with(KeycloakAuthLayer::new(verifier()), alg, &claims()).await;
+        assert_eq!(answer.status, StatusCode::OK);
+        assert_eq!(answer.body, "alice");
+    }
+}
+
+#[tokio::test]
+async fn missing_token_gets_a_bare_challenge() {
+    let answer = call(app(KeycloakAuthLayer::new(verifier())), None).await;
+    assert_eq!(answer.status, StatusCode::UNAUTHORIZED);
+    assert_eq!(answer.challenge.as_deref(), Some(r#"Bearer realm="test""#));
+}
+
+#[tokio::test]
+async fn malformed_header_is_a_bad_request() {
+    let answer = call(
+        app(KeycloakAuthLayer::new(verifier())),
+        Some("Basic YWxpY2U6d29uZGVybGFuZA==".into()),
+    )
+    .await;
+    assert_eq!(answer.status, StatusCode::BAD_REQUEST);
+    assert!(answer
+        .challenge
+        .unwrap()
+        .contains(r#"error="invalid_request""#));
+}
+
+#[tokio::test]
+async fn expired_token_is_unauthorized() {
+    let expired = claims_with(json!({ "exp": chrono::Utc::now().timestamp() - 120 }));
+    let answer = call_with(
+        KeycloakAuthLayer::new(verifier()),
+        Algorithm::RS256,
+        &expired,
+    )
+    .await;
+    assert_invalid_token(&answer);
+    assert!(answer.challenge.unwrap().contains("ExpiredSignature"));
+}
+
+#[tokio::test]
+async fn wrong_issuer_is_unauthorized() {
+    let other_realm = claims_with(json!({ "iss": "http://keycloak/realms/other" }));
+    let answer = call_with(
+        KeycloakAuthLayer::new(verifier()),
+        Algorithm::ES256,
+        &other_realm,
+    )
+    .await;
+    assert_invalid_token(&answer);
+    assert!(answer.challenge.unwrap().contains("InvalidIssuer"));
+}
+
+#[tokio::test]
+async fn wrong_audience_is_unauthorized() {
+    let other_client = claims_with(json!({ "aud": "other-client" }));
+    let answer = call_with(
+        KeycloakAuthLayer::new(verifier()),
+        Algorithm::RS256,
+        &other_client,
+    )
+    .await;
+    assert_invalid_token(&answer);
+    assert!(answer.challenge.unwrap().contains("InvalidAudience"));
+}
+
+#[tokio::test]
+async fn disallowed_algorithm_is_unauthorized() {
+    let layer = KeycloakAuthLayer::new(verifier().allowed_algorithms(&[Algorithm::RS256]));
+    let answer = call_with(layer, Algorithm::ES256, &claims()).await;
+    assert_invalid_token(&answer);
+    assert!(answer
+        .challenge
+        .unwrap()
+        .contains(r#"error_description="signing algorithm not allowed""#));
+}
+
+#[tokio::test]
+async fn unmet_requirement_is_forbidden() {
+    let layer = KeycloakAuthLayer::new(verifier()).require(Requirement::realm_role("admin"));
+    let answer = call_with(layer, Algorithm::RS256, &claims()).await;
+    assert_eq!(answer.status, StatusCode::FORBIDDEN);
+    assert!(answer
+        .challenge
+        .unwrap()
+        .starts_with(r#"Bearer realm="test", error="insufficient_scope""#));
+}
+
+/// A verifier for the test realm fetching its keys from `jwks_url`.
+fn fetching_verifier(jwks_url: String) -> TokenVerifier {
+    TokenVerifier::new(jwks_url, KeyCache::new().into_shared())
+        .audience([CLIENT_ID])
+        .issuer([ISSUER])
+        .allowed_algorithms(&[Algorithm::RS256])
+}
+
+#[tokio::test]
+async fn unreachable_jwks_is_service_unavailable() {
+    let layer = KeycloakAuthLayer::new(fetching_verifier(unreachable_url().await));
+    let answer = call_with(layer, Algorithm::RS256, &claims()).await;
+    assert_eq!(answer.status, StatusCode::SERVICE_UNAVAILABLE);
+    // no challenge, the client has no reason to drop its token
+    assert_eq!(answer.challenge, None);
+}
+
+#[tokio::test]
+async fn failing_jwks_endpoint_is_service_unavailable() {
+    let server = stub_server(vec![Route::json(
+        "/certs",
+        503,
+        json!({ "error": "unavailable" }),
+    )])
+    .await;
+    let layer = KeycloakAuthLayer::new(fetching_verifier(format!("{}/certs", server)));
+    let answer = call_with(layer, Algorithm::RS256, &claims()).await;
+    assert_eq!(answer.status, StatusCode::SERVICE_UNAVAILABLE);
+}
+
+#[tokio::test]
+async fn extractor_without_the_layer_is_a_server_error() {
+    let app = Router::new().route("/me", get(me));
+    let answer = call(
+        app,
+        Some(format!("Bearer {}", mint(Algorithm::RS256, &claims()))),
+    )
+    .await;
+    assert_eq!(answer.status, StatusCode::INTERNAL_SERVER_ERROR);
+    assert_eq!(answer.challenge, None);
+}
@@ -0,0 +1,99 @@
+//! `KeycloakMiddleware` keeps requests authenticated across token expiry.
+
+#![cfg(feature = "reqwest-middleware")]
+
+mod common;
+
+use std::sync::Arc;
+
+use chrono::Utc;
+use common::*;
+use keycloak_oauth::client::{CachedToken, KeycloakMiddleware};
+use serde_json::json;
+
+fn token_response(access_token: &str) -> serde_json::Value {
+    json!({"access_token": access_token, "token_type": "Bearer", "expires_in": 300})
+}
+
+fn expired(access_token: &str, refresh_token: Option<&str>) -> CachedToken {
+    CachedToken {
+        access_token: access_token.into(),
+        expires_at: Utc::now() - chrono::Duration::seconds(10),
+        refresh_token: refresh_token.map(str::to_string),
+    }
+}
+
+fn http(
+    middleware: KeycloakMiddleware<impl keycloak_oauth::client::AccessTokenProvider + 'static>,
+) -> reqwest_middleware::ClientWithMiddleware {
+    reqwest_middleware::ClientBuilder::new(reqwest::Client::new())
+        .with(middleware)
+        .build()
+}
+
+async fn authorization(http: &reqwest_middleware::ClientWithMiddleware, server: &str) -> String {
+    let response = http.get(format!("{}/api", server)).send().await.unwrap();
+    assert_eq!(response.status(), 200);
+    response.text().await.unwrap()
+}
+
+#[tokio::test]
+async fn client_credentials_requests_span_token_expiry() {
+    let server = stub_server(vec![
+        Route::json("/token", 200, token_response("granted")),
+        Route::echo_authorization("/api"),
+    ])
+    .await;
+    let client = Arc::new(service_client(configuration(&server)));
+    let http = http(KeycloakMiddleware::new(Arc::clone(&client)));
+
+    assert_eq!(authorization(&http, &server).await, "Bearer granted");
+
+    // the first token expired, and the grant issues no refresh token
+    client.store.save(&expired("stale", None)).await.unwrap();
+    assert_eq!(authorization(&http, &server).await, "Bearer granted");
+    assert_eq!(
+        client.load_cached_token().await.unwrap().access_token,
+        "granted"
+    );
+}
+
+#[tokio::test]
+async fn user_tokens_are_refreshed_ahead_of_expiry() {
+    let server = stub_server(vec![
+        Route::json("/token", 200, token_response("refreshed")),
+        Route::echo_authorization("/api"),
+    ])
+    .await;
+    let client = Arc::new(password_client(configuration(&server)));
+    client
+        .store
+        .save(&CachedToken {
+            access_token: "expiring".into(),
+            expires_at: Utc::now() + chrono::Duration::seconds(10),
+            refresh_token: Some("refresh".into()),
+        })
+        .await
+        .unwrap();
+    let http = http(KeycloakMiddleware::new(Arc::clone(&client)));
+
+    assert_eq!(authorization(&http, &server).await, "Bearer refreshed");
+}
+
+#[tokio::test]
+async fn failing_grant_fails_the_request() {
+    let server = stub_server(vec![
+        Route::json("/token", 401, json!({"error": "invalid_client"})),
+        Route::echo_authorization("/api"),
+    ])
+    .await;
+    let http = http(KeycloakMiddleware::new(service_client(configuration(
+        &server,
+    ))));
+    let error = http
+        .get(format!("{}/api", server))
+        .send()
+        .await
+        .unwrap_err();
+    assert!(matches!(error, reqwest_middleware::Error::Middleware(_)));
+}
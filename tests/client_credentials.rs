@@ -0,0 +1,68 @@
+//! A client credentials client replaces an expired token by performing the grant again, as
+//! Keycloak issues no refresh token for it by default.
+
+mod common;
+
+use chrono::Utc;
+use common::*;
+use keycloak_oauth::client::{CachedToken, KeycloakClient, WithClientCredentials};
+use serde_json::json;
+
+fn cached(expires_in: i64) -> CachedToken {
+    CachedToken {
+        access_token: "stale".into(),
+        expires_at: Utc::now() + chrono::Duration::seconds(expires_in),
+        refresh_token: None,
+    }
+}
+
+async fn client() -> (KeycloakClient<WithClientCredentials>, Requests) {
+    let (server, requests) = recording_stub_server(vec![Route::json(
+        "/token",
+        200,
+        json!({"access_token": "granted", "token_type": "Bearer", "expires_in": 300}),
+    )])
+    .await;
+    (service_client(configuration(&server)), requests)
+}
+
+#[tokio::test]
+async fn expired_token_is_replaced_by_a_new_grant() {
+    let (client, requests) = client().await;
+    client.store.save(&cached(-10)).await.unwrap();
+
+    assert_eq!(
+        client.verify_and_refresh_access_token().await.unwrap(),
+        "granted"
+    );
+    assert!(requests
+        .body("/token")
+        .unwrap()
+        .contains("grant_type=client_credentials"));
+    assert_eq!(
+        client.load_cached_token().await.unwrap().access_token,
+        "granted"
+    );
+}
+
+#[tokio::test]
+async fn missing_token_is_granted() {
+    let (client, requests) = client().await;
+    assert_eq!(
+        client.verify_and_refresh_access_token().await.unwrap(),
+        "granted"
+    );
+    assert_eq!(requests.lines().len(), 1);
+}
+
+#[tokio::test]
+async fn valid_token_is_used_as_is() {
+    let (client, requests) = client().await;
+    client.store.save(&cached(300)).await.unwrap();
+
+    assert_eq!(
+        client.verify_and_refresh_access_token().await.unwrap(),
+        "stale"
+    );
+    assert!(requests.lines().is_empty());
+}
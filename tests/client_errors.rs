@@ -0,0 +1,272 @@
+//! Misconfigured clients and failing servers end in a `ClientError`, never a panic.
+
+mod common;
+
+use chrono::Utc;
+use common::*;
+use keycloak_oauth::client::{
+    CachedToken, ClientConfiguration, ClientCredentials, ClientError, ErrorCategory,
+    KeycloakClient, KeycloakError, KeycloakErrorCode,
+};
+use serde_json::json;
+
+fn device_authorization() -> serde_json::Value {
+    json!({
+        "device_code": "device-code",
+        "user_code": "ABCD-EFGH",
+        "verification_uri": "http://keycloak/device",
+        "expires_in": 5,
+        "interval": 0
+    })
+}
+
+fn expired_token() -> CachedToken {
+    CachedToken {
+        access_token: "stale".into(),
+        expires_at: Utc::now() - chrono::Duration::seconds(10),
+        refresh_token: Some("refresh".into()),
+    }
+}
+
+#[test]
+fn missing_token_url_is_a_config_error() {
+    let configuration = ClientConfiguration {
+        token_url: None,
+        ..configuration("http://keycloak")
+    };
+    let result = KeycloakClient::try_new(client_credentials_app_config(configuration));
+    assert!(matches!(
+        result,
+        Err(ClientError::MissingConfigError("token_url"))
+    ));
+}
+
+#[test]
+fn malformed_auth_url_is_rejected() {
+    let mut app_config = client_credentials_app_config(configuration("http://keycloak"));
+    app_config.auth_url = "not a url".into();
+    let result = KeycloakClient::try_new(app_config);
+    assert!(
+        matches!(result, Err(ClientError::InvalidUrlError(url)) if url.starts_with("not a url"))
+    );
+}
+
+#[test]
+fn malformed_token_url_is_rejected() {
+    let configuration = ClientConfiguration {
+        token_url: Some("keycloak/token".into()),
+        ..configuration("http://keycloak")
+    };
+    let result = KeycloakClient::try_from(client_credentials_app_config(configuration));
+    assert!(matches!(result, Err(ClientError::InvalidUrlError(_))));
+}
+
+#[test]
+fn client_credentials_name_the_client() {
+    let configuration = ClientConfiguration {
+        client_id: Some("from-env".into()),
+        ..configuration("http://keycloak")
+    };
+    let client = service_client(configuration);
+    assert_eq!(client.config.client_id.as_deref(), Some(CLIENT_ID));
+}
+
+#[test]
+fn client_credentials_for_another_client_are_rejected() {
+    let mut app_config = client_credentials_app_config(configuration("http://keycloak"));
+    app_config.credential = ClientCredentials::new("other-client", "secret");
+    assert!(matches!(
+        KeycloakClient::try_new(app_config),
+        Err(ClientError::ClientIdMismatchError { .. })
+    ));
+}
+
+#[test]
+fn token_verifier_needs_a_jwks_url() {
+    let client = service_client(ClientConfiguration {
+        jwks_url: None,
+        ..configuration("http://keycloak")
+    });
+    assert!(matches!(
+        client.token_verifier(),
+        Err(ClientError::MissingConfigError("jwks_url"))
+    ));
+}
+
+#[tokio::test]
+async fn device_flow_needs_a_device_authorization_url() {
+    let client = device_client(ClientConfiguration {
+        device_authorization_url: None,
+        ..configuration("http://keycloak")
+    });
+    assert!(matches!(
+        client.initiate_device_flow().await,
+        Err(ClientError::MissingConfigError("device_authorization_url"))
+    ));
+}
+
+#[tokio::test]
+async fn device_flow_reports_an_unreachable_server() {
+    let client = device_client(configuration(&unreachable_url().await));
+    assert!(matches!(
+        client.initiate_device_flow().await,
+        Err(ClientError::TransportError(_))
+    ));
+}
+
+#[tokio::test]
+async fn device_flow_reports_a_rejected_client() {
+    let server = stub_server(vec![Route::json(
+        "/auth/device",
+        401,
+        json!({"error": "invalid_client", "error_description": "Invalid client credentials"}),
+    )])
+    .await;
+    let client = device_client(configuration(&server));
+    match client.initiate_device_flow().await {
+        Err(ClientError::KeycloakError(e)) => {
+            assert_eq!(e.error, "invalid_client");
+            assert_eq!(e.status, Some(401));
+        }
+        other => panic!("expected a Keycloak error, got {:?}", other.err()),
+    }
+}
+
+#[tokio::test]
+async fn device_polling_stops_when_access_is_denied() {
+    let server = stub_server(vec![
+        Route::json("/auth/device", 200, device_authorization()),
+        Route::json("/token", 400, json!({"error": "access_denied"})),
+    ])
+    .await;
+    let client = device_client(configuration(&server));
+    let result = client.authenticate().await;
+    assert!(
+        matches!(&result, Err(ClientError::KeycloakError(e)) if e.category() == ErrorCategory::Forbidden),
+        "{:?}",
+        result.err()
+    );
+}
+
+#[tokio::test]
+async fn device_polling_stops_on_other_token_errors() {
+    let server = stub_server(vec![
+        Route::json("/auth/device", 200, device_authorization()),
+        Route::json("/token", 401, json!({"error": "invalid_client"})),
+    ])
+    .await;
+    let client = device_client(configuration(&server));
+    assert!(matches!(
+        client.authenticate().await,
+        Err(ClientError::KeycloakError(e)) if e.code() == KeycloakErrorCode::InvalidClient
+    ));
+}
+
+#[tokio::test]
+async fn password_flow_needs_credentials() {
+    let client = password_client(ClientConfiguration {
+        password: None,
+        ..configuration("http://keycloak")
+    });
+    assert!(matches!(
+        client.initiate_password_flow().await,
+        Err(ClientError::NoPresentCredentialsError)
+    ));
+}
+
+#[tokio::test]
+async fn password_flow_reports_an_unreachable_server() {
+    let client = password_client(configuration(&unreachable_url().await));
+    assert!(matches!(
+        client.initiate_password_flow().await,
+        Err(ClientError::TransportError(_))
+    ));
+}
+
+#[tokio::test]
+async fn password_flow_returns_the_server_error() {
+    let server = stub_server(vec![Route::json(
+        "/token",
+        401,
+        json!({"error": "invalid_grant", "error_description": "Invalid user credentials"}),
+    )])
+    .await;
+    let client = password_client(configuration(&server));
+    match client.initiate_password_flow().await {
+        Err(ClientError::KeycloakError(e)) => {
+            assert_eq!(e.error, "invalid_grant");
+            assert_eq!(
+                e.error_description.as_deref(),
+                Some("Invalid user credentials")
+            );
+            assert_eq!(e.status, Some(401));
+        }
+        other => panic!("expected a Keycloak error, got {:?}", other.err()),
+    }
+}
+
+#[tokio::test]
+async fn client_credentials_survive_a_proxy_error_page() {
+    let server = stub_server(vec![Route::html(
+        "/token",
+        502,
+        "<html><body>Bad Gateway</body></html>",
+    )])
+    .await;
+    let client = service_client(configuration(&server));
+    let error = client.authenticate().await.unwrap_err();
+    assert!(matches!(
+        error,
+        ClientError::InvalidResponseError {
+            status: Some(502),
+            ..
+        }
+    ));
+    assert!(error.is_retryable());
+}
+
+#[tokio::test]
+async fn refresh_reports_an_unreachable_server() {
+    let client = service_client(configuration(&unreachable_url().await));
+    client.store.save(&expired_token()).await.unwrap();
+    assert!(matches!(
+        client.verify_and_refresh_access_token().await,
+        Err(ClientError::TransportError(_))
+    ));
+}
+
+#[tokio::test]
+async fn revocation_reports_an_unreachable_server() {
+    let client = service_client(configuration(&unreachable_url().await));
+    assert!(matches!(
+        client.revoke_token("token", None).await,
+        Err(ClientError::TransportError(_))
+    ));
+}
+
+#[tokio::test]
+async fn revocation_reports_a_server_error() {
+    let server = stub_server(vec![Route::json(
+        "/revoke",
+        500,
+        json!({"error": "unknown_error"}),
+    )])
+    .await;
+    let client = service_client(configuration(&server));
+    assert!(matches!(
+        client.revoke_token("token", None).await,
+        Err(ClientError::KeycloakError(KeycloakError {
+            status: Some(500),
+            ..
+        }))
+    ));
+}
+
+#[tokio::test]
+async fn introspection_reports_an_unreachable_server() {
+    let client = service_client(configuration(&unreachable_url().await));
+    assert!(matches!(
+        client.introspect("token").await,
+        Err(ClientError::TransportError(_))
+    ));
+}
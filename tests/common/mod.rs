@@ -0,0 +1,321 @@
+//! A canned-response HTTP server standing in for Keycloak, and clients pointed at it.
+
+#![allow(dead_code)]
+
+pub mod tokens;
+
+use std::sync::{
+    atomic::{AtomicUsize, Ordering},
+    Arc, Mutex,
+};
+
+use keycloak_oauth::client::{
+    AppConfig, AuthorizationCodeCredential, ClientConfiguration, ClientCredentials,
+    DeviceCodeCredential, KeycloakClient, ResourceOwnerPasswordCredential, WithAuthorizationCode,
+    WithClientCredentials, WithDeviceCredentials, WithOwnerCredentials,
+};
+use tokio::{
+    io::{AsyncReadExt, AsyncWriteExt},
+    net::{TcpListener, TcpStream},
+};
+
+pub const CLIENT_ID: &str = "test-client";
+pub const REALM: &str = "test";
+
+/// One canned answer: every request whose path ends with `path` gets `status` and `body`.
+#[derive(Clone)]
+pub struct Route {
+    pub path: &'static str,
+    pub status: u16,
+    pub content_type: &'static str,
+    pub body: String,
+    /// Answer with the request's `Authorization` header instead of `body`
+    pub echo_authorization: bool,
+    pub headers: Vec<(&'static str, String)>,
+    /// Answers after the first, in order, the last one repeating
+    pub then: Vec<(u16, String)>,
+    /// How long to wait before answering
+    pub delay: std::time::Duration,
+    hits: Arc<AtomicUsize>,
+}
+
+impl Route {
+    pub fn json(path: &'static str, status: u16, body: serde_json::Value) -> Self {
+        Self {
+            path,
+            status,
+            content_type: "application/json",
+            body: body.to_string(),
+            echo_authorization: false,
+            headers: Vec::new(),
+            then: Vec::new(),
+            delay: std::time::Duration::ZERO,
+            hits: Arc::default(),
+        }
+    }
+
+    pub fn html(path: &'static str, status: u16, body: &str) -> Self {
+        Self {
+            path,
+            status,
+            content_type: "text/html",
+            body: body.to_string(),
+            echo_authorization: false,
+            headers: Vec::new(),
+            then: Vec::new(),
+            delay: std::time::Duration::ZERO,
+            hits: Arc::default(),
+        }
+    }
+
+    /// A `200` whose body is the `Authorization` header the request carried.
+    pub fn echo_authorization(path: &'static str) -> Self {
+        Self {
+            path,
+            status: 200,
+            content_type: "text/plain",
+            body: String::new(),
+            echo_authorization: true,
+            headers: Vec::new(),
+            then: Vec::new(),
+            delay: std::time::Duration::ZERO,
+            hits: Arc::default(),
+        }
+    }
+
+    /// Answers the next request with `status` and `body`, e.g. a token after a pending poll.
+    pub fn then_json(mut self, status: u16, body: serde_json::Value) -> Self {
+        self.then.push((status, body.to_string()));
+        self
+    }
+
+    /// The status and body of the next answer.
+    fn next_answer(&self) -> (u16, String) {
+        let hit = self.hits.fetch_add(1, Ordering::SeqCst);
+        match hit.checked_sub(1) {
+            None => (self.status, self.body.clone()),
+            Some(i) => self.then[i.min(self.then.len() - 1)].clone(),
+        }
+    }
+
+    /// Answers only after `delay`, so concurrent callers overlap.
+    pub fn with_delay(mut self, delay: std::time::Duration) -> Self {
+        self.delay = delay;
+        self
+    }
+
+    pub fn with_header(mut self, name: &'static str, value: impl Into<String>) -> Self {
+        self.headers.push((name, value.into()));
+        self
+    }
+}
+
+/// The request lines (`GET /path?query`) a stub server received, in order, with their bodies.
+#[derive(Clone, Default)]
+pub struct Requests(Arc<Mutex<Vec<(String, String)>>>);
+
+impl Requests {
+    pub fn lines(&self) -> Vec<String> {
+        self.0
+            .lock()
+            .unwrap()
+            .iter()
+            .map(|(line, _)| line.clone())
+            .collect()
+    }
+
+    /// The body of the first request whose line ends with `path`.
+    pub fn body(&self, path: &str) -> Option<String> {
+        self.0
+            .lock()
+            .unwrap()
+            .iter()
+            .find(|(line, _)| line.ends_with(path))
+            .map(|(_, body)| body.clone())
+    }
+}
+
+/// Serves `routes` on an ephemeral loopback port until the test's runtime shuts down.
+/// Requests matching no route get a `404`.
+pub async fn stub_server(routes: Vec<Route>) -> String {
+    recording_stub_server(routes).await.0
+}
+
+/// Like `stub_server`, also returning the requests it receives.
+pub async fn recording_stub_server(routes: Vec<Route>) -> (String, Requests) {
+    let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let url = format!("http://{}", listener.local_addr().unwrap());
+    let requests = Requests::default();
+    let recorded = requests.clone();
+    tokio::spawn(async move {
+        while let Ok((stream, _)) = listener.accept().await {
+            tokio::spawn(answer(stream, routes.clone(), recorded.clone()));
+        }
+    });
+    (url, requests)
+}
+
+async fn answer(mut stream: TcpStream, routes: Vec<Route>, requests: Requests) {
+    let mut request = Vec::new();
+    let mut buf = [0; 4096];
+    // the head, then as much body as Content-Length announces
+    let body_start = loop {
+        let Ok(n) = stream.read(&mut buf).await else {
+            return;
+        };
+        if n == 0 {
+            return;
+        }
+        request.extend_from_slice(&buf[..n]);
+        if let Some(end) = request.windows(4).position(|w| w == b"\r\n\r\n") {
+            break end + 4;
+        }
+    };
+    let head = String::from_utf8_lossy(&request[..body_start]).to_string();
+    let content_length = head
+        .lines()
+        .find_map(|line| {
+            let (name, value) = line.split_once(':')?;
+            name.eq_ignore_ascii_case("content-length")
+                .then(|| value.trim().parse::<usize>().ok())
+                .flatten()
+        })
+        .unwrap_or(0);
+    while request.len() < body_start + content_length {
+        match stream.read(&mut buf).await {
+            Ok(0) | Err(_) => break,
+            Ok(n) => request.extend_from_slice(&buf[..n]),
+        }
+    }
+
+    if let Some(request_line) = head.lines().next() {
+        let request_line = request_line.trim_end_matches(" HTTP/1.1").to_string();
+        let body = String::from_utf8_lossy(&request[body_start..]).into_owned();
+        requests.0.lock().unwrap().push((request_line, body));
+    }
+    let path = head
+        .split_whitespace()
+        .nth(1)
+        .unwrap_or("/")
+        .split('?')
+        .next()
+        .unwrap_or("/");
+    let authorization = head.lines().find_map(|line| {
+        let (name, value) = line.split_once(':')?;
+        name.eq_ignore_ascii_case("authorization")
+            .then(|| value.trim().to_string())
+    });
+    let route = routes.iter().find(|r| path.ends_with(r.path));
+    if let Some(route) = route {
+        tokio::time::sleep(route.delay).await;
+    }
+    let (status, content_type, body) = match route {
+        Some(route) if route.echo_authorization => (
+            route.status,
+            route.content_type,
+            authorization.unwrap_or_default(),
+        ),
+        Some(route) if route.then.is_empty() => {
+            (route.status, route.content_type, route.body.clone())
+        }
+        Some(route) => {
+            let (status, body) = route.next_answer();
+            (status, route.content_type, body)
+        }
+        None => (404, "application/json", r#"{"error":"not_found"}"#.into()),
+    };
+    let headers: String = route
+        .map(|route| route.headers.as_slice())
+        .unwrap_or_default()
+        .iter()
+        .map(|(name, value)| format!("{}: {}\r\n", name, value))
+        .collect();
+    let response = format!(
+        "HTTP/1.1 {} Stub\r\nContent-Type: {}\r\nContent-Length: {}\r\n{}Connection: close\r\n\r\n{}",
+        status,
+        content_type,
+        body.len(),
+        headers,
+        body
+    );
+    let _ = stream.write_all(response.as_bytes()).await;
+}
+
+/// A URL nothing listens on, so every request to it fails to connect.
+pub async fn unreachable_url() -> String {
+    let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let url = format!("http://{}", listener.local_addr().unwrap());
+    drop(listener);
+    url
+}
+
+/// The realm's endpoints under `base`, laid out as Keycloak serves them. No token cache path
+/// is set, so tokens stay in memory.
+pub fn configuration(base: &str) -> ClientConfiguration {
+    let issuer = format!("{}/realms/{}", base, REALM);
+    let endpoint = |path: &str| Some(format!("{}/protocol/openid-connect/{}", issuer, path));
+    ClientConfiguration {
+        server_url: Some(base.to_string()),
+        client_id: Some(CLIENT_ID.to_string()),
+        client_secret: Some("secret".to_string()),
+        auth_url: endpoint("auth"),
+        token_url: endpoint("token"),
+        device_authorization_url: endpoint("auth/device"),
+        jwks_url: endpoint("certs"),
+        realm: Some(REALM.to_string()),
+        username: Some("alice".to_string()),
+        password: Some("wonderland".to_string()),
+        issuer: Some(issuer.clone()),
+        ..ClientConfiguration::default()
+    }
+}
+
+fn app_config<C: keycloak_oauth::client::Credential>(
+    configuration: ClientConfiguration,
+    credential: C,
+) -> AppConfig<C> {
+    let mut app_config = AppConfig::new(
+        CLIENT_ID,
+        configuration.auth_url.clone().unwrap_or_default(),
+        credential,
+    );
+    app_config.configuration = Some(configuration);
+    app_config
+}
+
+pub fn device_client(configuration: ClientConfiguration) -> KeycloakClient<WithDeviceCredentials> {
+    let credential = DeviceCodeCredential {
+        client_id: CLIENT_ID.to_string(),
+    };
+    KeycloakClient::try_from(app_config(configuration, credential)).unwrap()
+}
+
+pub fn password_client(configuration: ClientConfiguration) -> KeycloakClient<WithOwnerCredentials> {
+    let credential = ResourceOwnerPasswordCredential::new("alice", "wonderland", CLIENT_ID);
+    KeycloakClient::try_from(app_config(configuration, credential)).unwrap()
+}
+
+pub fn authorization_code_client(
+    configuration: ClientConfiguration,
+) -> KeycloakClient<WithAuthorizationCode> {
+    let credential = AuthorizationCodeCredential::new(CLIENT_ID);
+    KeycloakClient::try_from(app_config(configuration, credential)).unwrap()
+}
+
+pub fn service_client(configuration: ClientConfiguration) -> KeycloakClient<WithClientCredentials> {
+    let credential = ClientCredentials::new(CLIENT_ID, "secret");
+    KeycloakClient::try_from(app_config(configuration, credential)).unwrap()
+}
+
+pub fn client_credentials_app_config(
+    configuration: ClientConfiguration,
+) -> AppConfig<ClientCredentials> {
+    app_config(configuration, ClientCredentials::new(CLIENT_ID, "secret"))
+}
+
+pub fn device_app_config(configuration: ClientConfiguration) -> AppConfig<DeviceCodeCredential> {
+    let credential = DeviceCodeCredential {
+        client_id: CLIENT_ID.to_string(),
+    };
+    app_config(configuration, credential)
+}
@@ -0,0 +1,85 @@
+//! Tokens signed with the test keys in `keys/`, and a verifier trusting those keys.
+
+use jsonwebtoken::{encode, Algorithm, EncodingKey, Header};
+use keycloak_oauth::client::{Jwk, KeyCache, TokenVerifier};
+use serde_json::{json, Value};
+
+use super::{CLIENT_ID, REALM};
+
+pub const ISSUER: &str = "http://keycloak/realms/test";
+pub const RSA_KID: &str = "rsa-test-key";
+pub const EC_KID: &str = "ec-test-key";
+
+const RSA_PEM: &[u8] = include_bytes!("keys/rsa.pem");
+const EC_PEM: &[u8] = include_bytes!("keys/ec.pem");
+
+/// The public halves of the test keys as the realm's JWKS would list them.
+pub fn jwks() -> Vec<Jwk> {
+    let jwk = |kid: &str, kty: &str, alg: &str| Jwk {
+        kid: kid.into(),
+        kty: kty.into(),
+        alg: Some(alg.into()),
+        key_use: Some("sig".into()),
+        crv: None,
+        n: None,
+        e: None,
+        x: None,
+        y: None,
+        x5c: Vec::new(),
+    };
+    vec![
+        Jwk {
+            n: Some("9KkfPo1FJqmAyj6-cZv3xzczUom0J8XktX5TdbtSS0YBhYfRo_Dx0T6G_gHt6YgX1TVngflx9jfGUxFvz8ArqCtZlzzvH66tNAQumSiReH-IsFalVZJHl8N0dX_qova_WbGQYMIscmnIfNcFe10il-XErL1-mT3Qnh_6o_CYyJoGABHLZfA76Ui3eB67h4ndllogBcs0Fs9Nx7Rd9GYqNud0u__65DhI1vy8sWm-1BzxwBlb6sw8v9iUHcr6GUNjhd-C2hJc9rTdb8b2QRk1nd0b35EwhZQp_vU3xyko8gkjdWPKZcn-LvNDltFUDw5guGF5Z3iqVS-_Ihm4dTQnyQ".into()),
+            e: Some("AQAB".into()),
+            ..jwk(RSA_KID, "RSA", "RS256")
+        },
+        Jwk {
+            crv: Some("P-256".into()),
+            x: Some("TmOTRfrUBso28Ph3QKfoq5p92NPJQrvSpm3AdX9kJ1U".into()),
+            y: Some("rdUvqjC5qAG_8WVmLjq6SLeeHvhZ0em6hi13WnG-hFY".into()),
+            ..jwk(EC_KID, "EC", "ES256")
+        },
+    ]
+}
+
+/// A verifier for `CLIENT_ID` tokens of the test realm, trusting only `jwks()`.
+pub fn verifier() -> TokenVerifier {
+    TokenVerifier::new(
+        "http://keycloak/unused",
+        KeyCache::from_keys(jwks()).into_shared(),
+    )
+    .audience([CLIENT_ID])
+    .issuer([ISSUER])
+    .allowed_algorithms(&[Algorithm::RS256, Algorithm::ES256])
+}
+
+/// Claims of a valid access token for `CLIENT_ID`, expiring in five minutes.
+pub fn claims() -> Value {
+    let now = chrono::Utc::now().timestamp();
+    json!({
+        "sub": "alice-id",
+        "iat": now,
+        "exp": now + 300,
+        "iss": ISSUER,
+        "aud": CLIENT_ID,
+        "azp": CLIENT_ID,
+        "preferred_username": "alice",
+        "scope": "openid profile",
+        "realm_access": { "roles": ["user"] },
+        "resource_access": { REALM: { "roles": ["reader"] } }
+    })
+}
+
+/// Signs `claims` with the test key for `alg`, which is `RS256` or `ES256`.
+pub fn mint(alg: Algorithm, claims: &Value) -> String {
+    let (kid, key) = match alg {
+        Algorithm::RS256 | Algorithm::RS384 | Algorithm::PS256 => {
+            (RSA_KID, EncodingKey::from_rsa_pem(RSA_PEM).unwrap())
+        }
+        Algorithm::ES256 => (EC_KID, EncodingKey::from_ec_pem(EC_PEM).unwrap()),
+        alg => panic!("no test key for {:?}", alg),
+    };
+    let mut header = Header::new(alg);
+    header.kid = Some(kid.into());
+    encode(&header, claims, &key).unwrap()
+}
@@ -0,0 +1,174 @@
+//! The git and docker credential helpers of `kc-oauth`, against profiles with cached tokens.
+
+#![cfg(feature = "cli")]
+
+mod common;
+
+use std::{
+    path::{Path, PathBuf},
+    process::{Output, Stdio},
+};
+
+use chrono::Utc;
+use common::*;
+use keycloak_oauth::client::{CachedToken, FileTokenStore, TokenStore};
+use serde_json::{json, Value};
+use tokio::io::AsyncWriteExt;
+
+/// A config file with the profiles `corp` and `dev` against `server`, each caching an access
+/// token named after it that expires in `expires_in` seconds, and mapped to `*.example.com` and
+/// `*.dev.example.com` plus `registry.example.com` respectively.
+async fn config_file(name: &str, server: &str, expires_in: i64) -> PathBuf {
+    let dir = std::env::temp_dir().join(format!("kc-oauth-{}-{}", name, std::process::id()));
+    let _ = std::fs::remove_dir_all(&dir);
+    std::fs::create_dir_all(&dir).unwrap();
+
+    let mut config = String::new();
+    for profile in ["corp", "dev"] {
+        let cache = dir.join(format!("{}.json", profile));
+        FileTokenStore::new(&cache)
+            .save(&CachedToken {
+                access_token: format!("{}-token", profile),
+                expires_at: Utc::now() + chrono::Duration::seconds(expires_in),
+                refresh_token: Some(format!("{}-refresh", profile)),
+            })
+            .await
+            .unwrap();
+        config += &format!(
+            "[profiles.{}]\nserver_url = \"{}\"\nrealm = \"{}\"\nclient_id = \"{}\"\ntoken_cache_path = \"{}\"\n\n",
+            profile,
+            server,
+            REALM,
+            CLIENT_ID,
+            cache.display()
+        );
+    }
+    config += r#"[hosts."*.example.com"]
+profile = "corp"
+
+[hosts."*.dev.example.com"]
+profile = "dev"
+
+[hosts."registry.example.com"]
+profile = "dev"
+username = "robot"
+"#;
+    let path = dir.join("config.toml");
+    std::fs::write(&path, config).unwrap();
+    path
+}
+
+/// Runs `kc-oauth <args>` with `input` on stdin.
+async fn kc_oauth(config: &Path, args: &[&str], input: &str) -> Output {
+    let mut child = tokio::process::Command::new(env!("CARGO_BIN_EXE_kc-oauth"))
+        .arg("--config")
+        .arg(config)
+        .args(args)
+        .env_remove("KC_OAUTH_PROFILE")
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()
+        .unwrap();
+    let mut stdin = child.stdin.take().unwrap();
+    stdin.write_all(input.as_bytes()).await.unwrap();
+    drop(stdin);
+    let output = child.wait_with_output().await.unwrap();
+    assert!(
+        output.status.success(),
+        "kc-oauth failed: {}",
+        String::from_utf8_lossy(&output.stderr)
+    );
+    output
+}
+
+fn stdout(output: &Output) -> String {
+    String::from_utf8(output.stdout.clone()).unwrap()
+}
+
+#[tokio::test]
+async fn git_gets_the_token_of_the_closest_wildcard() {
+    let config = config_file("git-wildcard", "http://keycloak.invalid", 300).await;
+
+    let output = kc_oauth(
+        &config,
+        &["git-credential", "get"],
+        "protocol=https\nhost=git.dev.example.com\n\n",
+    )
+    .await;
+    let answer = stdout(&output);
+    let lines: Vec<&str> = answer.lines().collect();
+    assert_eq!(lines[..2], ["username=oauth2", "password=dev-token"]);
+    assert!(lines[2].starts_with("password_expiry_utc="));
+
+    let output = kc_oauth(
+        &config,
+        &["git-credential", "get"],
+        "protocol=https\nhost=GIT.EXAMPLE.COM:8443\n",
+    )
+    .await;
+    assert!(stdout(&output).contains("password=corp-token\n"));
+}
+
+#[tokio::test]
+async fn git_never_sends_a_token_in_clear_text() {
+    let config = config_file("git-http", "http://keycloak.invalid", 300).await;
+    let output = kc_oauth(
+        &config,
+        &["git-credential", "get"],
+        "protocol=http\nhost=git.example.com\n\n",
+    )
+    .await;
+    assert_eq!(stdout(&output), "");
+}
+
+#[tokio::test]
+async fn git_refreshes_a_token_about_to_expire() {
+    let (server, requests) = recording_stub_server(vec![Route::json(
+        "/token",
+        200,
+        json!({"access_token": "refreshed", "token_type": "Bearer", "expires_in": 300}),
+    )])
+    .await;
+    let config = config_file("git-refresh", &server, 10).await;
+
+    let output = kc_oauth(
+        &config,
+        &["git-credential", "get"],
+        "protocol=https\nhost=git.example.com\n\n",
+    )
+    .await;
+    assert!(stdout(&output).contains("password=refreshed\n"));
+    assert!(requests
+        .body("/token")
+        .unwrap()
+        .contains("refresh_token=corp-refresh"));
+}
+
+#[tokio::test]
+async fn docker_gets_the_token_with_the_mapped_username() {
+    let config = config_file("docker-get", "http://keycloak.invalid", 300).await;
+    let output = kc_oauth(
+        &config,
+        &["docker-credential", "get"],
+        "https://registry.example.com/v2/\n",
+    )
+    .await;
+    let credential: Value = serde_json::from_str(&stdout(&output)).unwrap();
+    assert_eq!(
+        credential,
+        json!({
+            "ServerURL": "https://registry.example.com/v2/",
+            "Username": "robot",
+            "Secret": "dev-token"
+        })
+    );
+}
+
+#[tokio::test]
+async fn docker_lists_the_mapped_hosts_but_not_wildcards() {
+    let config = config_file("docker-list", "http://keycloak.invalid", 300).await;
+    let output = kc_oauth(&config, &["docker-credential", "list"], "").await;
+    let hosts: Value = serde_json::from_str(&stdout(&output)).unwrap();
+    assert_eq!(hosts, json!({"registry.example.com": "robot"}));
+}
@@ -0,0 +1,69 @@
+//! The device flow for public clients, with the user code handed to a prompt.
+
+mod common;
+
+use std::sync::Mutex;
+
+use common::*;
+use keycloak_oauth::client::{ClientConfiguration, KeycloakClient};
+use oauth2::TokenResponse;
+use serde_json::json;
+
+fn public_configuration(base: &str) -> ClientConfiguration {
+    ClientConfiguration {
+        client_secret: None,
+        ..configuration(base)
+    }
+}
+
+#[test]
+fn public_device_clients_need_no_secret() {
+    let app_config = device_app_config(public_configuration("http://keycloak"));
+    assert!(KeycloakClient::try_new(app_config).is_ok());
+}
+
+#[tokio::test]
+async fn the_prompt_gets_the_user_code_before_polling() {
+    let server = stub_server(vec![
+        Route::json(
+            "/auth/device",
+            200,
+            json!({
+                "device_code": "device-code",
+                "user_code": "ABCD-EFGH",
+                "verification_uri": "http://keycloak/device",
+                "verification_uri_complete": "http://keycloak/device?user_code=ABCD-EFGH",
+                "expires_in": 5,
+                "interval": 0
+            }),
+        ),
+        Route::json(
+            "/token",
+            200,
+            json!({"access_token": "signed-in", "token_type": "Bearer", "expires_in": 300}),
+        ),
+    ])
+    .await;
+    let client = device_client(public_configuration(&server));
+
+    let prompted = Mutex::new(None);
+    let token = client
+        .authenticate_with_prompt(|details| {
+            *prompted.lock().unwrap() = Some((
+                details.verification_uri().to_string(),
+                details.user_code().secret().clone(),
+            ));
+        })
+        .await
+        .unwrap();
+
+    assert_eq!(token.access_token().secret(), "signed-in");
+    assert_eq!(
+        prompted.into_inner().unwrap(),
+        Some(("http://keycloak/device".into(), "ABCD-EFGH".into()))
+    );
+    assert_eq!(
+        client.load_cached_token().await.unwrap().access_token,
+        "signed-in"
+    );
+}
@@ -0,0 +1,113 @@
+//! Endpoints and signing algorithms discovered from a realm's `.well-known/openid-configuration`.
+
+mod common;
+
+use common::*;
+use jsonwebtoken::Algorithm;
+use keycloak_oauth::client::{ClientConfiguration, ClientError, OpenIdConfiguration};
+use serde_json::json;
+
+/// The discovery document Keycloak at `server` serves for the stub's realm.
+fn document(server: &str) -> serde_json::Value {
+    let endpoint = |path: &str| {
+        format!(
+            "{}/realms/{}/protocol/openid-connect/{}",
+            server, REALM, path
+        )
+    };
+    json!({
+        "issuer": format!("{}/realms/{}", server, REALM),
+        "authorization_endpoint": endpoint("auth"),
+        "token_endpoint": endpoint("token"),
+        "jwks_uri": endpoint("certs"),
+        "device_authorization_endpoint": endpoint("auth/device"),
+        "introspection_endpoint": endpoint("token/introspect"),
+        "revocation_endpoint": endpoint("revoke"),
+        "end_session_endpoint": endpoint("logout"),
+        "userinfo_endpoint": endpoint("userinfo"),
+        "grant_types_supported": ["authorization_code", "client_credentials"],
+        "id_token_signing_alg_values_supported": ["RS256", "HS256", "ES256", "HS512", "none"]
+    })
+}
+
+#[test]
+fn discovery_url_is_below_the_realm() {
+    assert_eq!(
+        OpenIdConfiguration::url("https://sso.example.com/", "waves"),
+        "https://sso.example.com/realms/waves/.well-known/openid-configuration"
+    );
+}
+
+#[tokio::test]
+async fn discovery_fills_in_the_endpoints() {
+    let (server, requests) = recording_stub_server(vec![Route::json(
+        "/.well-known/openid-configuration",
+        200,
+        document("http://keycloak"),
+    )])
+    .await;
+
+    let config = ClientConfiguration::discover(format!("{}/", server), REALM)
+        .await
+        .unwrap();
+
+    assert_eq!(
+        requests.lines(),
+        ["GET /realms/test/.well-known/openid-configuration"]
+    );
+    let endpoint = |path: &str| {
+        Some(format!(
+            "http://keycloak/realms/test/protocol/openid-connect/{}",
+            path
+        ))
+    };
+    assert_eq!(config.realm.as_deref(), Some(REALM));
+    assert_eq!(
+        config.issuer.as_deref(),
+        Some("http://keycloak/realms/test")
+    );
+    assert_eq!(config.auth_url, endpoint("auth"));
+    assert_eq!(config.token_url, endpoint("token"));
+    assert_eq!(config.jwks_url, endpoint("certs"));
+    assert_eq!(config.device_authorization_url, endpoint("auth/device"));
+    assert_eq!(config.introspection_url, endpoint("token/introspect"));
+    assert_eq!(config.revocation_url, endpoint("revoke"));
+    assert_eq!(config.end_session_url, endpoint("logout"));
+    assert_eq!(config.userinfo_url, endpoint("userinfo"));
+    assert_eq!(
+        config.grant_types_supported,
+        ["authorization_code", "client_credentials"]
+    );
+}
+
+#[test]
+fn symmetric_and_unknown_algorithms_are_not_allowed() {
+    let mut config = ClientConfiguration::default();
+    config.apply_discovery(serde_json::from_value(document("http://keycloak")).unwrap());
+
+    // reported as discovered, but only the asymmetric ones are accepted on tokens
+    assert_eq!(config.signing_algorithms.len(), 5);
+    assert_eq!(
+        config.allowed_algorithms,
+        [Algorithm::RS256, Algorithm::ES256]
+    );
+}
+
+#[test]
+fn configured_algorithms_are_kept() {
+    let mut config = ClientConfiguration {
+        allowed_algorithms: vec![Algorithm::PS256],
+        ..ClientConfiguration::default()
+    };
+    config.apply_discovery(serde_json::from_value(document("http://keycloak")).unwrap());
+    assert_eq!(config.allowed_algorithms, [Algorithm::PS256]);
+}
+
+#[tokio::test]
+async fn missing_realms_fail_discovery() {
+    let server = stub_server(vec![]).await;
+    assert!(matches!(
+        ClientConfiguration::discover(server, "gone").await,
+        Err(ClientError::DiscoveryError(_))
+    ));
+}
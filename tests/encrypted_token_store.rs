@@ -0,0 +1,176 @@
+//! The encrypted token file round-trips, and a wrong key or any modification is detected.
+
+use std::path::{Path, PathBuf};
+
+use chrono::{SubsecRound, Utc};
+use keycloak_oauth::client::{
+    CachedToken, ClientError, EncryptedFileTokenStore, TokenKey, TokenStore,
+};
+
+/// A fresh token file path in the system's temp directory.
+fn token_path(name: &str) -> PathBuf {
+    let dir = std::env::temp_dir().join(format!("keycloak-oauth-{}-{}", name, std::process::id()));
+    let _ = std::fs::remove_dir_all(&dir);
+    dir.join("token.bin")
+}
+
+fn token() -> CachedToken {
+    CachedToken {
+        access_token: "access".into(),
+        expires_at: Utc::now().trunc_subsecs(0) + chrono::Duration::seconds(300),
+        refresh_token: Some("refresh".into()),
+    }
+}
+
+fn passphrase_store(path: &PathBuf, passphrase: &str) -> EncryptedFileTokenStore {
+    EncryptedFileTokenStore::new(path, TokenKey::Passphrase(passphrase.into()))
+}
+
+/// The temporary files left next to `path`.
+fn leftovers(path: &Path) -> usize {
+    std::fs::read_dir(path.parent().unwrap())
+        .unwrap()
+        .filter(|entry| {
+            entry
+                .as_ref()
+                .unwrap()
+                .file_name()
+                .to_string_lossy()
+                .ends_with(".tmp")
+        })
+        .count()
+}
+
+/// Saves a token, flips the byte at `offset` and loads it again.
+async fn load_tampered(name: &str, offset: usize) -> Result<Option<CachedToken>, ClientError> {
+    let path = token_path(name);
+    passphrase_store(&path, "correct horse")
+        .save(&token())
+        .await
+        .unwrap();
+    let mut data = std::fs::read(&path).unwrap();
+    data[offset] ^= 0x01;
+    std::fs::write(&path, data).unwrap();
+    passphrase_store(&path, "correct horse").load().await
+}
+
+#[tokio::test]
+async fn round_trip_with_a_passphrase() {
+    let path = token_path("round-trip");
+    let token = token();
+    passphrase_store(&path, "correct horse")
+        .save(&token)
+        .await
+        .unwrap();
+
+    let loaded = passphrase_store(&path, "correct horse")
+        .load()
+        .await
+        .unwrap()
+        .unwrap();
+    assert_eq!(loaded.access_token, "access");
+    assert_eq!(loaded.refresh_token.as_deref(), Some("refresh"));
+    assert_eq!(loaded.expires_at, token.expires_at);
+    assert!(!std::fs::read(&path)
+        .unwrap()
+        .windows(6)
+        .any(|w| w == b"access"));
+}
+
+#[tokio::test]
+async fn round_trip_with_a_key_file() {
+    let path = token_path("key-file");
+    std::fs::create_dir_all(path.parent().unwrap()).unwrap();
+    let key_path = path.with_file_name("key");
+    std::fs::write(&key_path, [7u8; 32]).unwrap();
+    let store = EncryptedFileTokenStore::new(&path, TokenKey::File(key_path));
+
+    store.save(&token()).await.unwrap();
+    assert_eq!(store.load().await.unwrap().unwrap().access_token, "access");
+    store.clear().await.unwrap();
+    assert!(store.load().await.unwrap().is_none());
+}
+
+#[tokio::test]
+async fn wrong_passphrase_is_rejected() {
+    let path = token_path("wrong-key");
+    passphrase_store(&path, "correct horse")
+        .save(&token())
+        .await
+        .unwrap();
+    assert!(matches!(
+        passphrase_store(&path, "battery staple").load().await,
+        Err(ClientError::TokenDecryptionError)
+    ));
+}
+
+#[tokio::test]
+async fn tampered_ciphertext_is_rejected() {
+    // magic, salt and nonce come first
+    assert!(matches!(
+        load_tampered("ciphertext", 4 + 16 + 12).await,
+        Err(ClientError::TokenDecryptionError)
+    ));
+}
+
+#[tokio::test]
+async fn tampered_salt_is_rejected() {
+    // the salt follows the 4 byte magic and is authenticated as associated data
+    assert!(matches!(
+        load_tampered("salt", 4).await,
+        Err(ClientError::TokenDecryptionError)
+    ));
+}
+
+#[tokio::test]
+async fn tampered_magic_is_rejected() {
+    assert!(matches!(
+        load_tampered("magic", 0).await,
+        Err(ClientError::TokenDecryptionError)
+    ));
+}
+
+#[tokio::test]
+async fn concurrent_saves_do_not_collide() {
+    let path = token_path("concurrent");
+    let store = passphrase_store(&path, "correct horse");
+    let saves = (0..8).map(|_| {
+        let store = store.clone();
+        tokio::spawn(async move { store.save(&token()).await })
+    });
+    for save in saves {
+        save.await.unwrap().unwrap();
+    }
+    assert!(store.load().await.unwrap().is_some());
+    assert_eq!(leftovers(&path), 0);
+}
+
+#[tokio::test]
+async fn failed_save_leaves_no_temporary_file() {
+    let path = token_path("failed-save");
+    // a directory in the way of the rename
+    std::fs::create_dir_all(path.join("occupied")).unwrap();
+    assert!(passphrase_store(&path, "correct horse")
+        .save(&token())
+        .await
+        .is_err());
+    assert_eq!(leftovers(&path), 0);
+}
+
+#[cfg(unix)]
+#[tokio::test]
+async fn token_file_is_private() {
+    use std::os::unix::fs::PermissionsExt;
+
+    let path = token_path("permissions");
+    std::fs::create_dir_all(path.parent().unwrap()).unwrap();
+    std::fs::write(&path, b"old").unwrap();
+    std::fs::set_permissions(&path, std::fs::Permissions::from_mode(0o644)).unwrap();
+
+    passphrase_store(&path, "correct horse")
+        .save(&token())
+        .await
+        .unwrap();
+    let mode = std::fs::metadata(&path).unwrap().permissions().mode();
+    assert_eq!(mode & 0o777, 0o600);
+}
@@ -0,0 +1,114 @@
+//! `GrpcAuthLayer` in front of a gRPC service, with locally minted tokens.
+
+#![cfg(feature = "tonic")]
+
+mod common;
+
+use std::{
+    convert::Infallible,
+    future::{ready, Ready},
+    task::{Context, Poll},
+};
+
+use common::tokens::*;
+use jsonwebtoken::Algorithm;
+use keycloak_oauth::client::{GrpcAuthLayer, KeycloakClaims, Requirement};
+use tonic::{
+    body::{empty_body, BoxBody},
+    codegen::http::{self, header},
+    Code, Status,
+};
+use tower_layer::Layer;
+use tower_service::Service;
+
+/// Answers every call with the caller's username in an `x-user` header.
+#[derive(Clone)]
+struct Greeter;
+
+impl Service<http::Request<()>> for Greeter {
+    type Response = http::Response<BoxBody>;
+    type Error = Infallible;
+    type Future = Ready<Result<Self::Response, Infallible>>;
+
+    fn poll_ready(&mut self, _: &mut Context<'_>) -> Poll<Result<(), Infallible>> {
+        Poll::Ready(Ok(()))
+    }
+
+    fn call(&mut self, request: http::Request<()>) -> Self::Future {
+        let user = request
+            .extensions()
+            .get::<KeycloakClaims>()
+            .and_then(|claims| claims.preferred_username.clone())
+            .unwrap_or_default();
+        ready(Ok(http::Response::builder()
+            .header("x-user", user)
+            .body(empty_body())
+            .unwrap()))
+    }
+}
+
+/// Calls the service behind `layer`, returning the username it saw or the status of the
+/// rejection.
+async fn call(layer: GrpcAuthLayer, authorization: Option<String>) -> Result<String, Status> {
+    let mut request = http::Request::new(());
+    if let Some(authorization) = authorization {
+        request
+            .headers_mut()
+            .insert(header::AUTHORIZATION, authorization.parse().unwrap());
+    }
+    let response = layer.layer(Greeter).call(request).await.unwrap();
+    if let Some(status) = Status::from_header_map(response.headers()) {
+        return Err(status);
+    }
+    Ok(response.headers()["x-user"].to_str().unwrap().to_string())
+}
+
+fn bearer(alg: Algorithm) -> Option<String> {
+    Some(format!("Bearer {}", mint(alg, &claims())))
+}
+
+#[tokio::test]
+async fn valid_tokens_reach_the_service() {
+    let user = call(GrpcAuthLayer::new(verifier()), bearer(Algorithm::ES256)).await;
+    assert_eq!(user.unwrap(), "alice");
+}
+
+#[tokio::test]
+async fn missing_metadata_is_unauthenticated() {
+    let status = call(GrpcAuthLayer::new(verifier()), None)
+        .await
+        .unwrap_err();
+    assert_eq!(status.code(), Code::Unauthenticated);
+}
+
+#[tokio::test]
+async fn invalid_token_is_unauthenticated() {
+    let mut expired = claims();
+    expired["exp"] = (chrono::Utc::now().timestamp() - 120).into();
+    let token = format!("Bearer {}", mint(Algorithm::RS256, &expired));
+    let status = call(GrpcAuthLayer::new(verifier()), Some(token))
+        .await
+        .unwrap_err();
+    assert_eq!(status.code(), Code::Unauthenticated);
+
+    let status = call(
+        GrpcAuthLayer::new(verifier()),
+        Some("Bearer garbage".into()),
+    )
+    .await
+    .unwrap_err();
+    assert_eq!(status.code(), Code::Unauthenticated);
+}
+
+#[tokio::test]
+async fn unmet_requirement_is_permission_denied() {
+    let layer = GrpcAuthLayer::new(verifier()).require(Requirement::realm_role("admin"));
+    let status = call(layer, bearer(Algorithm::RS256)).await.unwrap_err();
+    assert_eq!(status.code(), Code::PermissionDenied);
+
+    let layer = GrpcAuthLayer::new(verifier()).require(Requirement::realm_role("user"));
+    assert_eq!(
+        call(layer, bearer(Algorithm::RS256)).await.unwrap(),
+        "alice"
+    );
+}
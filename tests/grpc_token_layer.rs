@@ -0,0 +1,83 @@
+//! `GrpcTokenLayer` keeps a client credentials client's calls authenticated across token expiry.
+
+#![cfg(feature = "tonic")]
+
+mod common;
+
+use std::{
+    convert::Infallible,
+    future::{ready, Ready},
+    sync::Arc,
+    task::{Context, Poll},
+};
+
+use chrono::Utc;
+use common::*;
+use keycloak_oauth::client::{CachedToken, GrpcTokenLayer};
+use serde_json::json;
+use tonic::codegen::http::{self, header};
+use tower_layer::Layer;
+use tower_service::Service;
+
+/// Answers every call with the `authorization` header it received.
+#[derive(Clone)]
+struct Echo;
+
+impl Service<http::Request<()>> for Echo {
+    type Response = Option<String>;
+    type Error = Infallible;
+    type Future = Ready<Result<Option<String>, Infallible>>;
+
+    fn poll_ready(&mut self, _: &mut Context<'_>) -> Poll<Result<(), Infallible>> {
+        Poll::Ready(Ok(()))
+    }
+
+    fn call(&mut self, request: http::Request<()>) -> Self::Future {
+        ready(Ok(request
+            .headers()
+            .get(header::AUTHORIZATION)
+            .map(|value| value.to_str().unwrap().to_string())))
+    }
+}
+
+#[tokio::test]
+async fn client_credentials_calls_outlive_the_first_token() {
+    let server = stub_server(vec![Route::json(
+        "/token",
+        200,
+        json!({"access_token": "fresh", "token_type": "Bearer", "expires_in": 300}),
+    )])
+    .await;
+    let client = Arc::new(service_client(configuration(&server)));
+    let mut channel = GrpcTokenLayer::new(Arc::clone(&client)).layer(Echo);
+
+    let first = channel.call(http::Request::new(())).await.unwrap();
+    assert_eq!(first.as_deref(), Some("Bearer fresh"));
+
+    // keycloak issues no refresh token for the client credentials grant
+    client
+        .store
+        .save(&CachedToken {
+            access_token: "stale".into(),
+            expires_at: Utc::now() - chrono::Duration::seconds(10),
+            refresh_token: None,
+        })
+        .await
+        .unwrap();
+    let second = channel.call(http::Request::new(())).await.unwrap();
+    assert_eq!(second.as_deref(), Some("Bearer fresh"));
+}
+
+#[tokio::test]
+async fn failing_grant_fails_the_call() {
+    let server = stub_server(vec![Route::json(
+        "/token",
+        401,
+        json!({"error": "invalid_client"}),
+    )])
+    .await;
+    let mut channel = GrpcTokenLayer::new(service_client(configuration(&server))).layer(Echo);
+    let error = channel.call(http::Request::new(())).await.unwrap_err();
+    let status = error.downcast::<tonic::Status>().unwrap();
+    assert_eq!(status.code(), tonic::Code::Unauthenticated);
+}
@@ -0,0 +1,95 @@
+//! Token introspection, and the online validation policy rejecting tokens Keycloak reports as
+//! inactive.
+
+mod common;
+
+use common::{tokens::*, *};
+use jsonwebtoken::Algorithm;
+use keycloak_oauth::client::{
+    ClientConfiguration, ClientError, KeycloakClient, ValidationPolicy, WithClientCredentials,
+};
+use serde_json::{json, Value};
+
+/// A client of a realm serving the test keys, whose introspection endpoint answers `answer`.
+async fn client(answer: Value) -> (KeycloakClient<WithClientCredentials>, Requests) {
+    let (server, requests) = recording_stub_server(vec![
+        Route::json("/certs", 200, json!({ "keys": jwks() })),
+        Route::json("/token/introspect", 200, answer),
+    ])
+    .await;
+    let client = service_client(ClientConfiguration {
+        issuer: Some(ISSUER.into()),
+        ..configuration(&server)
+    });
+    (client, requests)
+}
+
+fn introspections(requests: &Requests) -> usize {
+    requests
+        .lines()
+        .iter()
+        .filter(|line| line.ends_with("/token/introspect"))
+        .count()
+}
+
+#[tokio::test]
+async fn active_token_is_described() {
+    let (client, _) = client(json!({
+        "active": true,
+        "username": "alice",
+        "client_id": CLIENT_ID,
+        "scope": "openid profile",
+        "realm_access": { "roles": ["user"] },
+        "resource_access": { REALM: { "roles": ["reader"] } }
+    }))
+    .await;
+
+    let response = client.introspect("some-token").await.unwrap();
+    assert!(response.active);
+    assert_eq!(response.username.as_deref(), Some("alice"));
+    assert_eq!(response.roles(), ["user"]);
+    assert_eq!(response.client_roles(REALM), ["reader"]);
+}
+
+#[tokio::test]
+async fn inactive_token_has_nothing_but_active() {
+    let (client, _) = client(json!({ "active": false })).await;
+    let response = client.introspect("revoked-token").await.unwrap();
+    assert!(!response.active);
+    assert_eq!(response.username, None);
+    assert!(response.roles().is_empty());
+}
+
+#[tokio::test]
+async fn online_policy_rejects_inactive_tokens() {
+    let (client, requests) = client(json!({ "active": false })).await;
+    let client = client.with_validation_policy(ValidationPolicy::Online);
+
+    // the signature and claims are fine, only Keycloak knows the session is gone
+    let token = mint(Algorithm::RS256, &claims());
+    assert!(matches!(
+        client.verify_access_token(&token).await,
+        Err(ClientError::InactiveTokenError)
+    ));
+    assert_eq!(introspections(&requests), 1);
+}
+
+#[tokio::test]
+async fn online_policy_accepts_active_tokens() {
+    let (client, requests) = client(json!({ "active": true })).await;
+    let client = client.with_validation_policy(ValidationPolicy::Online);
+
+    let token = mint(Algorithm::RS256, &claims());
+    let claims = client.verify_access_token(&token).await.unwrap().claims;
+    assert_eq!(claims.preferred_username.as_deref(), Some("alice"));
+    assert_eq!(introspections(&requests), 1);
+}
+
+#[tokio::test]
+async fn local_policy_does_not_ask_keycloak() {
+    let (client, requests) = client(json!({ "active": false })).await;
+
+    let token = mint(Algorithm::RS256, &claims());
+    assert!(client.verify_access_token(&token).await.is_ok());
+    assert_eq!(introspections(&requests), 0);
+}
@@ -0,0 +1,195 @@
+//! Keycloak's error responses are parsed, classified and passed on, including on refresh.
+
+mod common;
+
+use chrono::Utc;
+use common::*;
+use keycloak_oauth::client::{
+    CachedToken, ClientError, ErrorCategory, KeycloakError, KeycloakErrorCode,
+};
+use serde_json::json;
+
+fn expired_token() -> CachedToken {
+    CachedToken {
+        access_token: "stale".into(),
+        expires_at: Utc::now() - chrono::Duration::seconds(10),
+        refresh_token: Some("refresh".into()),
+    }
+}
+
+fn error(status: u16, body: serde_json::Value) -> KeycloakError {
+    KeycloakError::from_body(status, body.to_string().as_bytes()).unwrap()
+}
+
+/// Refreshes an expired token against a token endpoint answering `status` and `body`.
+async fn refresh_error(status: u16, body: serde_json::Value) -> ClientError {
+    let server = stub_server(vec![Route::json("/token", status, body)]).await;
+    let client = service_client(configuration(&server));
+    client.store.save(&expired_token()).await.unwrap();
+    client.verify_and_refresh_access_token().await.unwrap_err()
+}
+
+#[test]
+fn invalid_grant_is_split_by_description() {
+    let session = error(
+        400,
+        json!({"error": "invalid_grant", "error_description": "Session not active"}),
+    );
+    let offline = error(
+        400,
+        json!({"error": "invalid_grant", "error_description": "Offline session not active"}),
+    );
+    let token = error(
+        400,
+        json!({"error": "invalid_grant", "error_description": "Token is not active"}),
+    );
+    let credentials = error(
+        401,
+        json!({"error": "invalid_grant", "error_description": "Invalid user credentials"}),
+    );
+
+    assert_eq!(session.code(), KeycloakErrorCode::SessionNotActive);
+    assert_eq!(offline.code(), KeycloakErrorCode::SessionNotActive);
+    assert_eq!(token.code(), KeycloakErrorCode::TokenNotActive);
+    assert_eq!(credentials.code(), KeycloakErrorCode::InvalidGrant);
+    for e in [session, offline, token, credentials] {
+        assert_eq!(e.category(), ErrorCategory::ReauthenticationRequired);
+    }
+}
+
+#[test]
+fn client_setup_errors_are_misconfiguration() {
+    for code in ["unauthorized_client", "invalid_scope", "invalid_client"] {
+        let e = error(400, json!({ "error": code }));
+        assert_eq!(e.category(), ErrorCategory::Misconfiguration, "{}", code);
+    }
+    assert_eq!(
+        error(400, json!({"error": "unauthorized_client"})).code(),
+        KeycloakErrorCode::UnauthorizedClient
+    );
+    assert_eq!(
+        error(400, json!({"error": "invalid_scope"})).code(),
+        KeycloakErrorCode::InvalidScope
+    );
+}
+
+#[test]
+fn unknown_codes_are_classified_by_status() {
+    let category = |status| error(status, json!({"error": "unknown_error"})).category();
+    assert_eq!(category(503), ErrorCategory::Retryable);
+    assert_eq!(category(429), ErrorCategory::Retryable);
+    assert_eq!(category(403), ErrorCategory::Forbidden);
+    assert_eq!(category(401), ErrorCategory::ReauthenticationRequired);
+    assert_eq!(category(400), ErrorCategory::Misconfiguration);
+    assert_eq!(
+        error(403, json!({"error": "access_denied"})).category(),
+        ErrorCategory::Forbidden
+    );
+}
+
+#[test]
+fn non_oauth_bodies_are_not_keycloak_errors() {
+    assert!(KeycloakError::from_body(502, b"<html>Bad Gateway</html>").is_none());
+    assert!(KeycloakError::from_body(500, br#"{"message": "boom"}"#).is_none());
+}
+
+#[test]
+fn display_includes_description_and_status() {
+    let e = error(
+        400,
+        json!({"error": "invalid_grant", "error_description": "Session not active"}),
+    );
+    assert_eq!(
+        e.to_string(),
+        "invalid_grant: Session not active (HTTP 400)"
+    );
+}
+
+#[tokio::test]
+async fn ended_session_on_refresh_requires_reauthentication() {
+    let e = refresh_error(
+        400,
+        json!({"error": "invalid_grant", "error_description": "Session not active"}),
+    )
+    .await;
+    assert!(e.requires_reauthentication());
+    match e {
+        ClientError::RefreshTokenExpiredError(e) => {
+            assert_eq!(e.code(), KeycloakErrorCode::SessionNotActive);
+            assert_eq!(e.status, Some(400));
+        }
+        e => panic!("expected an expired refresh token, got {:?}", e),
+    }
+}
+
+#[tokio::test]
+async fn expired_refresh_token_is_told_apart_from_an_ended_session() {
+    let e = refresh_error(
+        400,
+        json!({"error": "invalid_grant", "error_description": "Token is not active"}),
+    )
+    .await;
+    assert!(matches!(
+        e,
+        ClientError::RefreshTokenExpiredError(e) if e.code() == KeycloakErrorCode::TokenNotActive
+    ));
+}
+
+#[tokio::test]
+async fn other_refresh_errors_are_passed_on() {
+    let e = refresh_error(
+        400,
+        json!({
+            "error": "unauthorized_client",
+            "error_description": "Client not allowed to refresh tokens"
+        }),
+    )
+    .await;
+    assert_eq!(e.category(), Some(ErrorCategory::Misconfiguration));
+    match e {
+        ClientError::KeycloakError(e) => {
+            assert_eq!(e.code(), KeycloakErrorCode::UnauthorizedClient);
+            assert_eq!(
+                e.error_description.as_deref(),
+                Some("Client not allowed to refresh tokens")
+            );
+        }
+        e => panic!("expected a Keycloak error, got {:?}", e),
+    }
+}
+
+#[tokio::test]
+async fn unavailable_keycloak_is_retryable_on_refresh() {
+    let e = refresh_error(
+        503,
+        json!({"error": "temporarily_unavailable", "error_description": "Try again later"}),
+    )
+    .await;
+    assert!(e.is_retryable());
+    assert!(!e.requires_reauthentication());
+}
+
+#[tokio::test]
+async fn invalid_scope_is_reported_on_the_grant() {
+    let server = stub_server(vec![Route::json(
+        "/token",
+        400,
+        json!({"error": "invalid_scope", "error_description": "Invalid scopes: admin"}),
+    )])
+    .await;
+    let client = service_client(configuration(&server));
+    let e = client.authenticate().await.unwrap_err();
+    assert_eq!(e.category(), Some(ErrorCategory::Misconfiguration));
+    assert!(matches!(
+        e,
+        ClientError::KeycloakError(e) if e.code() == KeycloakErrorCode::InvalidScope
+    ));
+}
+
+#[tokio::test]
+async fn unreachable_keycloak_is_retryable() {
+    let client = service_client(configuration(&unreachable_url().await));
+    client.store.save(&expired_token()).await.unwrap();
+    let e = client.verify_and_refresh_access_token().await.unwrap_err();
+    assert!(e.is_retryable());
+}
@@ -0,0 +1,82 @@
+//! `kc-oauth kubectl` signs in on stderr and prints nothing but the ExecCredential on stdout.
+
+#![cfg(feature = "cli")]
+
+mod common;
+
+use std::path::PathBuf;
+
+use common::*;
+use serde_json::{json, Value};
+
+/// A config file with one profile against `server`, caching its tokens next to it.
+fn config_file(name: &str, server: &str) -> PathBuf {
+    let dir = std::env::temp_dir().join(format!("kc-oauth-{}-{}", name, std::process::id()));
+    let _ = std::fs::remove_dir_all(&dir);
+    std::fs::create_dir_all(&dir).unwrap();
+    let path = dir.join("config.toml");
+    let config = format!(
+        r#"default_profile = "test"
+
+[profiles.test]
+server_url = "{}"
+realm = "{}"
+client_id = "{}"
+flow = "device"
+token_cache_path = "{}"
+"#,
+        server,
+        REALM,
+        CLIENT_ID,
+        dir.join("token.json").display()
+    );
+    std::fs::write(&path, config).unwrap();
+    path
+}
+
+#[tokio::test]
+async fn device_fallback_keeps_stdout_for_the_exec_credential() {
+    let server = stub_server(vec![
+        Route::json(
+            "/auth/device",
+            200,
+            json!({
+                "device_code": "device-code",
+                "user_code": "ABCD-EFGH",
+                "verification_uri": "http://keycloak/device",
+                "expires_in": 5,
+                "interval": 0
+            }),
+        ),
+        // oauth2 retries pending polls itself, a mistyped code comes back to the poll loop
+        Route::json("/token", 400, json!({"error": "bad_verification_code"})).then_json(
+            200,
+            json!({"access_token": "signed-in", "token_type": "Bearer", "expires_in": 300}),
+        ),
+    ])
+    .await;
+    let config = config_file("kubectl-device", &server);
+
+    let output = tokio::process::Command::new(env!("CARGO_BIN_EXE_kc-oauth"))
+        .arg("--config")
+        .arg(&config)
+        .arg("kubectl")
+        .env(
+            "KUBERNETES_EXEC_INFO",
+            r#"{"apiVersion":"client.authentication.k8s.io/v1","spec":{"interactive":true}}"#,
+        )
+        .env_remove("KC_OAUTH_PROFILE")
+        .output()
+        .await
+        .unwrap();
+
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    assert!(output.status.success(), "kc-oauth failed: {}", stderr);
+    assert!(stderr.contains("ABCD-EFGH"));
+
+    let stdout = String::from_utf8(output.stdout).unwrap();
+    assert_eq!(stdout.lines().count(), 1, "unexpected stdout: {:?}", stdout);
+    let credential: Value = serde_json::from_str(&stdout).unwrap();
+    assert_eq!(credential["kind"], "ExecCredential");
+    assert_eq!(credential["status"]["token"], "signed-in");
+}
@@ -0,0 +1,76 @@
+//! Revocation and logout reach Keycloak's endpoints and leave the token store empty.
+
+mod common;
+
+use chrono::Utc;
+use common::*;
+use keycloak_oauth::client::{CachedToken, KeycloakClient, WithOwnerCredentials};
+
+fn signed_in_token() -> CachedToken {
+    CachedToken {
+        access_token: "access".into(),
+        expires_at: Utc::now() + chrono::Duration::seconds(300),
+        refresh_token: Some("refresh".into()),
+    }
+}
+
+async fn client() -> (KeycloakClient<WithOwnerCredentials>, Requests) {
+    let (server, requests) = recording_stub_server(vec![
+        Route::html("/revoke", 200, ""),
+        Route::html("/logout", 204, ""),
+    ])
+    .await;
+    (password_client(configuration(&server)), requests)
+}
+
+#[tokio::test]
+async fn signing_out_revokes_ends_the_session_and_clears_the_store() {
+    let (client, requests) = client().await;
+    client.store.save(&signed_in_token()).await.unwrap();
+
+    client
+        .revoke_token("access", Some("access_token"))
+        .await
+        .unwrap();
+    client.logout().await.unwrap();
+
+    assert_eq!(
+        requests.lines(),
+        [
+            "POST /realms/test/protocol/openid-connect/revoke",
+            "POST /realms/test/protocol/openid-connect/logout",
+        ]
+    );
+    assert!(requests.body("/revoke").unwrap().contains("token=access"));
+    assert!(requests
+        .body("/logout")
+        .unwrap()
+        .contains("refresh_token=refresh"));
+    assert!(client.store.load().await.unwrap().is_none());
+}
+
+#[tokio::test]
+async fn logout_without_a_token_only_clears_the_store() {
+    let (client, requests) = client().await;
+    client.logout().await.unwrap();
+    assert!(requests.lines().is_empty());
+    assert!(client.store.load().await.unwrap().is_none());
+}
+
+#[tokio::test]
+async fn logout_with_an_id_token_hint_clears_the_store() {
+    let (client, requests) = client().await;
+    client.store.save(&signed_in_token()).await.unwrap();
+
+    client.logout_with_id_token_hint("id-token").await.unwrap();
+
+    assert_eq!(
+        requests.lines(),
+        ["POST /realms/test/protocol/openid-connect/logout"]
+    );
+    assert!(requests
+        .body("/logout")
+        .unwrap()
+        .contains("id_token_hint=id-token"));
+    assert!(client.store.load().await.unwrap().is_none());
+}
@@ -0,0 +1,330 @@
+//! Planning a realm against canned live state.
+
+mod common;
+
+use common::*;
+use keycloak_oauth::admin::{
+    Change, ClientRepresentation, KeycloakAdmin, RealmPlan, RealmRepresentation, ReconcileOptions,
+};
+use serde_json::json;
+
+/// An admin client whose live realm is `routes`.
+async fn admin(routes: Vec<Route>) -> KeycloakAdmin {
+    let token = Route::json(
+        "/token",
+        200,
+        json!({"access_token": "admin-token", "token_type": "Bearer", "expires_in": 300}),
+    );
+    let server = stub_server(routes.into_iter().chain([token]).collect()).await;
+    KeycloakAdmin::from_client(service_client(configuration(&server))).unwrap()
+}
+
+fn realm(desired: serde_json::Value) -> RealmRepresentation {
+    serde_json::from_value(desired).unwrap()
+}
+
+async fn plan(live: Vec<Route>, desired: serde_json::Value, prune: bool) -> RealmPlan {
+    admin(live)
+        .await
+        .plan_realm(&realm(desired), &ReconcileOptions::new().with_prune(prune))
+        .await
+        .unwrap()
+}
+
+#[tokio::test]
+async fn nested_objects_match_when_live_has_more() {
+    let live = vec![Route::json(
+        "/clients",
+        200,
+        json!([{
+            "id": "1",
+            "clientId": "app",
+            "attributes": {"pkce.code.challenge.method": "S256", "post.logout.redirect.uris": "+"}
+        }]),
+    )];
+    let desired = json!({"clients": [{
+        "clientId": "app",
+        "attributes": {"pkce.code.challenge.method": "S256"}
+    }]});
+    assert!(plan(live, desired, false).await.is_empty());
+}
+
+#[tokio::test]
+async fn nested_objects_differ_when_a_desired_entry_does() {
+    let live = vec![Route::json(
+        "/clients",
+        200,
+        json!([{"id": "1", "clientId": "app", "attributes": {"pkce.code.challenge.method": "plain"}}]),
+    )];
+    let desired = json!({"clients": [{
+        "clientId": "app",
+        "attributes": {"pkce.code.challenge.method": "S256"}
+    }]});
+    assert_eq!(
+        plan(live, desired, false).await.to_string(),
+        "~ client app (attributes)\n"
+    );
+}
+
+#[tokio::test]
+async fn arrays_match_in_any_order() {
+    let live = vec![Route::json(
+        "/clients",
+        200,
+        json!([{"id": "1", "clientId": "app", "redirectUris": ["https://b/*", "https://a/*"]}]),
+    )];
+    let desired =
+        json!({"clients": [{"clientId": "app", "redirectUris": ["https://a/*", "https://b/*"]}]});
+    assert!(plan(live, desired, false).await.is_empty());
+}
+
+#[tokio::test]
+async fn masked_live_secrets_match() {
+    let live = vec![
+        Route::json(
+            "/clients",
+            200,
+            json!([{"id": "1", "clientId": "app", "secret": "**********"}]),
+        ),
+        Route::json(
+            "/identity-provider/instances",
+            200,
+            json!([{
+                "alias": "github",
+                "providerId": "github",
+                "config": {"clientId": "gh", "clientSecret": "**********"}
+            }]),
+        ),
+    ];
+    let desired = json!({
+        "clients": [{"clientId": "app", "secret": "s3cr3t"}],
+        "identityProviders": [{
+            "alias": "github",
+            "providerId": "github",
+            "config": {"clientId": "gh", "clientSecret": "s3cr3t"}
+        }]
+    });
+    assert!(plan(live, desired, false).await.is_empty());
+}
+
+#[tokio::test]
+async fn nested_groups_are_planned_by_path() {
+    let live = vec![
+        Route::json(
+            "/groups",
+            200,
+            json!([{"id": "1", "name": "staff", "subGroups": [{"id": "2", "name": "ops"}]}]),
+        ),
+        Route::json("/role-mappings", 200, json!({})),
+    ];
+    let desired = json!({"groups": [{
+        "name": "staff",
+        "subGroups": [
+            {"name": "ops", "subGroups": [{"name": "on-call"}]},
+            {"name": "dev"}
+        ]
+    }]});
+    let plan = plan(live, desired, false).await;
+    assert_eq!(
+        plan.to_string(),
+        "+ group /staff/ops/on-call\n+ group /staff/dev\n"
+    );
+    assert!(matches!(
+        &plan.changes[0],
+        Change::CreateGroup { parent: Some(parent), .. } if parent == "/staff/ops"
+    ));
+}
+
+#[tokio::test]
+async fn prune_keeps_builtin_clients() {
+    let live = vec![Route::json(
+        "/clients",
+        200,
+        json!([
+            {"id": "1", "clientId": "account"},
+            {"id": "2", "clientId": "realm-management"},
+            {"id": "3", "clientId": "legacy"}
+        ]),
+    )];
+    let desired = json!({"clients": []});
+    assert_eq!(
+        plan(live, desired, true).await.to_string(),
+        "- client legacy\n"
+    );
+}
+
+#[tokio::test]
+async fn prune_keeps_the_roles_of_builtin_clients() {
+    let live = vec![
+        Route::json(
+            "/clients",
+            200,
+            json!([{"id": "1", "clientId": "realm-management"}]),
+        ),
+        Route::json(
+            "/roles",
+            200,
+            json!([{"name": "manage-users"}, {"name": "auditor"}]),
+        ),
+    ];
+    let desired = json!({"roles": {"client": {"realm-management": [{"name": "auditor"}]}}});
+    assert!(plan(live, desired, true).await.is_empty());
+}
+
+#[tokio::test]
+async fn prune_deletes_the_roles_of_other_clients() {
+    let live = vec![
+        Route::json("/clients", 200, json!([{"id": "1", "clientId": "app"}])),
+        Route::json(
+            "/roles",
+            200,
+            json!([{"name": "reader"}, {"name": "writer"}]),
+        ),
+    ];
+    let desired = json!({"roles": {"client": {"app": [{"name": "reader"}]}}});
+    assert_eq!(
+        plan(live, desired, true).await.to_string(),
+        "- client role app/writer\n"
+    );
+}
+
+#[tokio::test]
+async fn a_second_plan_after_applying_is_empty() {
+    let live = json!({
+        "id": "1",
+        "clientId": "app",
+        "enabled": false,
+        "redirectUris": ["https://a/*"],
+        "attributes": {"pkce.code.challenge.method": "plain", "display.on.consent.screen": "false"}
+    });
+    let desired = json!({"clients": [{
+        "clientId": "app",
+        "enabled": true,
+        "redirectUris": ["https://b/*", "https://a/*"],
+        "attributes": {"pkce.code.challenge.method": "S256"}
+    }]});
+
+    let first = plan(
+        vec![Route::json("/clients", 200, json!([live]))],
+        desired.clone(),
+        false,
+    )
+    .await;
+    let [Change::UpdateClient { fields, client, .. }] = first.changes.as_slice() else {
+        panic!("unexpected plan {}", first);
+    };
+    assert_eq!(fields, &["attributes", "enabled", "redirectUris"]);
+    // what Keycloak holds once the update is applied
+    let applied: ClientRepresentation = client.clone();
+    assert_eq!(
+        applied
+            .attributes
+            .as_ref()
+            .and_then(|a| a.get("display.on.consent.screen"))
+            .map(String::as_str),
+        Some("false")
+    );
+
+    let second = plan(
+        vec![Route::json("/clients", 200, json!([applied]))],
+        desired,
+        false,
+    )
+    .await;
+    assert!(second.is_empty(), "{}", second);
+}
+
+#[tokio::test]
+async fn client_scopes_and_mappers_are_listed_as_unsupported() {
+    let live = vec![Route::json(
+        "/clients",
+        200,
+        json!([{
+            "id": "1",
+            "clientId": "app",
+            "defaultClientScopes": ["profile"],
+            "protocolMappers": [{"id": "m1", "name": "audience", "protocolMapper": "oidc-audience-mapper"}]
+        }]),
+    )];
+    let desired = json!({"clients": [{
+        "clientId": "app",
+        "defaultClientScopes": ["profile", "email"],
+        "protocolMappers": [{"name": "audience", "protocolMapper": "oidc-audience-mapper"}]
+    }]});
+    let plan = plan(live, desired, false).await;
+    assert!(!plan.is_empty());
+    assert_eq!(
+        plan.to_string(),
+        "! client app (defaultClientScopes): not reconciled\n"
+    );
+}
+
+#[tokio::test]
+async fn differing_composites_are_listed_as_unsupported() {
+    let live = vec![
+        Route::json(
+            "/roles",
+            200,
+            json!([{"id": "r1", "name": "admin", "composite": true}]),
+        ),
+        Route::json(
+            "/roles-by-id/r1/composites",
+            200,
+            json!([
+                {"name": "user", "clientRole": false},
+                {"name": "manage-users", "clientRole": true, "containerId": "c1"}
+            ]),
+        ),
+        Route::json(
+            "/clients/c1",
+            200,
+            json!({"id": "c1", "clientId": "realm-management"}),
+        ),
+    ];
+    let composites = |realm: serde_json::Value| {
+        json!({"roles": {"realm": [{
+            "name": "admin",
+            "composite": true,
+            "composites": {"realm": realm, "client": {"realm-management": ["manage-users"]}}
+        }]}})
+    };
+
+    assert!(plan(live.clone(), composites(json!(["user"])), false)
+        .await
+        .is_empty());
+    assert_eq!(
+        plan(live, composites(json!(["user", "auditor"])), false)
+            .await
+            .to_string(),
+        "! realm role admin (composites): not reconciled\n"
+    );
+}
+
+#[tokio::test]
+async fn client_scope_mappers_are_listed_as_unsupported() {
+    let live = vec![Route::json(
+        "/client-scopes",
+        200,
+        json!([{
+            "id": "s1",
+            "name": "waves",
+            "protocol": "openid-connect",
+            "protocolMappers": [{"id": "m1", "name": "audience", "protocolMapper": "oidc-audience-mapper"}]
+        }]),
+    )];
+    let scope = |mapper: &str| {
+        json!({"clientScopes": [{
+            "name": "waves",
+            "protocol": "openid-connect",
+            "protocolMappers": [{"name": mapper, "protocolMapper": "oidc-audience-mapper"}]
+        }]})
+    };
+
+    assert!(plan(live.clone(), scope("audience"), false)
+        .await
+        .is_empty());
+    assert_eq!(
+        plan(live, scope("waves-audience"), false).await.to_string(),
+        "! client scope waves (protocolMappers): not reconciled\n"
+    );
+}
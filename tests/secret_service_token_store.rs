@@ -0,0 +1,87 @@
+//! The Secret Service store against whatever provider runs on the session bus.
+//!
+//! The round trips need a provider, so they are ignored by default. Run them in a desktop
+//! session, or on a headless machine with gnome-keyring:
+//!
+//! ```sh
+//! dbus-run-session -- sh -c 'echo | gnome-keyring-daemon --unlock && \
+//!     cargo test --features secret-service --test secret_service_token_store -- --ignored'
+//! ```
+
+#![cfg(feature = "secret-service")]
+
+use chrono::Utc;
+use keycloak_oauth::client::{
+    CachedToken, ClientConfiguration, ClientError, SecretServiceTokenStore, TokenStore,
+};
+
+fn configuration(username: Option<&str>) -> ClientConfiguration {
+    ClientConfiguration {
+        server_url: Some("http://keycloak.test".into()),
+        realm: Some(format!("test-{}", std::process::id())),
+        client_id: Some("test-client".into()),
+        username: username.map(str::to_string),
+        ..ClientConfiguration::default()
+    }
+}
+
+fn token(access_token: &str) -> CachedToken {
+    CachedToken {
+        access_token: access_token.into(),
+        expires_at: Utc::now() + chrono::Duration::seconds(300),
+        refresh_token: Some("refresh".into()),
+    }
+}
+
+#[test]
+fn unavailable_service_is_reported() {
+    assert!(matches!(
+        ClientError::from(secret_service::Error::Unavailable),
+        ClientError::SecretServiceUnavailable
+    ));
+    assert!(matches!(
+        ClientError::from(secret_service::Error::NoResult),
+        ClientError::SecretServiceError(_)
+    ));
+}
+
+#[test]
+fn from_config_needs_an_account() {
+    assert!(matches!(
+        SecretServiceTokenStore::from_config(&configuration(None)),
+        Err(ClientError::MissingConfigError("username"))
+    ));
+    assert!(SecretServiceTokenStore::from_config(&configuration(Some("alice"))).is_ok());
+}
+
+#[tokio::test]
+#[ignore = "needs a Secret Service provider on the session bus"]
+async fn accounts_are_stored_side_by_side() {
+    assert!(
+        SecretServiceTokenStore::is_available().await,
+        "no Secret Service provider on the session bus"
+    );
+    let config = configuration(None);
+    let alice = SecretServiceTokenStore::for_account(&config, "alice");
+    let bob = SecretServiceTokenStore::for_account(&config, "bob");
+
+    alice.save(&token("alice-token")).await.unwrap();
+    bob.save(&token("bob-token")).await.unwrap();
+    assert_eq!(
+        alice.load().await.unwrap().unwrap().access_token,
+        "alice-token"
+    );
+    assert_eq!(bob.load().await.unwrap().unwrap().access_token, "bob-token");
+
+    // saving again replaces the item instead of adding one
+    alice.save(&token("alice-token-2")).await.unwrap();
+    assert_eq!(
+        alice.load().await.unwrap().unwrap().access_token,
+        "alice-token-2"
+    );
+
+    alice.clear().await.unwrap();
+    assert!(alice.load().await.unwrap().is_none());
+    assert!(bob.load().await.unwrap().is_some());
+    bob.clear().await.unwrap();
+}
@@ -0,0 +1,108 @@
+//! Concurrent refreshes of one token reach Keycloak once, within a process and across clients
+//! sharing a token file.
+
+mod common;
+
+use std::{path::PathBuf, sync::Arc, time::Duration};
+
+use chrono::Utc;
+use common::*;
+use keycloak_oauth::client::{
+    CachedToken, FileTokenStore, KeycloakClient, TokenStore, WithOwnerCredentials,
+};
+use serde_json::json;
+
+fn expired_token() -> CachedToken {
+    CachedToken {
+        access_token: "stale".into(),
+        expires_at: Utc::now() - chrono::Duration::seconds(10),
+        refresh_token: Some("refresh".into()),
+    }
+}
+
+fn token_route() -> Route {
+    Route::json(
+        "/token",
+        200,
+        json!({
+            "access_token": "refreshed",
+            "token_type": "Bearer",
+            "expires_in": 300,
+            "refresh_token": "rotated"
+        }),
+    )
+    // long enough for every caller to find the stale token before the first refresh ends
+    .with_delay(Duration::from_millis(200))
+}
+
+fn token_requests(requests: &Requests) -> usize {
+    requests
+        .lines()
+        .iter()
+        .filter(|line| line.ends_with("/token"))
+        .count()
+}
+
+/// A fresh token file path in the system's temp directory.
+fn token_path(name: &str) -> PathBuf {
+    let dir = std::env::temp_dir().join(format!("keycloak-oauth-{}-{}", name, std::process::id()));
+    let _ = std::fs::remove_dir_all(&dir);
+    dir.join("token.json")
+}
+
+/// Runs `refresh_if_needed` on every client at once and returns the tokens they got.
+async fn refresh_concurrently(
+    clients: Vec<Arc<KeycloakClient<WithOwnerCredentials>>>,
+) -> Vec<String> {
+    let tasks: Vec<_> = clients
+        .into_iter()
+        .map(|client| {
+            tokio::spawn(async move {
+                client
+                    .refresh_if_needed(chrono::Duration::seconds(30), None)
+                    .await
+            })
+        })
+        .collect();
+    let mut tokens = Vec::new();
+    for task in tasks {
+        tokens.push(task.await.unwrap().unwrap());
+    }
+    tokens
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn concurrent_refreshes_share_one_request() {
+    let (server, requests) = recording_stub_server(vec![token_route()]).await;
+    let client = Arc::new(password_client(configuration(&server)));
+    client.store.save(&expired_token()).await.unwrap();
+
+    let tokens = refresh_concurrently(vec![client; 8]).await;
+
+    assert_eq!(tokens, vec!["refreshed"; 8]);
+    assert_eq!(token_requests(&requests), 1);
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn clients_sharing_a_token_file_refresh_once() {
+    let (server, requests) = recording_stub_server(vec![token_route()]).await;
+    let path = token_path("shared-refresh");
+    FileTokenStore::new(&path)
+        .save(&expired_token())
+        .await
+        .unwrap();
+    // separate clients only have the file lock in common, like separate processes
+    let clients: Vec<_> = (0..4)
+        .map(|_| {
+            let client = password_client(configuration(&server));
+            Arc::new(client.with_token_store(FileTokenStore::new(&path)))
+        })
+        .collect();
+
+    let tokens = refresh_concurrently(clients).await;
+
+    assert_eq!(tokens, vec!["refreshed"; 4]);
+    assert_eq!(token_requests(&requests), 1);
+    let stored = FileTokenStore::new(&path).load().await.unwrap().unwrap();
+    assert_eq!(stored.refresh_token.as_deref(), Some("rotated"));
+}
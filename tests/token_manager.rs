@@ -0,0 +1,217 @@
+//! `TokenManager` keeps a token published for every kind of client.
+
+mod common;
+
+use std::time::{Duration, Instant};
+
+use chrono::Utc;
+use common::{tokens::*, *};
+use jsonwebtoken::Algorithm;
+use keycloak_oauth::client::{
+    CachedToken, NoopTokenStore, RefreshPolicy, TokenManager, TokenState,
+};
+use serde_json::json;
+use tokio::sync::watch;
+
+fn policy() -> RefreshPolicy {
+    RefreshPolicy::default()
+        .with_refresh_fraction(0.5)
+        .with_backoff(Duration::from_millis(10), Duration::from_millis(100))
+}
+
+#[tokio::test]
+async fn client_credentials_tokens_are_granted_again() {
+    let server = stub_server(vec![Route::json(
+        "/token",
+        200,
+        json!({"access_token": "granted", "token_type": "Bearer", "expires_in": 1}),
+    )])
+    .await;
+    let manager = TokenManager::start(service_client(configuration(&server)), policy()).await;
+    let first = manager.current();
+    assert_eq!(first.access_token(), Some("granted"));
+
+    // the grant issues no refresh token, so outliving the first token takes a new grant
+    let mut tokens = manager.subscribe();
+    tokio::time::timeout(Duration::from_secs(5), tokens.changed())
+        .await
+        .expect("no token published after the first one")
+        .unwrap();
+    let second = tokens.borrow().clone();
+    assert!(matches!(second, TokenState::Active { .. }));
+    assert_ne!(second, first);
+}
+
+#[tokio::test]
+async fn user_flows_without_a_token_require_a_sign_in() {
+    let server = stub_server(vec![]).await;
+    let manager = TokenManager::start(password_client(configuration(&server)), policy()).await;
+    assert_eq!(manager.current(), TokenState::ReauthenticationRequired);
+    assert!(manager
+        .access_token()
+        .unwrap_err()
+        .requires_reauthentication());
+}
+
+fn cached(access_token: &str, expires_in: chrono::Duration) -> CachedToken {
+    CachedToken {
+        access_token: access_token.into(),
+        expires_at: Utc::now() + expires_in,
+        refresh_token: Some("refresh".into()),
+    }
+}
+
+fn refreshed() -> serde_json::Value {
+    json!({
+        "access_token": "refreshed",
+        "token_type": "Bearer",
+        "expires_in": 300,
+        "refresh_token": "refresh-2"
+    })
+}
+
+fn token_requests(requests: &Requests) -> usize {
+    requests
+        .lines()
+        .iter()
+        .filter(|line| line.ends_with("/token"))
+        .count()
+}
+
+/// Waits up to five seconds for `tokens` to publish a state matching `wanted`.
+async fn published(
+    tokens: &mut watch::Receiver<TokenState>,
+    wanted: impl Fn(&TokenState) -> bool,
+) -> TokenState {
+    tokio::time::timeout(
+        Duration::from_secs(5),
+        tokens.wait_for(|state| wanted(state)),
+    )
+    .await
+    .expect("the state was not published")
+    .unwrap()
+    .clone()
+}
+
+#[tokio::test]
+async fn tokens_are_refreshed_after_the_refresh_fraction() {
+    let (server, requests) =
+        recording_stub_server(vec![Route::json("/token", 200, refreshed())]).await;
+    let client = password_client(configuration(&server));
+    client
+        .store
+        .save(&cached("stale", chrono::Duration::seconds(2)))
+        .await
+        .unwrap();
+
+    let started = Instant::now();
+    let manager = TokenManager::start(client, policy()).await;
+    assert_eq!(manager.current().access_token(), Some("stale"));
+    tokio::time::sleep(Duration::from_millis(500)).await;
+    assert_eq!(token_requests(&requests), 0, "refreshed too early");
+
+    let mut tokens = manager.subscribe();
+    published(&mut tokens, |state| {
+        state.access_token() == Some("refreshed")
+    })
+    .await;
+    // half of the two second lifetime
+    assert!(started.elapsed() >= Duration::from_millis(900));
+    assert_eq!(token_requests(&requests), 1);
+}
+
+#[tokio::test]
+async fn failed_refreshes_back_off() {
+    let (server, requests) = recording_stub_server(vec![Route::json(
+        "/token",
+        503,
+        json!({"error": "temporarily_unavailable"}),
+    )
+    .then_json(503, json!({"error": "temporarily_unavailable"}))
+    .then_json(200, refreshed())])
+    .await;
+    let client = password_client(configuration(&server));
+    client
+        .store
+        .save(&cached("stale", chrono::Duration::zero()))
+        .await
+        .unwrap();
+
+    let started = Instant::now();
+    let manager = TokenManager::start(
+        client,
+        policy().with_backoff(Duration::from_millis(100), Duration::from_secs(1)),
+    )
+    .await;
+    let mut tokens = manager.subscribe();
+    published(&mut tokens, |state| {
+        state.access_token() == Some("refreshed")
+    })
+    .await;
+
+    // waited 100ms, then 200ms, between the three attempts
+    assert!(started.elapsed() >= Duration::from_millis(300));
+    assert_eq!(token_requests(&requests), 3);
+}
+
+#[tokio::test]
+async fn resume_picks_up_a_new_sign_in_after_a_rejected_refresh() {
+    let server = stub_server(vec![Route::json(
+        "/token",
+        400,
+        json!({"error": "invalid_grant", "error_description": "Session not active"}),
+    )])
+    .await;
+    let client = password_client(configuration(&server));
+    client
+        .store
+        .save(&cached("stale", chrono::Duration::zero()))
+        .await
+        .unwrap();
+
+    let manager = TokenManager::start(client, policy()).await;
+    let mut tokens = manager.subscribe();
+    published(&mut tokens, |state| {
+        *state == TokenState::ReauthenticationRequired
+    })
+    .await;
+
+    // the user signs in again
+    manager
+        .client()
+        .store
+        .save(&cached("signed-in", chrono::Duration::seconds(300)))
+        .await
+        .unwrap();
+    manager.resume();
+    published(&mut tokens, |state| {
+        state.access_token() == Some("signed-in")
+    })
+    .await;
+}
+
+#[tokio::test]
+async fn grants_without_a_cache_are_scheduled_by_their_expiry() {
+    let claims = claims();
+    let exp = claims["exp"].as_i64().unwrap();
+    let token = mint(Algorithm::RS256, &claims);
+    let (server, requests) = recording_stub_server(vec![Route::json(
+        "/token",
+        200,
+        json!({"access_token": token, "token_type": "Bearer", "expires_in": 300}),
+    )])
+    .await;
+    let client = service_client(configuration(&server)).with_token_store(NoopTokenStore);
+
+    let manager = TokenManager::start(client, policy()).await;
+    let tokens = manager.subscribe();
+    tokio::time::sleep(Duration::from_millis(500)).await;
+
+    assert!(!tokens.has_changed().unwrap());
+    assert!(matches!(
+        manager.current(),
+        TokenState::Active { access_token, expires_at }
+            if access_token == token && expires_at.timestamp() == exp
+    ));
+    assert_eq!(token_requests(&requests), 1);
+}
@@ -0,0 +1,113 @@
+//! The memory, file and no-op token stores, and the store a client picks from its configuration.
+
+mod common;
+
+use std::path::PathBuf;
+
+use chrono::{SubsecRound, Utc};
+use common::*;
+use keycloak_oauth::client::{
+    CachedToken, ClientConfiguration, FileTokenStore, MemoryTokenStore, NoopTokenStore, TokenStore,
+};
+
+/// A fresh token file path in the system's temp directory.
+fn token_path(name: &str) -> PathBuf {
+    let dir = std::env::temp_dir().join(format!("keycloak-oauth-{}-{}", name, std::process::id()));
+    let _ = std::fs::remove_dir_all(&dir);
+    dir.join("token.json")
+}
+
+fn token() -> CachedToken {
+    CachedToken {
+        access_token: "access".into(),
+        expires_at: Utc::now().trunc_subsecs(0) + chrono::Duration::seconds(300),
+        refresh_token: Some("refresh".into()),
+    }
+}
+
+/// Saves `token()`, loads it back, then clears the store.
+async fn round_trip(store: &dyn TokenStore) {
+    let token = token();
+    store.save(&token).await.unwrap();
+
+    let loaded = store.load().await.unwrap().unwrap();
+    assert_eq!(loaded.access_token, "access");
+    assert_eq!(loaded.refresh_token.as_deref(), Some("refresh"));
+    assert_eq!(loaded.expires_at, token.expires_at);
+
+    store.clear().await.unwrap();
+    assert!(store.load().await.unwrap().is_none());
+}
+
+#[tokio::test]
+async fn memory_store_round_trip() {
+    let store = MemoryTokenStore::new();
+    assert!(store.load().await.unwrap().is_none());
+    round_trip(&store).await;
+}
+
+#[tokio::test]
+async fn file_store_round_trip() {
+    let path = token_path("file-round-trip");
+    let store = FileTokenStore::new(&path);
+    round_trip(&store).await;
+    assert!(!path.exists());
+}
+
+#[tokio::test]
+async fn missing_file_is_no_token() {
+    let store = FileTokenStore::new(token_path("file-missing"));
+    assert!(store.load().await.unwrap().is_none());
+    // clearing what is not there is not an error either
+    store.clear().await.unwrap();
+}
+
+#[tokio::test]
+async fn file_store_survives_the_client() {
+    let path = token_path("file-reopen");
+    FileTokenStore::new(&path).save(&token()).await.unwrap();
+    let loaded = FileTokenStore::new(&path).load().await.unwrap().unwrap();
+    assert_eq!(loaded.access_token, "access");
+}
+
+#[tokio::test]
+async fn noop_store_keeps_nothing() {
+    let store = NoopTokenStore;
+    store.save(&token()).await.unwrap();
+    assert!(store.load().await.unwrap().is_none());
+    store.clear().await.unwrap();
+}
+
+#[tokio::test]
+async fn clients_with_a_cache_path_use_a_file() {
+    let path = token_path("default-file");
+    let client = password_client(ClientConfiguration {
+        token_cache_path: Some(path.to_string_lossy().into_owned()),
+        ..configuration("http://keycloak")
+    });
+    client.store.save(&token()).await.unwrap();
+    assert!(path.exists());
+
+    let reopened = password_client(ClientConfiguration {
+        token_cache_path: Some(path.to_string_lossy().into_owned()),
+        ..configuration("http://keycloak")
+    });
+    assert_eq!(
+        reopened.load_cached_token().await.unwrap().access_token,
+        "access"
+    );
+}
+
+#[tokio::test]
+async fn clients_without_a_cache_path_keep_tokens_in_memory() {
+    let client = password_client(configuration("http://keycloak"));
+    client.store.save(&token()).await.unwrap();
+    assert_eq!(
+        client.load_cached_token().await.unwrap().access_token,
+        "access"
+    );
+
+    // another client does not see it
+    let other = password_client(configuration("http://keycloak"));
+    assert!(other.store.load().await.unwrap().is_none());
+}
@@ -0,0 +1,143 @@
+//! Locally minted tokens verified against a static JWKS.
+
+mod common;
+
+use common::{tokens::*, *};
+use jsonwebtoken::Algorithm;
+use keycloak_oauth::client::{
+    AppConfigBuilder, ClientConfiguration, DeviceCodeCredential, KeycloakClaims, KeycloakClient,
+    OpenIdConfiguration, VerifyJwtError, WithDeviceCredentials,
+};
+use serde_json::{json, Value};
+
+/// `claims()` with `changes` merged in.
+fn claims_with(changes: Value) -> Value {
+    let mut claims = claims();
+    for (key, value) in changes.as_object().unwrap() {
+        claims[key] = value.clone();
+    }
+    claims
+}
+
+fn is_rejected(result: Result<jsonwebtoken::TokenData<KeycloakClaims>, VerifyJwtError>) -> bool {
+    matches!(result, Err(VerifyJwtError::JwtDecodeError(_)))
+}
+
+#[tokio::test]
+async fn rs256_and_es256_tokens_are_accepted() {
+    for alg in [Algorithm::RS256, Algorithm::ES256] {
+        let token = mint(alg, &claims());
+        let claims = verifier()
+            .verify::<KeycloakClaims>(&token)
+            .await
+            .unwrap()
+            .claims;
+        assert_eq!(claims.preferred_username.as_deref(), Some("alice"));
+        assert_eq!(claims.realm_roles(), ["user"]);
+    }
+}
+
+#[tokio::test]
+async fn expired_token_is_rejected() {
+    // past jsonwebtoken's 60 second leeway
+    let expired = claims_with(json!({ "exp": chrono::Utc::now().timestamp() - 120 }));
+    let result = verifier().verify(&mint(Algorithm::ES256, &expired)).await;
+    assert!(is_rejected(result));
+}
+
+#[tokio::test]
+async fn wrong_issuer_is_rejected() {
+    let other_realm = claims_with(json!({ "iss": "http://keycloak/realms/other" }));
+    let result = verifier()
+        .verify(&mint(Algorithm::RS256, &other_realm))
+        .await;
+    assert!(is_rejected(result));
+}
+
+#[tokio::test]
+async fn wrong_audience_is_rejected() {
+    let other_client = claims_with(json!({ "aud": ["account", "other-client"] }));
+    let token = mint(Algorithm::RS256, &other_client);
+    assert!(is_rejected(verifier().verify(&token).await));
+
+    // unless the verifier explicitly accepts any audience
+    assert!(verifier()
+        .any_audience()
+        .verify::<KeycloakClaims>(&token)
+        .await
+        .is_ok());
+}
+
+#[tokio::test]
+async fn verifier_without_audience_rejects_every_token() {
+    let mut verifier = verifier();
+    verifier.audience.clear();
+    let result = verifier
+        .verify::<KeycloakClaims>(&mint(Algorithm::RS256, &claims()))
+        .await;
+    assert!(matches!(result, Err(VerifyJwtError::NoAudienceError)));
+}
+
+#[tokio::test]
+async fn disallowed_algorithm_is_rejected() {
+    let token = mint(Algorithm::ES256, &claims());
+    let result = verifier()
+        .allowed_algorithms(&[Algorithm::RS256])
+        .verify::<KeycloakClaims>(&token)
+        .await;
+    assert!(matches!(
+        result,
+        Err(VerifyJwtError::DisallowedAlgorithmError(Algorithm::ES256))
+    ));
+}
+
+#[tokio::test]
+async fn key_for_another_algorithm_is_not_used() {
+    // signed with the RSA key but announced as PS256, which the RS256 key does not declare
+    let token = mint(Algorithm::PS256, &claims());
+    let result = verifier()
+        .allowed_algorithms(&[Algorithm::PS256])
+        .verify::<KeycloakClaims>(&token)
+        .await;
+    assert!(matches!(
+        result,
+        Err(VerifyJwtError::InvalidKeyFormatError(_))
+    ));
+}
+
+#[tokio::test]
+async fn clients_from_discovery_verify_for_their_client_id() {
+    let server = stub_server(vec![Route::json("/certs", 200, json!({ "keys": jwks() }))]).await;
+    // nothing but what discovery provides, no client id in particular
+    let mut configuration = ClientConfiguration::default();
+    configuration.apply_discovery(OpenIdConfiguration {
+        issuer: ISSUER.into(),
+        authorization_endpoint: format!("{}/auth", server),
+        token_endpoint: format!("{}/token", server),
+        jwks_uri: format!("{}/certs", server),
+        device_authorization_endpoint: Some(format!("{}/auth/device", server)),
+        introspection_endpoint: None,
+        revocation_endpoint: None,
+        end_session_endpoint: None,
+        userinfo_endpoint: None,
+        grant_types_supported: Vec::new(),
+        id_token_signing_alg_values_supported: vec!["RS256".into(), "ES256".into()],
+    });
+    let app_config = AppConfigBuilder::new(CLIENT_ID)
+        .configuration(configuration)
+        .with_device_code_credentials(DeviceCodeCredential {
+            client_id: CLIENT_ID.to_string(),
+        })
+        .build()
+        .unwrap();
+    let client = KeycloakClient::<WithDeviceCredentials>::try_from(app_config).unwrap();
+
+    let claims = client
+        .token_verifier()
+        .unwrap()
+        .verify::<KeycloakClaims>(&mint(Algorithm::RS256, &claims()))
+        .await
+        .unwrap()
+        .claims;
+    assert_eq!(claims.preferred_username.as_deref(), Some("alice"));
+}